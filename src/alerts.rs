@@ -0,0 +1,285 @@
+//! Alerting on block unavailability and client failures.
+//!
+//! # Flow
+//!
+//! * Clients raise alerts on failed block confidence, repeated RPC node failover,
+//! finality verification failure, application data reconstruction failure
+//! and RPC nodes diverging on the skipped block header or being unavailable for the header quorum
+//! * Each alert is posted as JSON payload to all configured webhooks
+//! * Failed posts are retried per webhook with exponential back-off, so slow webhook doesn't delay the others
+//!
+//! # Notes
+//!
+//! In case no webhooks are configured, alerts are only logged.
+//! On shutdown, alerts raised before the shutdown are still posted, waiting up to [`SHUTDOWN_TIMEOUT`] for the webhooks.
+
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use hyper::{client::HttpConnector, header::CONTENT_TYPE, Body, Request};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+use tokio::{sync::mpsc, task::JoinSet};
+use tracing::{error, info, warn};
+
+use crate::{shutdown::Controller, types::AlertsConfig};
+
+/// Maximum time to wait for the queued alerts to be posted on shutdown
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Alert posted to the configured webhooks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "alert", rename_all = "kebab-case")]
+pub enum Alert {
+	/// Block confidence is not achieved
+	ConfidenceFailed { block_number: u32 },
+	/// Connection with the RPC node failed repeatedly, and client switched to another node
+	RpcFailover { host: String, failovers: u32 },
+	/// Finality of the received block header cannot be verified
+	FinalityVerificationFailed { block_number: u32, error: String },
+	/// Application data cannot be reconstructed from the verified block
+	AppDataReconstructionFailed {
+		block_number: u32,
+		app_id: u32,
+		error: String,
+	},
+	/// Configured RPC nodes reported different hashes of the skipped block header.
+	/// Hash of the header from the connected node is followed by the hashes reported by the other nodes.
+	HeaderQuorumFailed {
+		block_number: u32,
+		hash: String,
+		hashes: BTreeMap<String, String>,
+	},
+	/// Not enough configured RPC nodes responded with the hash of the skipped block header,
+	/// so the header cannot be emitted until the nodes are reachable again
+	HeaderQuorumUnavailable { block_number: u32, error: String },
+}
+
+/// Channel used to raise alerts.
+/// Alerts are best effort, so send errors can be ignored.
+pub type AlertSender = mpsc::UnboundedSender<Alert>;
+
+type HttpClient = hyper::Client<HttpsConnector<HttpConnector>>;
+
+async fn post(client: &HttpClient, url: &str, alert: &Alert) -> Result<()> {
+	let body = serde_json::to_vec(alert).wrap_err("Failed to serialize alert")?;
+	let request = Request::post(url)
+		.header(CONTENT_TYPE, "application/json")
+		.body(Body::from(body))
+		.wrap_err("Failed to create webhook request")?;
+
+	let response = client
+		.request(request)
+		.await
+		.wrap_err("Webhook request failed")?;
+
+	if !response.status().is_success() {
+		return Err(eyre!("Webhook responded with {}", response.status()));
+	}
+	Ok(())
+}
+
+/// Posts alerts to the webhook in order, retrying failed posts with exponential back-off.
+async fn deliver(
+	client: HttpClient,
+	url: String,
+	retries: u32,
+	retry_delay: Duration,
+	mut alert_receiver: mpsc::UnboundedReceiver<Alert>,
+) {
+	while let Some(alert) = alert_receiver.recv().await {
+		let mut attempt = 0;
+		while let Err(error) = post(&client, &url, &alert).await {
+			if attempt >= retries {
+				error!(url, "Failed to post alert {alert:?}: {error:#}");
+				break;
+			}
+			let delay = retry_delay.saturating_mul(2u32.saturating_pow(attempt));
+			warn!(
+				url,
+				"Failed to post alert, retrying in {delay:?}: {error:#}"
+			);
+			tokio::time::sleep(delay).await;
+			attempt += 1;
+		}
+	}
+}
+
+/// Runs alerts.
+///
+/// # Arguments
+///
+/// * `cfg` - Alerts configuration
+/// * `alert_receiver` - Channel used to receive raised alerts
+/// * `shutdown` - Shutdown controller, delayed until queued alerts are posted
+pub async fn run(
+	cfg: AlertsConfig,
+	mut alert_receiver: mpsc::UnboundedReceiver<Alert>,
+	shutdown: Controller<String>,
+) {
+	info!("Starting alerts...");
+
+	// shutdown will wait as long as this token is not dropped
+	let Ok(_delay_token) = shutdown.delay_token() else {
+		return;
+	};
+
+	let connector = HttpsConnectorBuilder::new()
+		.with_native_roots()
+		.https_or_http()
+		.enable_http1()
+		.build();
+	let client = hyper::Client::builder().build(connector);
+
+	// Each webhook has its own queue, so retries are not blocking other webhooks
+	let mut deliveries = JoinSet::new();
+	let webhooks = cfg
+		.webhooks
+		.into_iter()
+		.map(|url| {
+			let (sender, receiver) = mpsc::unbounded_channel();
+			let (client, retries, delay) = (client.clone(), cfg.retries, cfg.retry_delay);
+			deliveries.spawn(deliver(client, url, retries, delay, receiver));
+			sender
+		})
+		.collect::<Vec<_>>();
+
+	let raise = |alert: Alert| {
+		warn!("Alert raised: {alert:?}");
+		for webhook in &webhooks {
+			let _ = webhook.send(alert.clone());
+		}
+	};
+
+	loop {
+		tokio::select! {
+			alert = alert_receiver.recv() => match alert {
+				Some(alert) => raise(alert),
+				None => break,
+			},
+			_ = shutdown.triggered_shutdown() => {
+				info!("Shutdown triggered, posting queued alerts");
+				break;
+			},
+		}
+	}
+
+	// Failures are usually followed by the shutdown, so alerts raised before it are still posted
+	alert_receiver.close();
+	while let Some(alert) = alert_receiver.recv().await {
+		raise(alert);
+	}
+	// Closed webhook queues end the deliveries, once queued alerts are posted
+	drop(raise);
+	drop(webhooks);
+	let drained = async { while deliveries.join_next().await.is_some() {} };
+	if tokio::time::timeout(SHUTDOWN_TIMEOUT, drained)
+		.await
+		.is_err()
+	{
+		warn!("Queued alerts are not posted in {SHUTDOWN_TIMEOUT:?}, dropping them");
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{run, Alert};
+	use crate::{shutdown::Controller, types::AlertsConfig};
+	use hyper::{
+		service::{make_service_fn, service_fn},
+		Body, Request, Response, Server, StatusCode,
+	};
+	use std::{
+		convert::Infallible,
+		sync::{
+			atomic::{AtomicU32, Ordering},
+			Arc,
+		},
+		time::Duration,
+	};
+	use tokio::sync::mpsc;
+
+	// Webhook server, failing the first post and forwarding bodies of the successful posts
+	fn webhook(body_sender: mpsc::UnboundedSender<hyper::body::Bytes>) -> String {
+		let attempts = Arc::new(AtomicU32::new(0));
+
+		let make_service = make_service_fn(move |_| {
+			let (body_sender, attempts) = (body_sender.clone(), attempts.clone());
+			async move {
+				Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+					let (body_sender, attempts) = (body_sender.clone(), attempts.clone());
+					async move {
+						let body = hyper::body::to_bytes(request.into_body()).await?;
+						// First post fails, so it has to be retried
+						let status = if attempts.fetch_add(1, Ordering::Relaxed) == 0 {
+							StatusCode::INTERNAL_SERVER_ERROR
+						} else {
+							body_sender.send(body).unwrap();
+							StatusCode::OK
+						};
+						let mut response = Response::new(Body::empty());
+						*response.status_mut() = status;
+						Ok::<_, hyper::Error>(response)
+					}
+				}))
+			}
+		});
+		let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+		let url = format!("http://{}/alerts", server.local_addr());
+		tokio::spawn(server);
+		url
+	}
+
+	fn config(url: String) -> AlertsConfig {
+		AlertsConfig {
+			webhooks: vec![url],
+			retries: 1,
+			retry_delay: Duration::from_millis(10),
+		}
+	}
+
+	#[tokio::test]
+	async fn alerts_are_posted_with_retries() {
+		let (body_sender, mut body_receiver) = mpsc::unbounded_channel();
+		let cfg = config(webhook(body_sender));
+		let (alert_sender, alert_receiver) = mpsc::unbounded_channel();
+		tokio::spawn(run(cfg, alert_receiver, Controller::new()));
+		alert_sender
+			.send(Alert::ConfidenceFailed { block_number: 5 })
+			.unwrap();
+
+		let body = body_receiver.recv().await.unwrap();
+		let alert: serde_json::Value = serde_json::from_slice(&body).unwrap();
+		let expected = serde_json::json!({ "alert": "confidence-failed", "block_number": 5 });
+		assert_eq!(alert, expected);
+	}
+
+	#[tokio::test]
+	async fn queued_alerts_are_posted_on_shutdown() {
+		let (body_sender, mut body_receiver) = mpsc::unbounded_channel();
+		let cfg = config(webhook(body_sender));
+		let (alert_sender, alert_receiver) = mpsc::unbounded_channel();
+		let shutdown = Controller::new();
+		let alerts = tokio::spawn(run(cfg, alert_receiver, shutdown.clone()));
+		tokio::task::yield_now().await;
+
+		// Failure alert is immediately followed by the shutdown
+		let alert = Alert::AppDataReconstructionFailed {
+			block_number: 5,
+			app_id: 1,
+			error: "Too many cells are missing".to_string(),
+		};
+		alert_sender.send(alert).unwrap();
+		shutdown
+			.trigger_shutdown("App client failed".to_string())
+			.unwrap();
+		shutdown.completed_shutdown().await;
+		alerts.await.unwrap();
+
+		let body = body_receiver.try_recv().unwrap();
+		let alert: serde_json::Value = serde_json::from_slice(&body).unwrap();
+		assert_eq!(alert["alert"], "app-data-reconstruction-failed");
+	}
+}
@@ -6,7 +6,7 @@
 //! * `/v1/status` - returns status of a latest processed block
 //! * `/v1/latest_block` - returns latest processed block
 //! * `/v1/confidence/{block_number}` - returns calculated confidence for a given block number
-//! * `/v1/appdata/{block_number}` - returns decoded extrinsic data for configured app_id and given block number
+//! * `/v1/appdata/{block_number}` - returns decoded extrinsic data for configured or requested app_id and given block number
 
 use crate::api::v2;
 use crate::data::Database;
@@ -52,11 +52,16 @@ impl<T: Database + Clone + Send + Sync + 'static> Server<T> {
 		let RuntimeConfig {
 			http_server_host: host,
 			http_server_port: port,
-			app_id,
+			app_ids,
 			..
 		} = self.cfg.clone();
 
-		let v1_api = v1::routes(self.db.clone(), app_id, self.state.clone());
+		let v1_api = v1::routes(
+			self.db.clone(),
+			app_ids,
+			self.state.clone(),
+			self.cfg.confidence_model,
+		);
 		let v2_api = v2::routes(
 			self.version.clone(),
 			self.network_version.clone(),
@@ -2,8 +2,8 @@ use super::types::{AppDataQuery, ClientResponse, ConfidenceResponse, LatestBlock
 use crate::{
 	api::v1::types::{Extrinsics, ExtrinsicsDataResponse},
 	data::{Database, Key},
-	types::{Mode, OptionBlockRange, State},
-	utils::calculate_confidence,
+	types::{resolve_app_id, ConfidenceModel, Mode, OptionBlockRange, State},
+	utils::block_confidence,
 };
 use avail_subxt::{
 	api::runtime_types::{da_control::pallet::Call, da_runtime::RuntimeCall},
@@ -23,19 +23,19 @@ fn serialised_confidence(block: u32, factor: f64) -> Option<String> {
 	Some(shifted.to_str_radix(10))
 }
 
-pub fn mode(app_id: Option<u32>) -> ClientResponse<Mode> {
-	ClientResponse::Normal(Mode::from(app_id))
+pub fn mode(app_ids: Vec<u32>) -> ClientResponse<Mode> {
+	ClientResponse::Normal(Mode::from(app_ids.as_slice()))
 }
 
 pub fn confidence(
 	block_num: u32,
 	db: impl Database,
 	state: Arc<Mutex<State>>,
+	confidence_model: ConfidenceModel,
 ) -> ClientResponse<ConfidenceResponse> {
 	info!("Got request for confidence for block {block_num}");
-	let res = match db.get(Key::VerifiedCellCount(block_num)) {
-		Ok(Some(count)) => {
-			let confidence = calculate_confidence(count);
+	let res = match block_confidence(&db, confidence_model, block_num) {
+		Ok(Some(confidence)) => {
 			let serialised_confidence = serialised_confidence(block_num, confidence);
 			ClientResponse::Normal(ConfidenceResponse {
 				block: block_num,
@@ -63,23 +63,22 @@ pub fn confidence(
 }
 
 pub fn status(
-	app_id: Option<u32>,
+	app_ids: Vec<u32>,
 	state: Arc<Mutex<State>>,
 	db: impl Database,
+	confidence_model: ConfidenceModel,
 ) -> ClientResponse<Status> {
 	let state = state.lock().unwrap();
 	let Some(last) = state.confidence_achieved.last() else {
 		return ClientResponse::NotFound;
 	};
-	let res = match db.get(Key::VerifiedCellCount(last)) {
-		Ok(Some(count)) => {
-			let confidence = calculate_confidence(count);
-			ClientResponse::Normal(Status {
-				block_num: last,
-				confidence,
-				app_id,
-			})
-		},
+	let res = match block_confidence(&db, confidence_model, last) {
+		Ok(Some(confidence)) => ClientResponse::Normal(Status {
+			block_num: last,
+			confidence,
+			app_id: resolve_app_id(&app_ids, None),
+			app_ids,
+		}),
 		Ok(None) => ClientResponse::NotFound,
 
 		Err(e) => ClientResponse::Error(e),
@@ -101,7 +100,7 @@ pub fn appdata(
 	block_num: u32,
 	query: AppDataQuery,
 	db: impl Database,
-	app_id: Option<u32>,
+	app_ids: Vec<u32>,
 	state: Arc<Mutex<State>>,
 ) -> ClientResponse<ExtrinsicsDataResponse> {
 	fn decode_app_data_to_extrinsics(
@@ -126,12 +125,13 @@ pub fn appdata(
 		}
 	}
 	info!("Got request for AppData for block {block_num}");
+	let Some(app_id) = resolve_app_id(&app_ids, query.app_id) else {
+		return ClientResponse::NotFound;
+	};
 	let state = state.lock().unwrap();
 	let last = state.confidence_achieved.last();
 	let decode = query.decode.unwrap_or(false);
-	let res = match decode_app_data_to_extrinsics(
-		db.get(Key::AppData(app_id.unwrap_or(0u32), block_num)),
-	) {
+	let res = match decode_app_data_to_extrinsics(db.get(Key::AppData(app_id, block_num))) {
 		Ok(Some(data)) => {
 			if !decode {
 				ClientResponse::Normal(ExtrinsicsDataResponse {
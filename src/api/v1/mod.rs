@@ -1,4 +1,7 @@
-use crate::{data::Database, types::State};
+use crate::{
+	data::Database,
+	types::{ConfidenceModel, State},
+};
 
 use self::types::AppDataQuery;
 use std::{
@@ -22,19 +25,26 @@ fn with_db<T: Database + Clone + Send>(
 	warp::any().map(move || db.clone())
 }
 
-fn with_app_id(
-	app_id: Option<u32>,
-) -> impl Filter<Extract = (Option<u32>,), Error = Infallible> + Clone {
-	warp::any().map(move || app_id)
+fn with_app_ids(
+	app_ids: Vec<u32>,
+) -> impl Filter<Extract = (Vec<u32>,), Error = Infallible> + Clone {
+	warp::any().map(move || app_ids.clone())
+}
+
+fn with_confidence_model(
+	confidence_model: ConfidenceModel,
+) -> impl Filter<Extract = (ConfidenceModel,), Error = Infallible> + Clone {
+	warp::any().map(move || confidence_model)
 }
 
 pub fn routes(
 	db: impl Database + Clone + Send,
-	app_id: Option<u32>,
+	app_ids: Vec<u32>,
 	state: Arc<Mutex<State>>,
+	confidence_model: ConfidenceModel,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
 	let mode = warp::path!("v1" / "mode")
-		.and(with_app_id(app_id))
+		.and(with_app_ids(app_ids.clone()))
 		.map(handlers::mode);
 
 	let latest_block = warp::path!("v1" / "latest_block")
@@ -44,19 +54,21 @@ pub fn routes(
 	let confidence = warp::path!("v1" / "confidence" / u32)
 		.and(with_db(db.clone()))
 		.and(with_state(state.clone()))
+		.and(with_confidence_model(confidence_model))
 		.map(handlers::confidence);
 
 	let appdata = (warp::path!("v1" / "appdata" / u32))
 		.and(warp::query::<AppDataQuery>())
 		.and(with_db(db.clone()))
-		.and(with_app_id(app_id))
+		.and(with_app_ids(app_ids.clone()))
 		.and(with_state(state.clone()))
 		.map(handlers::appdata);
 
 	let status = warp::path!("v1" / "status")
-		.and(with_app_id(app_id))
+		.and(with_app_ids(app_ids))
 		.and(with_state(state))
 		.and(with_db(db))
+		.and(with_confidence_model(confidence_model))
 		.map(handlers::status);
 
 	warp::get().and(mode.or(latest_block).or(confidence).or(appdata).or(status))
@@ -44,12 +44,17 @@ pub struct LatestBlockResponse {
 pub struct Status {
 	pub block_num: u32,
 	pub confidence: f64,
+	/// Set only if single application is configured
 	pub app_id: Option<u32>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub app_ids: Vec<u32>,
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct AppDataQuery {
 	pub decode: Option<bool>,
+	/// Application ID, required if more than one application is configured
+	pub app_id: Option<u32>,
 }
 
 impl<T: Send + Serialize> warp::Reply for ClientResponse<T> {
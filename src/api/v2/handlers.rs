@@ -2,26 +2,31 @@ use super::{
 	transactions,
 	types::{
 		block_status, filter_fields, Block, BlockStatus, DataQuery, DataResponse, DataTransaction,
-		Error, FieldsQueryParameter, Header, Status, SubmitResponse, Subscription, SubscriptionId,
-		Transaction, Version, WsClients,
+		Equivocations, Error, FieldsQueryParameter, Header, Justification, Status, SubmitResponse,
+		Subscription, SubscriptionId, Transaction, Version, WsClients,
 	},
 	ws,
 };
 use crate::{
 	api::v2::types::{ErrorCode, InternalServerError},
 	data::Database,
-	data::Key,
-	types::{RuntimeConfig, State},
-	utils::calculate_confidence,
+	data::{Key, KeyRange},
+	finality::Equivocation,
+	network::{rpc, SamplingReport},
+	types::{resolve_app_id, GrandpaJustification, RuntimeConfig, State},
+	utils::block_confidence,
 };
-use avail_subxt::primitives;
+use async_trait::async_trait;
+use avail_subxt::{primitives, utils::H256};
+use codec::Encode;
 use color_eyre::{eyre::eyre, Result};
 use hyper::StatusCode;
+use sp_core::blake2_256;
 use std::{
 	convert::Infallible,
 	sync::{Arc, Mutex},
 };
-use tracing::error;
+use tracing::{error, warn};
 use uuid::Uuid;
 use warp::{ws::Ws, Rejection, Reply};
 
@@ -101,36 +106,182 @@ pub async fn block(
 		return Err(Error::not_found());
 	};
 
-	let confidence = db
-		.get(Key::VerifiedCellCount(block_number))
-		.map_err(Error::internal_server_error)?
-		.map(calculate_confidence);
+	let confidence = block_confidence(&db, config.confidence_model, block_number)
+		.map_err(Error::internal_server_error)?;
 
 	Ok(Block::new(block_status, confidence))
 }
 
+/// Fetches block headers which are not stored, e.g. headers skipped by the warp sync
+#[async_trait]
+pub trait FetchHeader {
+	async fn fetch_header(&self, block_number: u32) -> Result<primitives::Header>;
+}
+
+#[async_trait]
+impl FetchHeader for rpc::Client {
+	async fn fetch_header(&self, block_number: u32) -> Result<primitives::Header> {
+		let (header, _) = self.get_header_by_block_number(block_number).await?;
+		Ok(header)
+	}
+}
+
+/// Maximum number of headers fetched to link the missing header to the stored header
+const MAX_LINKED_HEADERS: u32 = 256;
+
+/// Fetches the missing header, along with the headers up to the nearest stored header above it.
+/// Fetched headers are linked to the stored header by parent hashes, so only headers of the verified chain are stored.
+/// Returns `None` if there is no stored header close enough, or if the fetched headers don't link to it.
+async fn fetch_linked_header(
+	db: impl Database,
+	header_fetcher: &impl FetchHeader,
+	block_number: u32,
+	latest: u32,
+) -> Result<Option<primitives::Header>> {
+	let last = latest.min(block_number.saturating_add(MAX_LINKED_HEADERS));
+	let mut stored = None;
+	for number in (block_number + 1)..=last {
+		if let Some(header) = db.get::<primitives::Header>(Key::BlockHeader(number))? {
+			stored = Some(header);
+			break;
+		}
+	}
+	let Some(stored) = stored else {
+		return Ok(None);
+	};
+
+	let mut parent_hash = stored.parent_hash;
+	let mut headers = vec![];
+	for number in (block_number..stored.number).rev() {
+		let header = header_fetcher.fetch_header(number).await?;
+		let hash: H256 = Encode::using_encoded(&header, blake2_256).into();
+		if header.number != number || hash != parent_hash {
+			warn!(
+				block_number = number,
+				"Fetched header doesn't link to the stored header"
+			);
+			return Ok(None);
+		}
+		parent_hash = header.parent_hash;
+		headers.push(header);
+	}
+
+	for header in &headers {
+		db.put(Key::BlockHeader(header.number), header.clone())?;
+	}
+	Ok(headers.pop())
+}
+
 pub async fn block_header(
 	block_number: u32,
 	config: RuntimeConfig,
 	state: Arc<Mutex<State>>,
 	db: impl Database,
+	header_fetcher: impl FetchHeader,
 ) -> Result<Header, Error> {
+	let latest = {
+		let state = state.lock().expect("Lock should be acquired");
+
+		let Some(block_status) = block_status(&config.sync_start_block, &state, block_number)
+		else {
+			return Err(Error::not_found());
+		};
+
+		if state.header_pruned >= Some(block_number) {
+			return Err(Error::bad_request_unknown("Block header is pruned"));
+		}
+
+		if matches!(
+			block_status,
+			BlockStatus::Unavailable | BlockStatus::Pending | BlockStatus::VerifyingHeader
+		) {
+			return Err(Error::bad_request_unknown("Block header is not available"));
+		};
+		state.latest
+	};
+
+	let stored = db
+		.get::<primitives::Header>(Key::BlockHeader(block_number))
+		.map_err(Error::internal_server_error)?;
+
+	let header = match stored {
+		Some(header) => header,
+		// Headers are downloaded lazily, so the header is fetched and stored on the first request
+		None => fetch_linked_header(db, &header_fetcher, block_number, latest)
+			.await
+			.map_err(Error::internal_server_error)?
+			.ok_or_else(|| Error::bad_request_unknown("Block header is not available"))?,
+	};
+
+	header.try_into().map_err(Error::internal_server_error)
+}
+
+pub async fn block_sampling(
+	block_number: u32,
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database,
+) -> Result<SamplingReport, Error> {
+	let state = state.lock().expect("Lock should be acquired");
+
+	let Some(block_status) = block_status(&config.sync_start_block, &state, block_number) else {
+		return Err(Error::not_found());
+	};
+
+	if block_status == BlockStatus::Pruned {
+		return Err(Error::bad_request_unknown(
+			"Block sampling report is pruned",
+		));
+	}
+
+	db.get::<SamplingReport>(Key::SamplingReport(block_number))
+		.map_err(Error::internal_server_error)?
+		.ok_or_else(|| Error::bad_request_unknown("Block sampling report is not available"))
+}
+
+pub async fn block_justification(
+	block_number: u32,
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database,
+) -> Result<Justification, Error> {
 	let state = state.lock().expect("Lock should be acquired");
 
 	let Some(block_status) = block_status(&config.sync_start_block, &state, block_number) else {
 		return Err(Error::not_found());
 	};
 
+	if state.header_pruned >= Some(block_number) {
+		return Err(Error::bad_request_unknown("Block justification is pruned"));
+	}
+
 	if matches!(
 		block_status,
 		BlockStatus::Unavailable | BlockStatus::Pending | BlockStatus::VerifyingHeader
 	) {
-		return Err(Error::bad_request_unknown("Block header is not available"));
+		return Err(Error::bad_request_unknown(
+			"Block justification is not available",
+		));
 	};
 
-	db.get::<primitives::Header>(Key::BlockHeader(block_number))
-		.and_then(|header| header.ok_or_else(|| eyre!("Header not found")))
-		.and_then(|header| header.try_into())
+	// Only the blocks with verified finality have the justification stored
+	db.get::<GrandpaJustification>(Key::Justification(block_number))
+		.map_err(Error::internal_server_error)?
+		.map(|justification| Justification {
+			block_number,
+			justification,
+		})
+		.ok_or_else(|| Error::bad_request_unknown("Block justification is not available"))
+}
+
+pub async fn equivocations(db: impl Database) -> Result<Equivocations, Error> {
+	db.iter::<Equivocation>(KeyRange::Equivocation(0..=u32::MAX))
+		.and_then(|equivocations| {
+			equivocations
+				.map(|equivocation| equivocation.map(|(_, equivocation)| equivocation))
+				.collect::<Result<Vec<_>>>()
+		})
+		.map(|equivocations| Equivocations { equivocations })
 		.map_err(Error::internal_server_error)
 }
 
@@ -143,7 +294,7 @@ pub async fn block_data(
 ) -> Result<DataResponse, Error> {
 	let state = state.lock().expect("Lock should be acquired");
 
-	let Some(app_id) = config.app_id else {
+	let Some(app_id) = resolve_app_id(&config.app_ids, query.app_id) else {
 		return Err(Error::not_found());
 	};
 
@@ -151,6 +302,10 @@ pub async fn block_data(
 		return Err(Error::not_found());
 	};
 
+	if block_status == BlockStatus::Pruned || state.data_pruned >= Some(block_number) {
+		return Err(Error::bad_request_unknown("Block data is pruned"));
+	}
+
 	if block_status != BlockStatus::Finished {
 		return Err(Error::bad_request_unknown("Block data is not available"));
 	};
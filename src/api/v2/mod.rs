@@ -83,12 +83,14 @@ fn block_header_route(
 	config: RuntimeConfig,
 	state: Arc<Mutex<State>>,
 	db: impl Database + Clone + Send,
+	header_fetcher: impl handlers::FetchHeader + Clone + Send + Sync,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
 	warp::path!("v2" / "blocks" / u32 / "header")
 		.and(warp::get())
 		.and(warp::any().map(move || config.clone()))
 		.and(warp::any().map(move || state.clone()))
 		.and(with_db(db))
+		.and(warp::any().map(move || header_fetcher.clone()))
 		.then(handlers::block_header)
 		.map(log_internal_server_error)
 }
@@ -108,6 +110,44 @@ fn block_data_route(
 		.map(log_internal_server_error)
 }
 
+fn block_sampling_route(
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database + Clone + Send,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "blocks" / u32 / "sampling")
+		.and(warp::get())
+		.and(warp::any().map(move || config.clone()))
+		.and(warp::any().map(move || state.clone()))
+		.and(with_db(db))
+		.then(handlers::block_sampling)
+		.map(log_internal_server_error)
+}
+
+fn block_justification_route(
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database + Clone + Send,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "blocks" / u32 / "justification")
+		.and(warp::get())
+		.and(warp::any().map(move || config.clone()))
+		.and(warp::any().map(move || state.clone()))
+		.and(with_db(db))
+		.then(handlers::block_justification)
+		.map(log_internal_server_error)
+}
+
+fn equivocations_route(
+	db: impl Database + Clone + Send,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "equivocations")
+		.and(warp::get())
+		.and(with_db(db))
+		.then(handlers::equivocations)
+		.map(log_internal_server_error)
+}
+
 fn submit_route(
 	submitter: Option<Arc<impl transactions::Submit + Clone + Send + Sync>>,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
@@ -200,12 +240,13 @@ pub fn routes(
 		network_version,
 	};
 
-	let app_id = config.app_id.as_ref();
+	// Data is submitted with the first configured application ID
+	let app_id = config.app_ids.first();
 	let pair_signer = <PairSigner<AvailConfig, Pair>>::new(identity_config.avail_key_pair);
 
 	let submitter = app_id.map(|&app_id| {
 		Arc::new(transactions::Submitter {
-			rpc_client,
+			rpc_client: rpc_client.clone(),
 			app_id,
 			pair_signer,
 		})
@@ -218,8 +259,20 @@ pub fn routes(
 			config.clone(),
 			state.clone(),
 			db.clone(),
+			rpc_client,
 		))
 		.or(block_data_route(config.clone(), state.clone(), db.clone()))
+		.or(block_sampling_route(
+			config.clone(),
+			state.clone(),
+			db.clone(),
+		))
+		.or(block_justification_route(
+			config.clone(),
+			state.clone(),
+			db.clone(),
+		))
+		.or(equivocations_route(db.clone()))
 		.or(subscriptions_route(ws_clients.clone()))
 		.or(submit_route(submitter.clone()))
 		.or(ws_route(ws_clients, version, config, submitter, state))
@@ -228,15 +281,20 @@ pub fn routes(
 
 #[cfg(test)]
 mod tests {
-	use super::{transactions, types::Transaction};
+	use super::{handlers, transactions, types::Transaction};
 	use crate::{
 		api::v2::types::{
-			DataField, ErrorCode, SubmitResponse, Subscription, SubscriptionId, Topic, Version,
-			WsClients, WsError, WsResponse,
+			DataField, Equivocations, ErrorCode, SubmitResponse, Subscription, SubscriptionId,
+			Topic, Version, WsClients, WsError, WsResponse,
 		},
 		data::Key,
 		data::{mem_db, Database},
-		types::{BlockRange, OptionBlockRange, RuntimeConfig, State},
+		finality::Equivocation,
+		network::{CellSource, SampledCell, SamplingReport},
+		types::{
+			BlockRange, Commit, GrandpaJustification, OptionBlockRange, Precommit, RuntimeConfig,
+			SignedPrecommit, State,
+		},
 	};
 	use async_trait::async_trait;
 	use avail_subxt::utils::H256;
@@ -248,8 +306,13 @@ mod tests {
 		},
 		primitives::Header as DaHeader,
 	};
+	use codec::Encode;
 	use hyper::StatusCode;
 	use kate_recovery::matrix::Partition;
+	use sp_core::{
+		blake2_256,
+		ed25519::{Public, Signature},
+	};
 	use std::{
 		collections::HashSet,
 		str::FromStr,
@@ -304,7 +367,7 @@ mod tests {
 	#[tokio::test]
 	async fn status_route() {
 		let runtime_config = RuntimeConfig {
-			app_id: Some(1),
+			app_ids: vec![1],
 			sync_start_block: Some(10),
 			block_matrix_partition: Some(Partition {
 				number: 1,
@@ -336,7 +399,7 @@ mod tests {
 
 		let gen_hash = H256::default();
 		let expected = format!(
-			r#"{{"modes":["light","app","partition"],"app_id":1,"genesis_hash":"{:#x}","network":"{NETWORK}","blocks":{{"latest":30,"available":{{"first":20,"last":29}},"app_data":{{"first":20,"last":29}},"historical_sync":{{"synced":false,"available":{{"first":10,"last":19}},"app_data":{{"first":10,"last":18}}}}}},"partition":"1/10"}}"#,
+			r#"{{"modes":["light","app","partition"],"app_id":1,"app_ids":[1],"genesis_hash":"{:#x}","network":"{NETWORK}","blocks":{{"latest":30,"available":{{"first":20,"last":29}},"app_data":{{"first":20,"last":29}},"historical_sync":{{"synced":false,"available":{{"first":10,"last":19}},"app_data":{{"first":10,"last":18}}}}}},"partition":"1/10"}}"#,
 			gen_hash
 		);
 		assert_eq!(response.body(), &expected);
@@ -407,7 +470,7 @@ mod tests {
 		}));
 
 		let db = mem_db::MemoryDB::default();
-		let route = super::block_header_route(config, state, db);
+		let route = super::block_header_route(config, state, db, MockHeaderFetcher {});
 		let response = warp::test::request()
 			.method("GET")
 			.path(&format!("/v2/blocks/{block_number}/header"))
@@ -425,7 +488,7 @@ mod tests {
 			..Default::default()
 		}));
 		let db = mem_db::MemoryDB::default();
-		let route = super::block_header_route(config, state, db);
+		let route = super::block_header_route(config, state, db, MockHeaderFetcher {});
 		let response = warp::test::request()
 			.method("GET")
 			.path("/v2/blocks/11/header")
@@ -461,7 +524,7 @@ mod tests {
 		}));
 		let db = mem_db::MemoryDB::default();
 		_ = db.put(Key::BlockHeader(1), header());
-		let route = super::block_header_route(config, state, db);
+		let route = super::block_header_route(config, state, db, MockHeaderFetcher {});
 		let response = warp::test::request()
 			.method("GET")
 			.path("/v2/blocks/1/header")
@@ -473,6 +536,68 @@ mod tests {
 		);
 	}
 
+	#[derive(Clone)]
+	struct MockHeaderFetcher {}
+
+	#[async_trait]
+	impl handlers::FetchHeader for MockHeaderFetcher {
+		async fn fetch_header(&self, _: u32) -> color_eyre::Result<DaHeader> {
+			Ok(header())
+		}
+	}
+
+	fn header_state() -> Arc<Mutex<State>> {
+		Arc::new(Mutex::new(State {
+			latest: 2,
+			header_verified: Some(BlockRange { first: 1, last: 2 }),
+			..Default::default()
+		}))
+	}
+
+	#[tokio::test]
+	async fn block_header_route_fetches_missing_header() {
+		let config = RuntimeConfig::default();
+		let db = mem_db::MemoryDB::default();
+		let parent_hash: H256 = Encode::using_encoded(&header(), blake2_256).into();
+		let stored = DaHeader {
+			number: 2,
+			parent_hash,
+			..header()
+		};
+		_ = db.put(Key::BlockHeader(2), stored);
+		let route =
+			super::block_header_route(config, header_state(), db.clone(), MockHeaderFetcher {});
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/blocks/1/header")
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::OK);
+		let stored = db.get::<DaHeader>(Key::BlockHeader(1)).unwrap();
+		assert_eq!(stored.map(|header| header.number), Some(1));
+	}
+
+	#[tokio::test]
+	async fn block_header_route_rejects_unlinked_header() {
+		let config = RuntimeConfig::default();
+		let db = mem_db::MemoryDB::default();
+		let stored = DaHeader {
+			number: 2,
+			parent_hash: H256::repeat_byte(1),
+			..header()
+		};
+		_ = db.put(Key::BlockHeader(2), stored);
+		let route =
+			super::block_header_route(config, header_state(), db.clone(), MockHeaderFetcher {});
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/blocks/1/header")
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+		assert!(db.get::<DaHeader>(Key::BlockHeader(1)).unwrap().is_none());
+	}
+
 	#[test_case(0, r#"Block data is not available"#  ; "Block is unavailable")]
 	#[test_case(6, r#"Block data is not available"#  ; "Block is pending")]
 	#[test_case(8, r#"Block data is not available"#  ; "Block is in verifying-data state")]
@@ -481,7 +606,7 @@ mod tests {
 	#[tokio::test]
 	async fn block_data_route_bad_request(block_number: u32, expected: &str) {
 		let config = RuntimeConfig {
-			app_id: Some(1),
+			app_ids: vec![1],
 			sync_start_block: Some(1),
 			..Default::default()
 		};
@@ -524,7 +649,7 @@ mod tests {
 	#[tokio::test]
 	async fn block_data_route_ok_empty() {
 		let config = RuntimeConfig {
-			app_id: Some(1),
+			app_ids: vec![1],
 			..Default::default()
 		};
 		let state = Arc::new(Mutex::new(State {
@@ -548,10 +673,36 @@ mod tests {
 		);
 	}
 
+	#[test_case("/v2/blocks/5/data", StatusCode::NOT_FOUND ; "App ID is ambiguous")]
+	#[test_case("/v2/blocks/5/data?app_id=3", StatusCode::NOT_FOUND ; "App ID is not configured")]
+	#[test_case("/v2/blocks/5/data?app_id=2", StatusCode::OK ; "App ID is configured")]
+	#[tokio::test]
+	async fn block_data_route_multiple_apps(path: &str, expected: StatusCode) {
+		let config = RuntimeConfig {
+			app_ids: vec![1, 2],
+			..Default::default()
+		};
+		let state = Arc::new(Mutex::new(State {
+			latest: 10,
+			header_verified: Some(BlockRange::init(5)),
+			confidence_achieved: Some(BlockRange::init(5)),
+			data_verified: Some(BlockRange::init(5)),
+			..Default::default()
+		}));
+		let db = mem_db::MemoryDB::default();
+		let route = super::block_data_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path(path)
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), expected);
+	}
+
 	#[tokio::test]
 	async fn block_data_route_ok() {
 		let config = RuntimeConfig {
-			app_id: Some(1),
+			app_ids: vec![1],
 			..Default::default()
 		};
 		let state = Arc::new(Mutex::new(State {
@@ -587,11 +738,163 @@ mod tests {
 		);
 	}
 
+	#[tokio::test]
+	async fn block_sampling_route_ok() {
+		let config = RuntimeConfig::default();
+		let state = Arc::new(Mutex::new(State {
+			latest: 10,
+			header_verified: Some(BlockRange::init(5)),
+			confidence_achieved: Some(BlockRange::init(5)),
+			..Default::default()
+		}));
+		let db = mem_db::MemoryDB::default();
+		_ = db.put(
+			Key::SamplingReport(5),
+			SamplingReport {
+				block_number: 5,
+				seed: H256::zero(),
+				cells: vec![
+					SampledCell {
+						row: 0,
+						col: 1,
+						source: Some(CellSource::Dht),
+						verified: true,
+					},
+					SampledCell {
+						row: 1,
+						col: 2,
+						source: None,
+						verified: false,
+					},
+				],
+				dht_fetch_duration: 120,
+				rpc_fetch_duration: None,
+				retries: 0,
+			},
+		);
+		let route = super::block_sampling_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/blocks/5/sampling")
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(
+			response.body(),
+			r#"{"block_number":5,"seed":"0x0000000000000000000000000000000000000000000000000000000000000000","cells":[{"row":0,"col":1,"source":"dht","verified":true},{"row":1,"col":2,"source":null,"verified":false}],"dht_fetch_duration":120,"rpc_fetch_duration":null,"retries":0}"#
+		);
+	}
+
+	#[test_case(4, StatusCode::BAD_REQUEST ; "Sampling report is not stored")]
+	#[test_case(11, StatusCode::NOT_FOUND ; "Block is not processed")]
+	#[tokio::test]
+	async fn block_sampling_route_not_available(block_number: u32, expected: StatusCode) {
+		let config = RuntimeConfig::default();
+		let state = Arc::new(Mutex::new(State {
+			latest: 10,
+			header_verified: Some(BlockRange::init(4)),
+			..Default::default()
+		}));
+		let db = mem_db::MemoryDB::default();
+		let route = super::block_sampling_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path(&format!("/v2/blocks/{block_number}/sampling"))
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), expected);
+	}
+
+	#[tokio::test]
+	async fn block_justification_route_ok() {
+		let config = RuntimeConfig::default();
+		let state = Arc::new(Mutex::new(State {
+			latest: 10,
+			header_verified: Some(BlockRange::init(5)),
+			..Default::default()
+		}));
+		let db = mem_db::MemoryDB::default();
+		let justification = GrandpaJustification {
+			round: 1,
+			commit: Commit {
+				target_hash: H256::zero(),
+				target_number: 5,
+				precommits: vec![],
+			},
+			votes_ancestries: vec![],
+		};
+		_ = db.put(Key::Justification(5), justification);
+		let route = super::block_justification_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/blocks/5/justification")
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(
+			response.body(),
+			r#"{"block_number":5,"justification":"0x01000000000000000000000000000000000000000000000000000000000000000000000000000000050000000000"}"#
+		);
+	}
+
+	#[test_case(4, StatusCode::BAD_REQUEST ; "Justification is not stored")]
+	#[test_case(6, StatusCode::BAD_REQUEST ; "Header is not verified")]
+	#[test_case(11, StatusCode::NOT_FOUND ; "Block is not processed")]
+	#[tokio::test]
+	async fn block_justification_route_not_available(block_number: u32, expected: StatusCode) {
+		let config = RuntimeConfig::default();
+		let state = Arc::new(Mutex::new(State {
+			latest: 10,
+			header_verified: Some(BlockRange::init(4)),
+			..Default::default()
+		}));
+		let db = mem_db::MemoryDB::default();
+		let route = super::block_justification_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path(&format!("/v2/blocks/{block_number}/justification"))
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), expected);
+	}
+
+	#[tokio::test]
+	async fn equivocations_route() {
+		let db = mem_db::MemoryDB::default();
+		let signed_precommit = |target_number| SignedPrecommit {
+			precommit: Precommit {
+				target_hash: H256::zero(),
+				target_number,
+			},
+			signature: Signature::from_raw([0u8; 64]),
+			id: Public::from_raw([0u8; 32]),
+		};
+		let equivocation = Equivocation {
+			set_id: 1,
+			round: 1,
+			block_number: 5,
+			signer: Public::from_raw([0u8; 32]),
+			first: signed_precommit(5),
+			second: signed_precommit(6),
+		};
+		_ = db.put(Key::Equivocation(5), equivocation.clone());
+		let route = super::equivocations_route(db);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/equivocations")
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::OK);
+		let Equivocations { equivocations } = serde_json::from_slice(response.body()).unwrap();
+		assert_eq!(equivocations, vec![equivocation]);
+	}
+
 	fn all_topics() -> HashSet<Topic> {
 		vec![
 			Topic::HeaderVerified,
 			Topic::ConfidenceAchieved,
 			Topic::DataVerified,
+			Topic::JustificationVerified,
 		]
 		.into_iter()
 		.collect()
@@ -654,7 +957,7 @@ mod tests {
 		let clients = WsClients::default();
 		let route = super::subscriptions_route(clients.clone());
 
-		let body = r#"{"topics":["confidence-achieved","data-verified","header-verified"],"data_fields":["data","extrinsic"]}"#;
+		let body = r#"{"topics":["confidence-achieved","data-verified","header-verified","justification-verified"],"data_fields":["data","extrinsic"]}"#;
 		let response = warp::test::request()
 			.method("POST")
 			.body(body)
@@ -726,7 +1029,7 @@ mod tests {
 	#[tokio::test]
 	async fn ws_route_status() {
 		let config = RuntimeConfig {
-			app_id: Some(1),
+			app_ids: vec![1],
 			sync_start_block: Some(10),
 			block_matrix_partition: Some(Partition {
 				number: 1,
@@ -753,7 +1056,7 @@ mod tests {
 
 		let gen_hash = H256::default();
 		let expected = format!(
-			r#"{{"topic":"status","request_id":"363c71fc-90f7-4276-a5b6-bec688bf01e2","message":{{"modes":["light","app","partition"],"app_id":1,"genesis_hash":"{:x?}","network":"{NETWORK}","blocks":{{"latest":30,"available":{{"first":20,"last":29}},"app_data":{{"first":20,"last":29}},"historical_sync":{{"synced":false,"available":{{"first":10,"last":19}},"app_data":{{"first":10,"last":18}}}}}},"partition":"1/10"}}}}"#,
+			r#"{{"topic":"status","request_id":"363c71fc-90f7-4276-a5b6-bec688bf01e2","message":{{"modes":["light","app","partition"],"app_id":1,"app_ids":[1],"genesis_hash":"{:x?}","network":"{NETWORK}","blocks":{{"latest":30,"available":{{"first":20,"last":29}},"app_data":{{"first":20,"last":29}},"historical_sync":{{"synced":false,"available":{{"first":10,"last":19}},"app_data":{{"first":10,"last":18}}}}}},"partition":"1/10"}}}}"#,
 			gen_hash
 		);
 
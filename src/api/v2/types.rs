@@ -25,9 +25,14 @@ use warp::{
 };
 
 use crate::{
-	network::rpc::Event as RpcEvent,
+	finality::Equivocation,
+	network::{
+		rpc::{Event as RpcEvent, NodeHealth},
+		SamplingReport,
+	},
 	types::{
-		self, block_matrix_partition_format, BlockVerified, OptionBlockRange, RuntimeConfig, State,
+		self, block_matrix_partition_format, resolve_app_id, BlockVerified, GrandpaJustification,
+		OptionBlockRange, RuntimeConfig, State,
 	},
 	utils::decode_app_data,
 };
@@ -73,6 +78,14 @@ pub struct HistoricalSync {
 	pub app_data: Option<BlockRange>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RestoredBlocks {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub available: Option<BlockRange>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub app_data: Option<BlockRange>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Blocks {
 	pub latest: u32,
@@ -82,13 +95,18 @@ pub struct Blocks {
 	pub app_data: Option<BlockRange>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub historical_sync: Option<HistoricalSync>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub restored: Option<RestoredBlocks>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Status {
 	pub modes: Vec<Mode>,
+	/// Set only if single application is configured
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub app_id: Option<u32>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub app_ids: Vec<u32>,
 	pub genesis_hash: String,
 	pub network: String,
 	pub blocks: Blocks,
@@ -97,6 +115,8 @@ pub struct Status {
 		with = "block_matrix_partition_format"
 	)]
 	pub partition: Option<Partition>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub nodes: Vec<NodeHealth>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -167,22 +187,32 @@ impl Status {
 			app_data: state.sync_data_verified.as_ref().map(From::from),
 		});
 
+		let restored = (state.restored_confidence_achieved.is_some()
+			|| state.restored_data_verified.is_some())
+		.then(|| RestoredBlocks {
+			available: state.restored_confidence_achieved.as_ref().map(From::from),
+			app_data: state.restored_data_verified.as_ref().map(From::from),
+		});
+
 		let blocks = Blocks {
 			latest: state.latest,
 			available: state.confidence_achieved.as_ref().map(From::from),
 			app_data: state.data_verified.as_ref().map(From::from),
 			historical_sync,
+			restored,
 		};
 
 		let node = state.connected_node.clone();
 
 		Status {
 			modes: config.into(),
-			app_id: config.app_id,
+			app_id: resolve_app_id(&config.app_ids, None),
+			app_ids: config.app_ids.clone(),
 			genesis_hash: format!("{:?}", node.genesis_hash),
 			network: node.network(),
 			blocks,
 			partition: config.block_matrix_partition,
+			nodes: state.rpc_nodes.nodes.clone(),
 		}
 	}
 }
@@ -199,7 +229,7 @@ impl From<&RuntimeConfig> for Vec<Mode> {
 	fn from(value: &RuntimeConfig) -> Self {
 		let mut result: Vec<Mode> = vec![];
 		result.push(Mode::Light);
-		if value.app_id.is_some() {
+		if !value.app_ids.is_empty() {
 			result.push(Mode::App);
 		}
 		if value.block_matrix_partition.is_some() {
@@ -221,6 +251,7 @@ pub enum Topic {
 	HeaderVerified,
 	ConfidenceAchieved,
 	DataVerified,
+	JustificationVerified,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -251,6 +282,8 @@ pub enum BlockStatus {
 	VerifyingConfidence,
 	VerifyingData,
 	Finished,
+	Pruned,
+	ConfidenceFailed,
 }
 
 pub fn block_status(
@@ -263,20 +296,47 @@ pub fn block_status(
 	}
 
 	let first_block = state.header_verified.first().unwrap_or(state.latest);
-	let first_sync_block = sync_start_block.unwrap_or(first_block);
+	// Ranges restored from the previous run can start before the configured sync start block
+	let first_sync_block = sync_start_block
+		.iter()
+		.copied()
+		.chain(state.restored_header_verified.first())
+		.min()
+		.unwrap_or(first_block);
 
 	if block_number < first_sync_block {
 		return Some(BlockStatus::Unavailable);
 	}
 
+	if state.confidence_pruned >= Some(block_number) {
+		return Some(BlockStatus::Pruned);
+	}
+
+	if state.confidence_failed.contains(&block_number) {
+		return Some(BlockStatus::ConfidenceFailed);
+	}
+
+	// Range of blocks with achieved confidence can include blocks waiting to be re-sampled or backfilled
+	if state.confidence_retrying.contains(&block_number)
+		|| state.backfilling.contains(&block_number)
+	{
+		return Some(BlockStatus::VerifyingConfidence);
+	}
+
 	if block_number < first_block {
-		if state.sync_data_verified.contains(block_number) {
+		if state.sync_data_verified.contains(block_number)
+			|| state.restored_data_verified.contains(block_number)
+		{
 			return Some(BlockStatus::Finished);
 		}
-		if state.sync_confidence_achieved.contains(block_number) {
+		if state.sync_confidence_achieved.contains(block_number)
+			|| state.restored_confidence_achieved.contains(block_number)
+		{
 			return Some(BlockStatus::VerifyingData);
 		}
-		if state.sync_header_verified.contains(block_number) {
+		if state.sync_header_verified.contains(block_number)
+			|| state.restored_header_verified.contains(block_number)
+		{
 			return Some(BlockStatus::VerifyingConfidence);
 		}
 		let is_sync_latest = state.sync_latest.map(|latest| block_number == latest);
@@ -319,6 +379,12 @@ impl Reply for Block {
 	}
 }
 
+impl Reply for SamplingReport {
+	fn into_response(self) -> warp::reply::Response {
+		warp::reply::json(&self).into_response()
+	}
+}
+
 impl TryFrom<avail_subxt::primitives::Header> for HeaderMessage {
 	type Error = Report;
 
@@ -464,6 +530,43 @@ impl TryFrom<BlockVerified> for PublishMessage {
 	}
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Justification {
+	pub block_number: u32,
+	pub justification: GrandpaJustification,
+}
+
+impl Reply for Justification {
+	fn into_response(self) -> warp::reply::Response {
+		warp::reply::json(&self).into_response()
+	}
+}
+
+impl TryFrom<(u32, GrandpaJustification)> for PublishMessage {
+	type Error = Report;
+
+	fn try_from(
+		(block_number, justification): (u32, GrandpaJustification),
+	) -> Result<Self, Self::Error> {
+		Ok(PublishMessage::JustificationVerified(Justification {
+			block_number,
+			justification,
+		}))
+	}
+}
+
+/// Evidence of the validator equivocations, detected while verifying justifications
+#[derive(Serialize, Deserialize)]
+pub struct Equivocations {
+	pub equivocations: Vec<Equivocation>,
+}
+
+impl Reply for Equivocations {
+	fn into_response(self) -> warp::reply::Response {
+		warp::reply::json(&self).into_response()
+	}
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(try_from = "String")]
 pub struct FieldsQueryParameter(pub HashSet<DataField>);
@@ -484,6 +587,8 @@ impl TryFrom<String> for FieldsQueryParameter {
 #[derive(Serialize, Deserialize)]
 pub struct DataQuery {
 	pub fields: Option<FieldsQueryParameter>,
+	/// Application ID, required if more than one application is configured
+	pub app_id: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -500,6 +605,7 @@ impl Reply for DataResponse {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DataMessage {
+	app_id: u32,
 	block_number: u32,
 	data_transactions: Vec<DataTransaction>,
 }
@@ -536,15 +642,18 @@ pub fn filter_fields(data_transactions: &mut [DataTransaction], fields: &HashSet
 	}
 }
 
-impl TryFrom<(u32, AppData)> for PublishMessage {
+impl TryFrom<(u32, u32, AppData)> for PublishMessage {
 	type Error = Report;
 
-	fn try_from((block_number, app_data): (u32, AppData)) -> Result<Self, Self::Error> {
+	fn try_from(
+		(app_id, block_number, app_data): (u32, u32, AppData),
+	) -> Result<Self, Self::Error> {
 		let data_transactions = app_data
 			.into_iter()
 			.map(TryFrom::try_from)
 			.collect::<Result<Vec<_>>>()?;
 		Ok(PublishMessage::DataVerified(DataMessage {
+			app_id,
 			block_number,
 			data_transactions,
 		}))
@@ -557,6 +666,7 @@ pub enum PublishMessage {
 	HeaderVerified(Box<HeaderMessage>),
 	ConfidenceAchieved(ConfidenceMessage),
 	DataVerified(DataMessage),
+	JustificationVerified(Justification),
 }
 
 impl PublishMessage {
@@ -564,6 +674,7 @@ impl PublishMessage {
 		match self {
 			PublishMessage::HeaderVerified(_) => (),
 			PublishMessage::ConfidenceAchieved(_) => (),
+			PublishMessage::JustificationVerified(_) => (),
 			PublishMessage::DataVerified(data) => {
 				filter_fields(&mut data.data_transactions, fields)
 			},
@@ -806,7 +917,7 @@ mod tests {
 
 	use crate::{
 		api::v2::types::{BlockStatus, Header, HeaderMessage, PublishMessage},
-		types::{OptionBlockRange, State},
+		types::{BlockRange, OptionBlockRange, State},
 	};
 
 	use super::{
@@ -857,6 +968,7 @@ mod tests {
 
 	fn data_verified() -> PublishMessage {
 		PublishMessage::DataVerified(DataMessage {
+			app_id: 1,
 			block_number: 1,
 			data_transactions: vec![DataTransaction {
 				data: transaction_data(),
@@ -951,6 +1063,47 @@ mod tests {
 		assert_eq!(block_status(&Some(10), &state, 0), unavailable);
 		assert_eq!(block_status(&Some(10), &state, 9), unavailable);
 		assert_ne!(block_status(&Some(9), &state, 9), unavailable);
+
+		let mut state = State {
+			latest: 10,
+			..Default::default()
+		};
+		state.restored_header_verified.set(5);
+		assert_eq!(block_status(&None, &state, 4), unavailable);
+		assert_ne!(block_status(&None, &state, 5), unavailable);
+		assert_ne!(block_status(&Some(8), &state, 5), unavailable);
+	}
+
+	#[test]
+	fn block_status_restored() {
+		let mut state = State {
+			latest: 1000,
+			..Default::default()
+		};
+		state.header_verified.set(1000);
+		state.restored_header_verified = Some(BlockRange {
+			first: 100,
+			last: 500,
+		});
+		state.restored_confidence_achieved = state.restored_header_verified.clone();
+
+		// Blocks synced after the gap don't extend the restored range
+		state.sync_header_verified.set(900);
+		state.sync_confidence_achieved.set(900);
+		let verifying_data = Some(BlockStatus::VerifyingData);
+		assert_eq!(block_status(&Some(100), &state, 500), verifying_data);
+		assert_eq!(block_status(&Some(100), &state, 900), verifying_data);
+		let pending = Some(BlockStatus::Pending);
+		assert_eq!(block_status(&Some(100), &state, 600), pending);
+
+		// Blocks synced before the restored range don't replace it
+		state.sync_header_verified = None;
+		state.sync_confidence_achieved = None;
+		state.sync_header_verified.set(50);
+		state.sync_confidence_achieved.set(50);
+		assert_eq!(block_status(&Some(50), &state, 50), verifying_data);
+		assert_eq!(block_status(&Some(50), &state, 100), verifying_data);
+		assert_eq!(block_status(&Some(50), &state, 60), pending);
 	}
 
 	#[test]
@@ -1076,4 +1229,63 @@ mod tests {
 		assert_eq!(block_status(&Some(1), &state, 5), finished);
 		assert_ne!(block_status(&Some(1), &state, 6), finished);
 	}
+
+	#[test]
+	fn block_status_pruned() {
+		let mut state = State {
+			latest: 10,
+			confidence_pruned: Some(4),
+			..Default::default()
+		};
+		let pruned = Some(BlockStatus::Pruned);
+		state.sync_header_verified.set(1);
+		state.sync_data_verified.set(1);
+		state.sync_header_verified.set(5);
+		state.sync_data_verified.set(5);
+		assert_eq!(block_status(&Some(0), &state, 1), pruned);
+		assert_eq!(block_status(&Some(0), &state, 4), pruned);
+		assert_ne!(block_status(&Some(0), &state, 5), pruned);
+		state.confidence_pruned = None;
+		assert_ne!(block_status(&Some(0), &state, 1), pruned);
+	}
+
+	#[test]
+	fn block_status_confidence_failed() {
+		let mut state = State {
+			latest: 10,
+			..Default::default()
+		};
+		let confidence_failed = Some(BlockStatus::ConfidenceFailed);
+		let verifying_confidence = Some(BlockStatus::VerifyingConfidence);
+		state.header_verified.set(1);
+		state.confidence_achieved.set(1);
+		state.header_verified.set(5);
+		state.confidence_achieved.set(5);
+		state.confidence_retrying.insert(3);
+		state.confidence_failed.insert(4);
+		assert_eq!(block_status(&None, &state, 3), verifying_confidence);
+		assert_eq!(block_status(&None, &state, 4), confidence_failed);
+		assert_ne!(block_status(&None, &state, 5), confidence_failed);
+		state.confidence_pruned = Some(4);
+		assert_ne!(block_status(&None, &state, 4), confidence_failed);
+	}
+
+	#[test]
+	fn block_status_backfilling() {
+		let mut state = State {
+			latest: 10,
+			..Default::default()
+		};
+		state.header_verified.set(1);
+		state.confidence_achieved.set(1);
+		state.data_verified.set(1);
+		state.header_verified.set(5);
+		state.confidence_achieved.set(5);
+		state.data_verified.set(5);
+		state.backfilling.extend(2..4);
+		let verifying_confidence = Some(BlockStatus::VerifyingConfidence);
+		assert_eq!(block_status(&None, &state, 2), verifying_confidence);
+		assert_eq!(block_status(&None, &state, 3), verifying_confidence);
+		assert_eq!(block_status(&None, &state, 4), Some(BlockStatus::Finished));
+	}
 }
@@ -1,13 +1,18 @@
 //! Application client for data fetching and reconstruction.
 //!
-//! App client is enabled when app_id is configured and greater than 0 in avail-light configuration. [`Light client`](super::light_client) triggers application client if block is verified with high enough confidence. Currently [`run`] function is separate task and doesn't block main thread.
+//! App client is enabled when one or more app IDs are configured and greater than 0 in avail-light configuration. [`Light client`](super::light_client) triggers application client if block is verified with high enough confidence. Currently [`run`] function is separate task and doesn't block main thread.
 //!
 //! # Flow
 //!
+//! For each configured application:
 //! Get app data rows from node
 //! Verify commitment equality for each row
 //! Decode app data and store it into local database under the `app_id:block_number` key
 //!
+//! Block data is verified once data of all configured applications is stored
+//!
+//! Blocks missed due to the channel lag are handed over to the backfill, which publishes them again
+//!
 //! # Notes
 //!
 //! If application client fails to run or stops its execution, error is logged, and other tasks continue with execution.
@@ -37,15 +42,21 @@ use std::{
 	ops::Range,
 	sync::{Arc, Mutex},
 };
-use tokio::sync::broadcast;
-use tracing::{debug, error, info, instrument};
+use tokio::sync::{
+	broadcast::{self, error::RecvError},
+	mpsc,
+};
+use tracing::{debug, error, info, instrument, warn};
 
 use crate::{
+	alerts::{Alert, AlertSender},
 	data::{Database, Key},
 	network::{p2p::Client as P2pClient, rpc::Client as RpcClient},
 	proof,
 	shutdown::Controller,
+	sync_client,
 	types::{AppClientConfig, BlockVerified, OptionBlockRange, State},
+	utils::block_gap,
 };
 
 #[async_trait]
@@ -410,24 +421,28 @@ async fn process_block(
 /// * `db` - Database to store data inot DB
 /// * `network_client` - Reference to a libp2p custom network client
 /// * `rpc_client` - Node's RPC subxt client for fetching data unavailable in DHT (if configured)
-/// * `app_id` - Application ID
+/// * `app_ids` - Application IDs whose data is reconstructed from each verified block
 /// * `block_receive` - Channel used to receive header of verified block
 /// * `pp` - Public parameters (i.e. SRS) needed for proof verification
+/// * `backfill_sender` - Channel used to hand blocks missed due to the channel lag over to the backfill
+/// * `alert_sender` - Channel used to raise alerts on failed app data reconstruction
 #[allow(clippy::too_many_arguments)]
 pub async fn run(
 	cfg: AppClientConfig,
 	db: impl Database + Clone + Sync,
 	network_client: P2pClient,
 	rpc_client: RpcClient,
-	app_id: AppId,
+	app_ids: Vec<AppId>,
 	mut block_receive: broadcast::Receiver<BlockVerified>,
 	pp: Arc<PublicParameters>,
 	state: Arc<Mutex<State>>,
 	sync_range: Range<u32>,
-	data_verified_sender: broadcast::Sender<(u32, AppData)>,
+	data_verified_sender: broadcast::Sender<(u32, u32, AppData)>,
+	backfill_sender: mpsc::UnboundedSender<Range<u32>>,
+	alert_sender: AlertSender,
 	shutdown: Controller<String>,
 ) {
-	info!("Starting for app {app_id}...");
+	info!("Starting for apps {app_ids:?}...");
 
 	fn set_data_verified_state(
 		state: Arc<Mutex<State>>,
@@ -437,6 +452,8 @@ pub async fn run(
 		let mut state = state.lock().expect("State lock can be acquired");
 		match sync_range.contains(&block_number) {
 			true => state.sync_data_verified.set(block_number),
+			// Backfilled blocks are behind the latest processed block
+			false if state.data_verified.contains(block_number) => {},
 			false => state.data_verified.set(block_number),
 		}
 		if state.synced == Some(false) && sync_range.clone().last() == Some(block_number) {
@@ -444,9 +461,16 @@ pub async fn run(
 		};
 	}
 
+	let mut last_block = None;
+
 	loop {
 		let block = match block_receive.recv().await {
 			Ok(block) => block,
+			// Missed blocks are detected on the next received block
+			Err(RecvError::Lagged(count)) => {
+				warn!("App client lagged behind, {count} messages skipped");
+				continue;
+			},
 			Err(error) => {
 				error!("Cannot receive message: {error}");
 				let _ = shutdown.trigger_shutdown(format!("Cannot receive message: {error:#}"));
@@ -457,36 +481,75 @@ pub async fn run(
 		let block_number = block.block_num;
 		let dimensions = &block.dimensions;
 
+		// Sync blocks are received out of order, so gaps are detected only for the latest blocks
+		if !sync_range.contains(&block_number) {
+			if let Some(gap) = block_gap(last_block, block_number) {
+				sync_client::backfill(&backfill_sender, &state, gap);
+			}
+			last_block = last_block.max(Some(block_number));
+		}
+
 		info!(block_number, "Block available: {dimensions:?}");
 
-		if block.lookup.range_of(app_id).is_none() {
-			info!(
-				block_number,
-				"Skipping block with no cells for app {app_id}"
-			);
-			set_data_verified_state(state.clone(), &sync_range, block_number);
-			continue;
-		}
+		let mut verified_data = vec![];
+		for &app_id in &app_ids {
+			// Re-sampled blocks are received again once confidence is achieved
+			match db.get::<AppData>(Key::AppData(app_id.0, block_number)) {
+				Ok(Some(_)) => {
+					debug!(block_number, "App data for app {app_id} is already stored");
+					continue;
+				},
+				Ok(None) => {},
+				Err(error) => {
+					error!(block_number, "Cannot get app data: {error}");
+					let _ = shutdown.trigger_shutdown(format!("Cannot get app data: {error:#}"));
+					return;
+				},
+			}
+			if block.lookup.range_of(app_id).is_none() {
+				info!(
+					block_number,
+					"Skipping block with no cells for app {app_id}"
+				);
+				// Empty app data is stored so verified data ranges can be checked on restart
+				if let Err(error) = db.put(Key::AppData(app_id.0, block_number), AppData::new()) {
+					error!(block_number, "Cannot store app data: {error}");
+					let _ = shutdown.trigger_shutdown(format!("Cannot store app data: {error:#}"));
+					return;
+				}
+				continue;
+			}
 
-		let app_client = AppClient {
-			p2p_client: network_client.clone(),
-			rpc_client: rpc_client.clone(),
-		};
-		let data =
+			let app_client = AppClient {
+				p2p_client: network_client.clone(),
+				rpc_client: rpc_client.clone(),
+			};
 			match process_block(app_client, db.clone(), &cfg, app_id, &block, pp.clone()).await {
-				Ok(data) => data,
+				Ok(data) => verified_data.push((app_id.0, block_number, data)),
 				Err(error) => {
-					error!(block_number, "Cannot process block: {error}");
+					error!(
+						block_number,
+						"Cannot process block for app {app_id}: {error}"
+					);
+					let _ = alert_sender.send(Alert::AppDataReconstructionFailed {
+						block_number,
+						app_id: app_id.0,
+						error: format!("{error:#}"),
+					});
 					let _ = shutdown.trigger_shutdown(format!("Cannot process block: {error:#}"));
 					return;
 				},
 			};
+		}
+
 		set_data_verified_state(state.clone(), &sync_range, block_number);
-		if let Err(error) = data_verified_sender.send((block_number, data)) {
-			error!("Cannot send data verified message: {error}");
-			let _ =
-				shutdown.trigger_shutdown(format!("Cannot send data verified message: {error:#}"));
-			return;
+		for data in verified_data {
+			if let Err(error) = data_verified_sender.send(data) {
+				error!("Cannot send data verified message: {error}");
+				let _ = shutdown
+					.trigger_shutdown(format!("Cannot send data verified message: {error:#}"));
+				return;
+			}
 		}
 		debug!(block_number, "Block processed");
 	}
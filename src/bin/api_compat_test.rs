@@ -1,12 +1,15 @@
 use avail_light::{
 	data::rocks_db::RocksDB,
 	network::rpc,
-	types::{ExponentialConfig, RetryConfig, State},
+	shutdown::Controller,
+	telemetry::MockMetrics,
+	types::{ExponentialConfig, RetryConfig, RuntimeConfig, State},
 };
 use clap::Parser;
 use color_eyre::{eyre::Context, Result};
 use kate_recovery::matrix::Position;
 use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc};
 
 #[derive(Parser)]
 struct CommandArgs {
@@ -31,9 +34,33 @@ async fn main() -> Result<()> {
 		retries: 4,
 	});
 
-	let (rpc_client, _, subscriptions) =
-		rpc::init(db, state, &[command_args.url], "DEV", retry_cfg).await?;
-	tokio::spawn(subscriptions.run());
+	// Metrics are not exported by the compatibility test
+	let mut metrics = MockMetrics::new();
+	metrics.expect_count().returning(|_| ());
+	let metrics = Arc::new(metrics);
+
+	// Alerts are not posted by the compatibility test
+	let (alert_sender, _alert_receiver) = mpsc::unbounded_channel();
+
+	// Justifications are not published by the compatibility test
+	let (justification_sender, _) = broadcast::channel(1);
+
+	let nodes = &[command_args.url];
+	let (rpc_client, _, subscriptions) = rpc::init(
+		db,
+		metrics,
+		state,
+		nodes,
+		"DEV",
+		retry_cfg,
+		(&RuntimeConfig::default()).into(),
+		// single node is tested, so headers cannot be cross-checked
+		None,
+		justification_sender,
+		alert_sender,
+	)
+	.await?;
+	tokio::spawn(subscriptions.run(Controller::new()));
 
 	let mut correct: bool = true;
 
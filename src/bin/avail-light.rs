@@ -4,14 +4,14 @@ use avail_core::AppId;
 use avail_light::{
 	api,
 	consts::EXPECTED_SYSTEM_VERSION,
-	data::rocks_db::RocksDB,
+	data::{self, inspect, rocks_db::RocksDB},
 	maintenance::StaticConfigParams,
 	network::{self, p2p, rpc},
 	shutdown::Controller,
 	sync_client::SyncClient,
 	sync_finality::SyncFinality,
 	telemetry::{self, otlp::MetricAttributes},
-	types::{CliOpts, IdentityConfig, LibP2PConfig, RuntimeConfig, State},
+	types::{CliOpts, Command, FinalityCheckpoint, IdentityConfig, LibP2PConfig, RuntimeConfig},
 };
 use clap::Parser;
 use color_eyre::{
@@ -22,6 +22,7 @@ use kate_recovery::com::AppData;
 use libp2p::{multiaddr::Protocol, Multiaddr};
 use std::{
 	fs,
+	io::BufReader,
 	net::Ipv4Addr,
 	path::Path,
 	sync::{Arc, Mutex},
@@ -70,9 +71,7 @@ fn parse_log_level(log_level: &str, default: Level) -> (Level, Option<ParseLevel
 		.unwrap_or_else(|parse_err| (default, Some(parse_err)))
 }
 
-async fn run(shutdown: Controller<String>) -> Result<()> {
-	let opts = CliOpts::parse();
-
+async fn run(shutdown: Controller<String>, opts: CliOpts) -> Result<()> {
 	let mut cfg: RuntimeConfig = RuntimeConfig::default();
 	cfg.load_runtime_config(&opts)?;
 
@@ -115,6 +114,12 @@ async fn run(shutdown: Controller<String>) -> Result<()> {
 		Err(eyre!("Bootstrap node list must not be empty. Either use a '--network' flag or add a list of bootstrap nodes in the configuration file"))?
 	}
 
+	if matches!(cfg.rpc_header_quorum, Some(quorum) if quorum > cfg.full_node_ws.len()) {
+		Err(eyre!(
+			"RPC header quorum must not exceed the number of configured full nodes"
+		))?
+	}
+
 	let db =
 		RocksDB::open(&cfg.avail_path).wrap_err("Avail Light could not initialize database")?;
 
@@ -158,15 +163,14 @@ async fn run(shutdown: Controller<String>) -> Result<()> {
 		&id_keys,
 		cfg.is_fat_client(),
 		cfg.ws_transport_enable,
+		db.clone(),
 		shutdown.clone(),
-	);
+	)
+	.await
+	.wrap_err("Unable to create P2P event loop")?;
 
 	tokio::spawn(
-		shutdown.with_cancel(
-			p2p_event_loop
-				.await
-				.run(ot_metrics.clone(), p2p_event_loop_receiver),
-		),
+		shutdown.with_cancel(p2p_event_loop.run(ot_metrics.clone(), p2p_event_loop_receiver)),
 	);
 
 	let p2p_client = p2p::Client::new(
@@ -208,16 +212,36 @@ async fn run(shutdown: Controller<String>) -> Result<()> {
 	let public_params_len = hex::encode(raw_pp).len();
 	trace!("Public params ({public_params_len}): hash: {public_params_hash}");
 
-	let state = Arc::new(Mutex::new(State::default()));
+	let state = data::load_state(&db, &cfg.app_ids).wrap_err("Cannot load stored state")?;
+	let state = Arc::new(Mutex::new(state));
+
+	let (alert_tx, alert_rx) = mpsc::unbounded_channel();
+	// Alerts are not cancelled on shutdown, so alerts raised on failures are still posted
+	let alerts = avail_light::alerts::run((&cfg).into(), alert_rx, shutdown.clone());
+	tokio::task::spawn(alerts);
+
+	let (justification_tx, justification_rx) = broadcast::channel(1 << 7);
+
 	let (rpc_client, rpc_events, rpc_subscriptions) = rpc::init(
 		db.clone(),
+		ot_metrics.clone(),
 		state.clone(),
 		&cfg.full_node_ws,
 		&cfg.genesis_hash,
 		cfg.retry_config.clone(),
+		(&cfg).into(),
+		cfg.rpc_header_quorum,
+		justification_tx,
+		alert_tx.clone(),
 	)
 	.await?;
 
+	tokio::task::spawn(shutdown.with_cancel(rpc::run_health_probes(
+		rpc_client.clone(),
+		ot_metrics.clone(),
+		(&cfg).into(),
+	)));
+
 	// Subscribing to RPC events before first event is published
 	let publish_rpc_event_receiver = rpc_events.subscribe();
 	let first_header_rpc_event_receiver = rpc_events.subscribe();
@@ -227,10 +251,11 @@ async fn run(shutdown: Controller<String>) -> Result<()> {
 
 	// spawn the RPC Network task for Event Loop to run in the background
 	// and shut it down, without delays
+	let subscriptions_shutdown = shutdown.clone();
 	let rpc_subscriptions_handle = tokio::spawn(shutdown.with_cancel(shutdown.with_trigger(
 		"Subscription loop failure triggered shutdown".to_string(),
 		async {
-			let result = rpc_subscriptions.run().await;
+			let result = rpc_subscriptions.run(subscriptions_shutdown).await;
 			if let Err(ref err) = result {
 				error!(%err, "Subscription loop ended with error");
 			};
@@ -287,20 +312,24 @@ async fn run(shutdown: Controller<String>) -> Result<()> {
 	tokio::task::spawn(shutdown.with_cancel(server.bind()));
 
 	let (block_tx, block_rx) = broadcast::channel::<avail_light::types::BlockVerified>(1 << 7);
+	let (backfill_tx, backfill_rx) = mpsc::unbounded_channel();
 
-	let data_rx = cfg.app_id.map(AppId).map(|app_id| {
-		let (data_tx, data_rx) = broadcast::channel::<(u32, AppData)>(1 << 7);
+	let app_ids = cfg.app_ids.iter().copied().map(AppId).collect::<Vec<_>>();
+	let data_rx = (!app_ids.is_empty()).then(|| {
+		let (data_tx, data_rx) = broadcast::channel::<(u32, u32, AppData)>(1 << 7);
 		tokio::task::spawn(shutdown.with_cancel(avail_light::app_client::run(
 			(&cfg).into(),
 			db.clone(),
 			p2p_client.clone(),
 			rpc_client.clone(),
-			app_id,
+			app_ids,
 			block_tx.subscribe(),
 			pp.clone(),
 			state.clone(),
 			sync_range.clone(),
 			data_tx,
+			backfill_tx.clone(),
+			alert_tx.clone(),
 			shutdown.clone(),
 		)));
 		data_rx
@@ -318,6 +347,12 @@ async fn run(shutdown: Controller<String>) -> Result<()> {
 		ws_clients.clone(),
 	)));
 
+	tokio::task::spawn(shutdown.with_cancel(api::v2::publish(
+		api::v2::types::Topic::JustificationVerified,
+		justification_rx,
+		ws_clients.clone(),
+	)));
+
 	if let Some(data_rx) = data_rx {
 		tokio::task::spawn(shutdown.with_cancel(api::v2::publish(
 			api::v2::types::Topic::DataVerified,
@@ -360,13 +395,36 @@ async fn run(shutdown: Controller<String>) -> Result<()> {
 		)));
 	}
 
+	let backfill_client = SyncClient::new(db.clone(), rpc_client.clone());
+	let backfill_network_client = network::new(
+		p2p_client.clone(),
+		rpc_client.clone(),
+		pp.clone(),
+		cfg.disable_rpc,
+	);
+	tokio::task::spawn(shutdown.with_cancel(avail_light::sync_client::run_backfill(
+		backfill_client,
+		backfill_network_client,
+		(&cfg).into(),
+		backfill_rx,
+		block_tx.clone(),
+		state.clone(),
+		alert_tx.clone(),
+	)));
+
 	if cfg.sync_finality_enable {
+		let trusted_checkpoint = cfg
+			.finality_checkpoint
+			.as_ref()
+			.map(FinalityCheckpoint::load)
+			.transpose()?;
 		let sync_finality = SyncFinality::new(db.clone(), rpc_client.clone());
 		tokio::task::spawn(shutdown.with_cancel(avail_light::sync_finality::run(
 			sync_finality,
 			shutdown.clone(),
 			state.clone(),
 			block_header.clone(),
+			trusted_checkpoint,
 		)));
 	} else {
 		let mut s = state
@@ -384,16 +442,29 @@ async fn run(shutdown: Controller<String>) -> Result<()> {
 	};
 
 	tokio::task::spawn(shutdown.with_cancel(avail_light::maintenance::run(
+		db.clone(),
 		p2p_client.clone(),
 		ot_metrics.clone(),
 		block_rx,
 		static_config_params,
+		state.clone(),
+		shutdown.clone(),
+	)));
+
+	tokio::task::spawn(shutdown.with_cancel(avail_light::retention::run(
+		db.clone(),
+		ot_metrics.clone(),
+		block_tx.subscribe(),
+		(&cfg).into(),
+		state.clone(),
 		shutdown.clone(),
 	)));
 
 	let channels = avail_light::types::ClientChannels {
 		block_sender: block_tx,
 		rpc_event_receiver: client_rpc_event_receiver,
+		backfill_sender: backfill_tx,
+		alert_sender: alert_tx,
 	};
 
 	if let Some(partition) = cfg.block_matrix_partition {
@@ -404,6 +475,7 @@ async fn run(shutdown: Controller<String>) -> Result<()> {
 			db.clone(),
 			(&cfg).into(),
 			ot_metrics.clone(),
+			state.clone(),
 			channels,
 			partition,
 			shutdown.clone(),
@@ -425,6 +497,50 @@ async fn run(shutdown: Controller<String>) -> Result<()> {
 	Ok(())
 }
 
+/// Runs database inspection command on the configured database, without starting the light client.
+fn run_command(command: &Command, opts: &CliOpts) -> Result<()> {
+	let mut cfg: RuntimeConfig = RuntimeConfig::default();
+	cfg.load_runtime_config(opts)?;
+
+	let db = RocksDB::open(&cfg.avail_path).wrap_err("Could not open database")?;
+
+	match command {
+		Command::Dump { from, to } => {
+			let blocks = inspect::dump(&db, &cfg.app_ids, *from..=*to)?;
+			println!("{}", serde_json::to_string_pretty(&blocks)?);
+		},
+		Command::Export { path } => {
+			let snapshot = inspect::export(&db, &cfg.app_ids)?;
+			let file = fs::File::create(path).wrap_err("Failed to create snapshot file")?;
+			serde_json::to_writer(file, &snapshot).wrap_err("Failed to write snapshot")?;
+			println!("Exported {} blocks into {path}", snapshot.blocks.len());
+		},
+		Command::Import { path } => {
+			let file = fs::File::open(path).wrap_err("Failed to open snapshot file")?;
+			let snapshot = serde_json::from_reader(BufReader::new(file))
+				.wrap_err("Failed to read snapshot")?;
+			let count = inspect::import(&db, snapshot)?;
+			println!("Imported {count} blocks from {path}");
+		},
+		Command::Stats => {
+			let stats = inspect::stats(&db, &cfg.app_ids)?;
+			println!("{}", serde_json::to_string_pretty(&stats)?);
+		},
+		Command::ExportCheckpoint { path } => {
+			let checkpoint = inspect::checkpoint(&db)?;
+			let file = fs::File::create(path).wrap_err("Failed to create checkpoint file")?;
+			serde_json::to_writer_pretty(file, &checkpoint)
+				.wrap_err("Failed to write checkpoint")?;
+			println!(
+				"Exported checkpoint at block {} into {path}",
+				checkpoint.number
+			);
+		},
+	}
+
+	Ok(())
+}
+
 fn construct_multiaddress(is_websocket: bool, port: u16) -> Multiaddr {
 	let tcp_multiaddress = Multiaddr::empty()
 		.with(Protocol::from(Ipv4Addr::UNSPECIFIED))
@@ -516,10 +632,15 @@ pub async fn main() -> Result<()> {
 	// install custom panic hooks
 	install_panic_hooks(shutdown.clone())?;
 
+	let opts = CliOpts::parse();
+	if let Some(command) = &opts.command {
+		return run_command(command, &opts);
+	}
+
 	// spawn a task to watch for ctrl-c signals from user to trigger the shutdown
 	tokio::spawn(shutdown.with_trigger("user signaled shutdown".to_string(), user_signal()));
 
-	if let Err(error) = run(shutdown.clone()).await {
+	if let Err(error) = run(shutdown.clone(), opts).await {
 		error!("{error:#}");
 		return Err(error.wrap_err("Starting Light Client failed"));
 	};
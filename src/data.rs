@@ -1,8 +1,13 @@
+use crate::types::{BlockRange, State};
+use avail_subxt::primitives::Header as DaHeader;
 use codec::{Decode, Encode};
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{Result, WrapErr};
+use kate_recovery::com::AppData;
 use serde::{Deserialize, Serialize};
 use sp_core::ed25519;
+use std::{collections::BTreeSet, ops::RangeInclusive};
 
+pub mod inspect;
 pub mod rocks_db;
 
 #[cfg(test)]
@@ -12,6 +17,9 @@ pub trait Database {
 	/// Type of the database key which we can get from the custom key.
 	type Key;
 
+	/// Type of the batch of write operations supported by database.
+	type Batch: WriteBatch;
+
 	/// Puts value for given key into database.
 	/// Key is serialized into database key, value is serialized into type supported by database.
 	fn put<T>(&self, key: Key, value: T) -> Result<()>
@@ -26,8 +34,39 @@ pub trait Database {
 
 	/// Deletes value from the database for the given key.
 	fn delete(&self, key: Key) -> Result<()>;
+
+	/// Deletes all values from the column family which are stored for blocks before the given block.
+	/// Returns number of deleted values.
+	fn delete_before(&self, column_family: &str, block_number: u32) -> Result<usize>;
+
+	/// Iterates over values stored for the given range of keys, ordered by block number.
+	/// Values are deserialized into the given type.
+	fn iter<'a, T>(&'a self, range: KeyRange) -> Result<BlockIterator<'a, T>>
+	where
+		for<'de> T: Deserialize<'de> + Decode + 'a;
+
+	/// Creates new empty batch of write operations.
+	fn batch(&self) -> Self::Batch;
+
+	/// Applies all write operations from the batch atomically.
+	fn write(&self, batch: Self::Batch) -> Result<()>;
 }
 
+/// Batch of write operations, which are applied to the database atomically.
+pub trait WriteBatch {
+	/// Adds put operation for the given key into the batch.
+	/// Key is serialized into database key, value is serialized into type supported by database.
+	fn put<T>(&mut self, key: Key, value: T) -> Result<()>
+	where
+		T: Serialize + Encode;
+
+	/// Adds delete operation for the given key into the batch.
+	fn delete(&mut self, key: Key) -> Result<()>;
+}
+
+/// Iterator over block numbers and values stored for them
+pub type BlockIterator<'a, T> = Box<dyn Iterator<Item = Result<(u32, T)>> + 'a>;
+
 /// Column family for confidence factor
 pub const CONFIDENCE_FACTOR_CF: &str = "avail_light_confidence_factor_cf";
 
@@ -40,15 +79,81 @@ pub const APP_DATA_CF: &str = "avail_light_app_data_cf";
 /// Column family for state
 pub const STATE_CF: &str = "avail_light_state_cf";
 
+/// Column family for Kademlia records
+pub const KADEMLIA_STORE_CF: &str = "avail_light_kademlia_store_cf";
+
+/// Column family for sampling reports
+pub const SAMPLING_REPORT_CF: &str = "avail_light_sampling_report_cf";
+
+/// Column family for verified GRANDPA justifications
+pub const JUSTIFICATION_CF: &str = "avail_light_justification_cf";
+
+/// Column family for evidence of validator equivocations
+pub const EQUIVOCATION_CF: &str = "avail_light_equivocation_cf";
+
 /// Sync finality checkpoint key name
 const FINALITY_SYNC_CHECKPOINT_KEY: &str = "finality_sync_checkpoint";
 
+/// Block processing state key name
+const STATE_KEY: &str = "state";
+
+/// Database schema version key name
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
 #[derive(Clone)]
 pub enum Key {
 	AppData(u32, u32),
 	BlockHeader(u32),
 	VerifiedCellCount(u32),
+	SamplingReport(u32),
+	Justification(u32),
+	Equivocation(u32),
 	FinalitySyncCheckpoint,
+	State,
+	SchemaVersion,
+}
+
+/// Inclusive range of block keys
+#[derive(Clone)]
+pub enum KeyRange {
+	AppData(u32, RangeInclusive<u32>),
+	BlockHeader(RangeInclusive<u32>),
+	VerifiedCellCount(RangeInclusive<u32>),
+	SamplingReport(RangeInclusive<u32>),
+	Justification(RangeInclusive<u32>),
+	Equivocation(RangeInclusive<u32>),
+}
+
+impl KeyRange {
+	/// Returns first and last key in the range
+	pub fn bounds(&self) -> (Key, Key) {
+		match self {
+			KeyRange::AppData(app_id, blocks) => (
+				Key::AppData(*app_id, *blocks.start()),
+				Key::AppData(*app_id, *blocks.end()),
+			),
+			KeyRange::BlockHeader(blocks) => (
+				Key::BlockHeader(*blocks.start()),
+				Key::BlockHeader(*blocks.end()),
+			),
+			KeyRange::VerifiedCellCount(blocks) => (
+				Key::VerifiedCellCount(*blocks.start()),
+				Key::VerifiedCellCount(*blocks.end()),
+			),
+			KeyRange::SamplingReport(blocks) => (
+				Key::SamplingReport(*blocks.start()),
+				Key::SamplingReport(*blocks.end()),
+			),
+			KeyRange::Justification(blocks) => (
+				Key::Justification(*blocks.start()),
+				Key::Justification(*blocks.end()),
+			),
+			KeyRange::Equivocation(blocks) => (
+				Key::Equivocation(*blocks.start()),
+				Key::Equivocation(*blocks.end()),
+			),
+		}
+	}
 }
 
 #[derive(Serialize, Deserialize, Debug, Decode, Encode)]
@@ -57,3 +162,364 @@ pub struct FinalitySyncCheckpoint {
 	pub set_id: u64,
 	pub validator_set: Vec<ed25519::Public>,
 }
+
+/// Block processing state ranges and failed blocks, persisted to survive restarts
+#[derive(Serialize, Deserialize, Debug, Default, Decode, Encode)]
+pub struct StoredState {
+	pub header_verified: Option<BlockRange>,
+	pub confidence_achieved: Option<BlockRange>,
+	pub data_verified: Option<BlockRange>,
+	pub sync_header_verified: Option<BlockRange>,
+	pub sync_confidence_achieved: Option<BlockRange>,
+	pub sync_data_verified: Option<BlockRange>,
+	pub restored_header_verified: Option<BlockRange>,
+	pub restored_confidence_achieved: Option<BlockRange>,
+	pub restored_data_verified: Option<BlockRange>,
+	pub finality_synced: bool,
+	pub confidence_pruned: Option<u32>,
+	pub header_pruned: Option<u32>,
+	pub data_pruned: Option<u32>,
+	pub confidence_retrying: BTreeSet<u32>,
+	pub confidence_failed: BTreeSet<u32>,
+}
+
+impl From<&State> for StoredState {
+	fn from(state: &State) -> Self {
+		StoredState {
+			header_verified: state.header_verified.clone(),
+			confidence_achieved: state.confidence_achieved.clone(),
+			data_verified: state.data_verified.clone(),
+			sync_header_verified: state.sync_header_verified.clone(),
+			sync_confidence_achieved: state.sync_confidence_achieved.clone(),
+			sync_data_verified: state.sync_data_verified.clone(),
+			restored_header_verified: state.restored_header_verified.clone(),
+			restored_confidence_achieved: state.restored_confidence_achieved.clone(),
+			restored_data_verified: state.restored_data_verified.clone(),
+			finality_synced: state.finality_synced,
+			confidence_pruned: state.confidence_pruned,
+			header_pruned: state.header_pruned,
+			data_pruned: state.data_pruned,
+			confidence_retrying: state.confidence_retrying.clone(),
+			confidence_failed: state.confidence_failed.clone(),
+		}
+	}
+}
+
+/// Stores block processing state ranges into the database.
+pub fn store_state(db: &impl Database, state: &State) -> Result<()> {
+	db.put(Key::State, StoredState::from(state))
+		.wrap_err("Failed to store state")
+}
+
+/// Loads block processing state stored in the previous run.
+///
+/// Stored ranges are trimmed to the blocks whose confidence, header or app data
+/// (of all configured applications) is present in the database. Since live processing starts from the latest finalized block,
+/// ranges verified in the previous runs are restored apart from the live and sync ranges.
+pub fn load_state(db: &impl Database, app_ids: &[u32]) -> Result<State> {
+	let Some(stored) = db
+		.get::<StoredState>(Key::State)
+		.wrap_err("Failed to load state")?
+	else {
+		return Ok(State::default());
+	};
+
+	let is_header_stored = |block_number| -> Result<bool> {
+		Ok(db
+			.get::<DaHeader>(Key::BlockHeader(block_number))?
+			.is_some())
+	};
+	let is_confidence_stored = |block_number| -> Result<bool> {
+		Ok(db
+			.get::<u32>(Key::VerifiedCellCount(block_number))?
+			.is_some())
+	};
+	let is_data_stored = |block_number| -> Result<bool> {
+		if app_ids.is_empty() {
+			return Ok(false);
+		}
+		for &app_id in app_ids {
+			if db
+				.get::<AppData>(Key::AppData(app_id, block_number))?
+				.is_none()
+			{
+				return Ok(false);
+			}
+		}
+		Ok(true)
+	};
+
+	Ok(State {
+		restored_header_verified: restored_range(
+			[
+				stored.restored_header_verified,
+				stored.sync_header_verified,
+				stored.header_verified,
+			],
+			stored.header_pruned,
+			is_header_stored,
+		)?,
+		restored_confidence_achieved: restored_range(
+			[
+				stored.restored_confidence_achieved,
+				stored.sync_confidence_achieved,
+				stored.confidence_achieved,
+			],
+			stored.confidence_pruned,
+			is_confidence_stored,
+		)?,
+		restored_data_verified: restored_range(
+			[
+				stored.restored_data_verified,
+				stored.sync_data_verified,
+				stored.data_verified,
+			],
+			stored.data_pruned,
+			is_data_stored,
+		)?,
+		finality_synced: stored.finality_synced,
+		confidence_pruned: stored.confidence_pruned,
+		header_pruned: stored.header_pruned,
+		data_pruned: stored.data_pruned,
+		// Re-sampling is not resumed, so blocks interrupted while retrying are restored as failed
+		confidence_failed: stored
+			.confidence_failed
+			.into_iter()
+			.chain(stored.confidence_retrying)
+			.filter(|&block_number| Some(block_number) > stored.confidence_pruned)
+			.collect(),
+		..Default::default()
+	})
+}
+
+/// Merges ranges of the previous runs, trimmed to the stored blocks.
+fn restored_range(
+	ranges: [Option<BlockRange>; 3],
+	pruned: Option<u32>,
+	is_stored: impl Fn(u32) -> Result<bool>,
+) -> Result<Option<BlockRange>> {
+	let mut restored = None;
+	for range in ranges {
+		restored = merge_ranges(restored, stored_range(range, pruned, &is_stored)?);
+	}
+	Ok(restored)
+}
+
+/// Trims range to the latest run of consecutive blocks stored in the database, skipping pruned blocks.
+/// Blocks before the last missing block are not restored, since the range cannot represent the gap.
+fn stored_range(
+	range: Option<BlockRange>,
+	pruned: Option<u32>,
+	is_stored: impl Fn(u32) -> Result<bool>,
+) -> Result<Option<BlockRange>> {
+	let Some(BlockRange {
+		mut first,
+		mut last,
+	}) = range
+	else {
+		return Ok(None);
+	};
+
+	if let Some(pruned) = pruned {
+		if pruned >= last {
+			return Ok(None);
+		}
+		first = first.max(pruned + 1);
+	}
+
+	while !is_stored(last)? {
+		if last <= first {
+			return Ok(None);
+		}
+		last -= 1;
+	}
+
+	let mut start = last;
+	while start > first && is_stored(start - 1)? {
+		start -= 1;
+	}
+
+	Ok(Some(BlockRange { first: start, last }))
+}
+
+/// Merges ranges if they are contiguous, otherwise the range with the latest blocks is kept.
+fn merge_ranges(range: Option<BlockRange>, other: Option<BlockRange>) -> Option<BlockRange> {
+	match (range, other) {
+		(Some(range), Some(other))
+			if other.first <= range.last.saturating_add(1)
+				&& range.first <= other.last.saturating_add(1) =>
+		{
+			Some(BlockRange {
+				first: range.first.min(other.first),
+				last: range.last.max(other.last),
+			})
+		},
+		(Some(range), Some(other)) => Some(if range.last > other.last {
+			range
+		} else {
+			other
+		}),
+		(range, None) => range,
+		(None, other) => other,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{load_state, mem_db::MemoryDB, store_state, Database, Key, KeyRange, WriteBatch};
+	use crate::types::{BlockRange, OptionBlockRange, State};
+	use std::collections::BTreeSet;
+
+	#[test]
+	fn iter_key_range() {
+		let db = MemoryDB::default();
+		for block_number in [1, 3, 10, 11, 100] {
+			db.put(Key::VerifiedCellCount(block_number), block_number * 2)
+				.unwrap();
+			db.put(Key::AppData(1, block_number), vec![vec![1u8]])
+				.unwrap();
+		}
+		db.put(Key::AppData(2, 5), vec![vec![2u8]]).unwrap();
+
+		let counts = db
+			.iter::<u32>(KeyRange::VerifiedCellCount(2..=11))
+			.unwrap()
+			.collect::<color_eyre::Result<Vec<_>>>()
+			.unwrap();
+		assert_eq!(counts, vec![(3, 6), (10, 20), (11, 22)]);
+
+		let blocks = db
+			.iter::<Vec<Vec<u8>>>(KeyRange::AppData(1, 0..=u32::MAX))
+			.unwrap()
+			.map(|entry| entry.map(|(block_number, _)| block_number))
+			.collect::<color_eyre::Result<Vec<_>>>()
+			.unwrap();
+		assert_eq!(blocks, vec![1, 3, 10, 11, 100]);
+
+		let data = db
+			.iter::<Vec<Vec<u8>>>(KeyRange::AppData(2, 0..=u32::MAX))
+			.unwrap()
+			.collect::<color_eyre::Result<Vec<_>>>()
+			.unwrap();
+		assert_eq!(data, vec![(5, vec![vec![2u8]])]);
+
+		let mut headers = db.iter::<u32>(KeyRange::BlockHeader(0..=100)).unwrap();
+		assert!(headers.next().is_none());
+	}
+
+	#[test]
+	fn write_batch() {
+		let db = MemoryDB::default();
+		db.put(Key::BlockHeader(1), 1u32).unwrap();
+
+		let mut batch = db.batch();
+		batch.put(Key::VerifiedCellCount(2), 10u32).unwrap();
+		batch.put(Key::BlockHeader(2), 2u32).unwrap();
+		batch.delete(Key::BlockHeader(1)).unwrap();
+		assert!(db.get::<u32>(Key::VerifiedCellCount(2)).unwrap().is_none());
+
+		db.write(batch).unwrap();
+		assert_eq!(db.get::<u32>(Key::VerifiedCellCount(2)).unwrap(), Some(10));
+		assert_eq!(db.get::<u32>(Key::BlockHeader(2)).unwrap(), Some(2));
+		assert!(db.get::<u32>(Key::BlockHeader(1)).unwrap().is_none());
+	}
+
+	#[test]
+	fn load_state_empty() {
+		let db = MemoryDB::default();
+		let state = load_state(&db, &[]).unwrap();
+		assert!(state.sync_header_verified.is_none());
+		assert!(state.sync_confidence_achieved.is_none());
+		assert!(!state.finality_synced);
+	}
+
+	#[test]
+	fn load_state_restores_failed_blocks() {
+		let db = MemoryDB::default();
+		let mut state = State::default();
+		state.confidence_failed.extend([3, 7]);
+		state.confidence_retrying.insert(9);
+		state.confidence_pruned = Some(5);
+		store_state(&db, &state).unwrap();
+
+		let state = load_state(&db, &[]).unwrap();
+		assert_eq!(state.confidence_failed, BTreeSet::from([7, 9]));
+		assert!(state.confidence_retrying.is_empty());
+	}
+
+	#[test]
+	fn load_state_merges_and_trims_ranges() {
+		let db = MemoryDB::default();
+		for block_number in 1..=8 {
+			db.put(Key::VerifiedCellCount(block_number), 10u32).unwrap();
+		}
+		for block_number in [1, 2, 3] {
+			db.put(Key::AppData(1, block_number), Vec::<Vec<u8>>::new())
+				.unwrap();
+		}
+
+		let mut state = State::default();
+		state.sync_confidence_achieved.set(1);
+		state.sync_confidence_achieved.set(5);
+		state.confidence_achieved.set(6);
+		state.confidence_achieved.set(10);
+		state.sync_data_verified.set(1);
+		state.sync_data_verified.set(4);
+		state.finality_synced = true;
+		store_state(&db, &state).unwrap();
+
+		let state = load_state(&db, &[1]).unwrap();
+		assert_eq!(
+			state.restored_confidence_achieved,
+			Some(BlockRange { first: 1, last: 8 })
+		);
+		assert_eq!(
+			state.restored_data_verified,
+			Some(BlockRange { first: 1, last: 3 })
+		);
+		assert!(state.confidence_achieved.is_none());
+		assert!(state.sync_confidence_achieved.is_none());
+		assert!(state.finality_synced);
+
+		let state = load_state(&db, &[]).unwrap();
+		assert!(state.restored_data_verified.is_none());
+
+		// Data is verified only if it is stored for all applications
+		db.put(Key::AppData(2, 1), Vec::<Vec<u8>>::new()).unwrap();
+		let state = load_state(&db, &[1, 2]).unwrap();
+		assert_eq!(
+			state.restored_data_verified,
+			Some(BlockRange { first: 1, last: 1 })
+		);
+	}
+
+	#[test]
+	fn load_state_skips_gaps() {
+		let db = MemoryDB::default();
+		for block_number in (1..=3).chain(6..=10) {
+			db.put(Key::VerifiedCellCount(block_number), 10u32).unwrap();
+		}
+
+		let mut state = State::default();
+		state.sync_confidence_achieved.set(1);
+		state.sync_confidence_achieved.set(10);
+		state.confidence_achieved.set(20);
+		state.confidence_achieved.set(30);
+		store_state(&db, &state).unwrap();
+
+		// Blocks before the gap, and the blocks which are not stored, are not restored
+		let state = load_state(&db, &[]).unwrap();
+		assert_eq!(
+			state.restored_confidence_achieved,
+			Some(BlockRange { first: 6, last: 10 })
+		);
+
+		// Restored ranges are kept on the next restart
+		store_state(&db, &state).unwrap();
+		let state = load_state(&db, &[]).unwrap();
+		assert_eq!(
+			state.restored_confidence_achieved,
+			Some(BlockRange { first: 6, last: 10 })
+		);
+	}
+}
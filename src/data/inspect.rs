@@ -0,0 +1,410 @@
+//! Database inspection, used by the command line tools to dump, export and import stored data.
+//!
+//! All values are read and written through the [`Database`] trait,
+//! so the output uses the same decoding as the running client.
+
+use super::{
+	Database, FinalitySyncCheckpoint, Key, KeyRange, StoredState, WriteBatch, APP_DATA_CF,
+	BLOCK_HEADER_CF, CONFIDENCE_FACTOR_CF, EQUIVOCATION_CF, JUSTIFICATION_CF, SAMPLING_REPORT_CF,
+	STATE_CF,
+};
+use crate::{
+	finality::Equivocation,
+	network::SamplingReport,
+	types::{GrandpaJustification, TrustedCheckpoint},
+};
+use avail_subxt::primitives::Header as DaHeader;
+use codec::{Decode, Encode};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use kate_recovery::com::AppData;
+use serde::{Deserialize, Serialize};
+use sp_core::blake2_256;
+use std::{collections::BTreeMap, ops::RangeInclusive};
+
+/// Data stored for a single block
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct BlockDump {
+	pub block_number: u32,
+	pub header: Option<DaHeader>,
+	pub confidence: Option<u32>,
+	/// App data per application ID
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub app_data: BTreeMap<u32, AppData>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub sampling_report: Option<SamplingReport>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub justification: Option<GrandpaJustification>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub equivocation: Option<Equivocation>,
+}
+
+/// Portable database snapshot
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Snapshot {
+	pub state: Option<StoredState>,
+	pub finality_sync_checkpoint: Option<FinalitySyncCheckpoint>,
+	pub blocks: Vec<BlockDump>,
+}
+
+/// Column family statistics
+#[derive(Serialize, Debug)]
+pub struct ColumnFamilyStats {
+	pub column_family: &'static str,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub app_id: Option<u32>,
+	pub count: usize,
+	pub first_block: Option<u32>,
+	pub last_block: Option<u32>,
+}
+
+/// Dumps headers, confidence, app data, sampling reports, justifications and equivocations
+/// stored for the given block range. App data is dumped for the given application IDs.
+pub fn dump(
+	db: &impl Database,
+	app_ids: &[u32],
+	blocks: RangeInclusive<u32>,
+) -> Result<Vec<BlockDump>> {
+	let mut dumps = BTreeMap::<u32, BlockDump>::new();
+	let mut entry = |block_number| {
+		dumps.entry(block_number).or_insert_with(|| BlockDump {
+			block_number,
+			..Default::default()
+		})
+	};
+
+	for header in db.iter::<DaHeader>(KeyRange::BlockHeader(blocks.clone()))? {
+		let (block_number, header) = header.wrap_err("Failed to read block header")?;
+		entry(block_number).header = Some(header);
+	}
+
+	for count in db.iter::<u32>(KeyRange::VerifiedCellCount(blocks.clone()))? {
+		let (block_number, count) = count.wrap_err("Failed to read confidence")?;
+		entry(block_number).confidence = Some(count);
+	}
+
+	for &app_id in app_ids {
+		for data in db.iter::<AppData>(KeyRange::AppData(app_id, blocks.clone()))? {
+			let (block_number, data) = data.wrap_err("Failed to read app data")?;
+			entry(block_number).app_data.insert(app_id, data);
+		}
+	}
+
+	for report in db.iter::<SamplingReport>(KeyRange::SamplingReport(blocks.clone()))? {
+		let (block_number, report) = report.wrap_err("Failed to read sampling report")?;
+		entry(block_number).sampling_report = Some(report);
+	}
+
+	for justification in db.iter::<GrandpaJustification>(KeyRange::Justification(blocks.clone()))? {
+		let (block_number, justification) =
+			justification.wrap_err("Failed to read justification")?;
+		entry(block_number).justification = Some(justification);
+	}
+
+	for equivocation in db.iter::<Equivocation>(KeyRange::Equivocation(blocks))? {
+		let (block_number, equivocation) = equivocation.wrap_err("Failed to read equivocation")?;
+		entry(block_number).equivocation = Some(equivocation);
+	}
+
+	Ok(dumps.into_values().collect())
+}
+
+/// Exports all stored block data, state and finality checkpoint into the snapshot.
+pub fn export(db: &impl Database, app_ids: &[u32]) -> Result<Snapshot> {
+	Ok(Snapshot {
+		state: db.get(Key::State).wrap_err("Failed to read state")?,
+		finality_sync_checkpoint: db
+			.get(Key::FinalitySyncCheckpoint)
+			.wrap_err("Failed to read finality sync checkpoint")?,
+		blocks: dump(db, app_ids, 0..=u32::MAX)?,
+	})
+}
+
+/// Imports snapshot into the database atomically, returns number of imported blocks.
+pub fn import(db: &impl Database, snapshot: Snapshot) -> Result<usize> {
+	let mut batch = db.batch();
+
+	if let Some(state) = snapshot.state {
+		batch.put(Key::State, state)?;
+	}
+
+	if let Some(checkpoint) = snapshot.finality_sync_checkpoint {
+		batch.put(Key::FinalitySyncCheckpoint, checkpoint)?;
+	}
+
+	let count = snapshot.blocks.len();
+	for block in snapshot.blocks {
+		if let Some(header) = block.header {
+			batch.put(Key::BlockHeader(block.block_number), header)?;
+		}
+		if let Some(confidence) = block.confidence {
+			batch.put(Key::VerifiedCellCount(block.block_number), confidence)?;
+		}
+		for (app_id, data) in block.app_data {
+			batch.put(Key::AppData(app_id, block.block_number), data)?;
+		}
+		if let Some(report) = block.sampling_report {
+			batch.put(Key::SamplingReport(block.block_number), report)?;
+		}
+		if let Some(justification) = block.justification {
+			batch.put(Key::Justification(block.block_number), justification)?;
+		}
+		if let Some(equivocation) = block.equivocation {
+			batch.put(Key::Equivocation(block.block_number), equivocation)?;
+		}
+	}
+
+	db.write(batch).wrap_err("Failed to write snapshot")?;
+	Ok(count)
+}
+
+/// Returns stored finality sync checkpoint as trusted checkpoint.
+/// Header of the checkpoint block has to be stored, since its hash is part of the trusted checkpoint.
+pub fn checkpoint(db: &impl Database) -> Result<TrustedCheckpoint> {
+	let checkpoint: FinalitySyncCheckpoint = db
+		.get(Key::FinalitySyncCheckpoint)
+		.wrap_err("Failed to read finality sync checkpoint")?
+		.ok_or_else(|| eyre!("Finality sync checkpoint is not stored"))?;
+	let header: DaHeader = db
+		.get(Key::BlockHeader(checkpoint.number))
+		.wrap_err("Failed to read block header")?
+		.ok_or_else(|| eyre!("Header of block no. {} is not stored", checkpoint.number))?;
+
+	Ok(TrustedCheckpoint {
+		number: checkpoint.number,
+		hash: header.using_encoded(blake2_256).into(),
+		set_id: checkpoint.set_id,
+		validator_set: checkpoint.validator_set,
+	})
+}
+
+fn range_stats<T>(
+	db: &impl Database,
+	column_family: &'static str,
+	range: KeyRange,
+) -> Result<ColumnFamilyStats>
+where
+	for<'de> T: Deserialize<'de> + Decode,
+{
+	let app_id = match range {
+		KeyRange::AppData(app_id, _) => Some(app_id),
+		_ => None,
+	};
+	let mut stats = ColumnFamilyStats {
+		column_family,
+		app_id,
+		count: 0,
+		first_block: None,
+		last_block: None,
+	};
+
+	for entry in db.iter::<T>(range)? {
+		let (block_number, _) =
+			entry.wrap_err_with(|| format!("Failed to read {column_family}"))?;
+		stats.count += 1;
+		stats.first_block.get_or_insert(block_number);
+		stats.last_block = Some(block_number);
+	}
+
+	Ok(stats)
+}
+
+/// Returns number of stored values and stored block range per column family.
+/// App data statistics are returned for the given application IDs.
+pub fn stats(db: &impl Database, app_ids: &[u32]) -> Result<Vec<ColumnFamilyStats>> {
+	let mut stats = vec![
+		range_stats::<u32>(
+			db,
+			CONFIDENCE_FACTOR_CF,
+			KeyRange::VerifiedCellCount(0..=u32::MAX),
+		)?,
+		range_stats::<DaHeader>(db, BLOCK_HEADER_CF, KeyRange::BlockHeader(0..=u32::MAX))?,
+		range_stats::<SamplingReport>(
+			db,
+			SAMPLING_REPORT_CF,
+			KeyRange::SamplingReport(0..=u32::MAX),
+		)?,
+		range_stats::<GrandpaJustification>(
+			db,
+			JUSTIFICATION_CF,
+			KeyRange::Justification(0..=u32::MAX),
+		)?,
+		range_stats::<Equivocation>(db, EQUIVOCATION_CF, KeyRange::Equivocation(0..=u32::MAX))?,
+	];
+
+	for &app_id in app_ids {
+		stats.push(range_stats::<AppData>(
+			db,
+			APP_DATA_CF,
+			KeyRange::AppData(app_id, 0..=u32::MAX),
+		)?);
+	}
+
+	let state_count = [
+		db.get::<StoredState>(Key::State)?.is_some(),
+		db.get::<u32>(Key::SchemaVersion)?.is_some(),
+		db.get::<FinalitySyncCheckpoint>(Key::FinalitySyncCheckpoint)?
+			.is_some(),
+	]
+	.into_iter()
+	.filter(|is_stored| *is_stored)
+	.count();
+
+	stats.push(ColumnFamilyStats {
+		column_family: STATE_CF,
+		app_id: None,
+		count: state_count,
+		first_block: None,
+		last_block: None,
+	});
+
+	Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{checkpoint, dump, export, import, stats};
+	use crate::{
+		data::{
+			mem_db::MemoryDB, Database, FinalitySyncCheckpoint, Key, StoredState, APP_DATA_CF,
+			CONFIDENCE_FACTOR_CF, EQUIVOCATION_CF, JUSTIFICATION_CF, SAMPLING_REPORT_CF,
+		},
+		finality::Equivocation,
+		network::SamplingReport,
+		types::{Commit, GrandpaJustification, Precommit, SignedPrecommit},
+	};
+	use avail_subxt::utils::H256;
+	use sp_core::ed25519::{Public, Signature};
+
+	fn sampling_report(block_number: u32) -> SamplingReport {
+		SamplingReport {
+			block_number,
+			seed: H256::repeat_byte(1),
+			cells: vec![],
+			dht_fetch_duration: 10,
+			rpc_fetch_duration: None,
+			retries: 0,
+		}
+	}
+
+	fn justification(block_number: u32) -> GrandpaJustification {
+		GrandpaJustification {
+			round: 1,
+			commit: Commit {
+				target_hash: H256::repeat_byte(2),
+				target_number: block_number,
+				precommits: vec![],
+			},
+			votes_ancestries: vec![],
+		}
+	}
+
+	fn equivocation(block_number: u32) -> Equivocation {
+		let signed_precommit = |byte| SignedPrecommit {
+			precommit: Precommit {
+				target_hash: H256::repeat_byte(byte),
+				target_number: block_number,
+			},
+			signature: Signature::from_raw([0u8; 64]),
+			id: Public::from_raw([1u8; 32]),
+		};
+		Equivocation {
+			set_id: 1,
+			round: 1,
+			block_number,
+			signer: Public::from_raw([1u8; 32]),
+			first: signed_precommit(3),
+			second: signed_precommit(4),
+		}
+	}
+
+	#[test]
+	fn dump_block_range() {
+		let db = MemoryDB::default();
+		for block_number in 1..=5 {
+			db.put(Key::VerifiedCellCount(block_number), block_number)
+				.unwrap();
+		}
+		db.put(Key::AppData(1, 3), vec![vec![3u8]]).unwrap();
+
+		let blocks = dump(&db, &[1], 2..=4).unwrap();
+		let numbers = blocks.iter().map(|block| block.block_number);
+		assert_eq!(numbers.collect::<Vec<_>>(), vec![2, 3, 4]);
+		assert_eq!(blocks[1].confidence, Some(3));
+		assert_eq!(blocks[1].app_data.get(&1), Some(&vec![vec![3u8]]));
+		assert!(blocks[0].app_data.is_empty());
+		assert!(blocks[0].header.is_none());
+	}
+
+	#[test]
+	fn export_import_roundtrip() {
+		let db = MemoryDB::default();
+		for block_number in 1..=3 {
+			db.put(Key::VerifiedCellCount(block_number), 10u32).unwrap();
+			db.put(Key::AppData(1, block_number), vec![vec![1u8]])
+				.unwrap();
+		}
+		db.put(Key::SamplingReport(2), sampling_report(2)).unwrap();
+		db.put(Key::Justification(3), justification(3)).unwrap();
+		db.put(Key::Equivocation(3), equivocation(3)).unwrap();
+		db.put(Key::State, StoredState::default()).unwrap();
+
+		let snapshot = export(&db, &[1]).unwrap();
+		let snapshot = serde_json::to_string(&snapshot).unwrap();
+
+		let imported_db = MemoryDB::default();
+		let snapshot = serde_json::from_str(&snapshot).unwrap();
+		assert_eq!(import(&imported_db, snapshot).unwrap(), 3);
+
+		let count = imported_db.get::<u32>(Key::VerifiedCellCount(2)).unwrap();
+		assert_eq!(count, Some(10));
+		let data = imported_db.get::<Vec<Vec<u8>>>(Key::AppData(1, 3)).unwrap();
+		assert_eq!(data, Some(vec![vec![1u8]]));
+		let state = imported_db.get::<StoredState>(Key::State).unwrap();
+		assert!(state.is_some());
+		let report = imported_db.get::<SamplingReport>(Key::SamplingReport(2));
+		assert_eq!(report.unwrap(), Some(sampling_report(2)));
+		let stored = imported_db.get::<GrandpaJustification>(Key::Justification(3));
+		let stored = stored
+			.unwrap()
+			.map(|justification| justification.commit.target_number);
+		assert_eq!(stored, Some(3));
+		let stored = imported_db.get::<Equivocation>(Key::Equivocation(3));
+		assert_eq!(stored.unwrap(), Some(equivocation(3)));
+
+		let stats = stats(&imported_db, &[1]).unwrap();
+		let confidence = stats
+			.iter()
+			.find(|stats| stats.column_family == CONFIDENCE_FACTOR_CF)
+			.unwrap();
+		assert_eq!(confidence.count, 3);
+		assert_eq!(confidence.first_block, Some(1));
+		assert_eq!(confidence.last_block, Some(3));
+		let app_data = stats
+			.iter()
+			.find(|stats| stats.column_family == APP_DATA_CF)
+			.unwrap();
+		assert_eq!(app_data.count, 3);
+		for column_family in [SAMPLING_REPORT_CF, JUSTIFICATION_CF, EQUIVOCATION_CF] {
+			let stored = stats
+				.iter()
+				.find(|stats| stats.column_family == column_family)
+				.unwrap();
+			assert_eq!(stored.count, 1);
+		}
+	}
+
+	#[test]
+	fn checkpoint_requires_stored_header() {
+		let db = MemoryDB::default();
+		assert!(checkpoint(&db).is_err());
+
+		let stored = FinalitySyncCheckpoint {
+			number: 5,
+			set_id: 1,
+			validator_set: vec![],
+		};
+		db.put(Key::FinalitySyncCheckpoint, stored).unwrap();
+		// Hash of the checkpoint block is not known without the block header
+		assert!(checkpoint(&db).is_err());
+	}
+}
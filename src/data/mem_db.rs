@@ -1,5 +1,7 @@
 use crate::data::{
-	Database, Key, APP_DATA_CF, BLOCK_HEADER_CF, CONFIDENCE_FACTOR_CF, FINALITY_SYNC_CHECKPOINT_KEY,
+	BlockIterator, Database, Key, KeyRange, WriteBatch, APP_DATA_CF, BLOCK_HEADER_CF,
+	CONFIDENCE_FACTOR_CF, EQUIVOCATION_CF, FINALITY_SYNC_CHECKPOINT_KEY, JUSTIFICATION_CF,
+	SAMPLING_REPORT_CF, SCHEMA_VERSION_KEY, STATE_KEY,
 };
 use color_eyre::eyre::{eyre, Result};
 use serde::{Deserialize, Serialize};
@@ -24,8 +26,30 @@ impl Default for MemoryDB {
 	}
 }
 
+#[derive(Default)]
+pub struct MemoryBatch {
+	operations: Vec<(HashMapKey, Option<String>)>,
+}
+
+impl WriteBatch for MemoryBatch {
+	fn put<T>(&mut self, key: Key, value: T) -> Result<()>
+	where
+		T: Serialize,
+	{
+		let value = serde_json::to_string(&value)?;
+		self.operations.push((key.into(), Some(value)));
+		Ok(())
+	}
+
+	fn delete(&mut self, key: Key) -> Result<()> {
+		self.operations.push((key.into(), None));
+		Ok(())
+	}
+}
+
 impl Database for MemoryDB {
 	type Key = HashMapKey;
+	type Batch = MemoryBatch;
 	fn put<T>(&self, key: Key, value: T) -> Result<()>
 	where
 		T: Serialize,
@@ -51,6 +75,68 @@ impl Database for MemoryDB {
 		map.remove(&key.into());
 		Ok(())
 	}
+
+	fn delete_before(&self, column_family: &str, block_number: u32) -> Result<usize> {
+		let mut map = self.map.write().expect("Lock acquired");
+		let prefix = format!("{column_family}:");
+		let len = map.len();
+		// Block number is the last part of the column family keys
+		map.retain(|HashMapKey(key), _| {
+			let number = key
+				.strip_prefix(&prefix)
+				.and_then(|key| key.rsplit(':').next())
+				.and_then(|number| number.parse::<u32>().ok());
+			!matches!(number, Some(number) if number < block_number)
+		});
+		Ok(len - map.len())
+	}
+
+	fn iter<'a, T>(&'a self, range: KeyRange) -> Result<BlockIterator<'a, T>>
+	where
+		for<'de> T: Deserialize<'de> + 'a,
+	{
+		let map = self.map.read().expect("Lock acquired");
+		let (prefix, blocks) = match range {
+			KeyRange::AppData(app_id, blocks) => (format!("{APP_DATA_CF}:{app_id}:"), blocks),
+			KeyRange::BlockHeader(blocks) => (format!("{BLOCK_HEADER_CF}:"), blocks),
+			KeyRange::VerifiedCellCount(blocks) => (format!("{CONFIDENCE_FACTOR_CF}:"), blocks),
+			KeyRange::SamplingReport(blocks) => (format!("{SAMPLING_REPORT_CF}:"), blocks),
+			KeyRange::Justification(blocks) => (format!("{JUSTIFICATION_CF}:"), blocks),
+			KeyRange::Equivocation(blocks) => (format!("{EQUIVOCATION_CF}:"), blocks),
+		};
+
+		let mut values = map
+			.iter()
+			.filter_map(|(HashMapKey(key), value)| {
+				let block_number = key.strip_prefix(&prefix)?.parse::<u32>().ok()?;
+				blocks
+					.contains(&block_number)
+					.then(|| (block_number, value.clone()))
+			})
+			.collect::<Vec<_>>();
+		values.sort_by_key(|(block_number, _)| *block_number);
+
+		Ok(Box::new(values.into_iter().map(|(block_number, value)| {
+			serde_json::from_str(&value)
+				.map(|value| (block_number, value))
+				.map_err(|error| eyre!("{error}"))
+		})))
+	}
+
+	fn batch(&self) -> Self::Batch {
+		MemoryBatch::default()
+	}
+
+	fn write(&self, batch: Self::Batch) -> Result<()> {
+		let mut map = self.map.write().expect("Lock acquired");
+		for (key, value) in batch.operations {
+			match value {
+				Some(value) => map.insert(key, value),
+				None => map.remove(&key),
+			};
+		}
+		Ok(())
+	}
 }
 
 impl From<Key> for HashMapKey {
@@ -65,7 +151,18 @@ impl From<Key> for HashMapKey {
 			Key::VerifiedCellCount(block_number) => {
 				HashMapKey(format!("{CONFIDENCE_FACTOR_CF}:{block_number}"))
 			},
+			Key::SamplingReport(block_number) => {
+				HashMapKey(format!("{SAMPLING_REPORT_CF}:{block_number}"))
+			},
+			Key::Justification(block_number) => {
+				HashMapKey(format!("{JUSTIFICATION_CF}:{block_number}"))
+			},
+			Key::Equivocation(block_number) => {
+				HashMapKey(format!("{EQUIVOCATION_CF}:{block_number}"))
+			},
 			Key::FinalitySyncCheckpoint => HashMapKey(FINALITY_SYNC_CHECKPOINT_KEY.to_string()),
+			Key::State => HashMapKey(STATE_KEY.to_string()),
+			Key::SchemaVersion => HashMapKey(SCHEMA_VERSION_KEY.to_string()),
 		}
 	}
 }
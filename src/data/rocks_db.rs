@@ -1,11 +1,19 @@
-use crate::data::{self, Key, APP_DATA_CF, BLOCK_HEADER_CF, CONFIDENCE_FACTOR_CF, STATE_CF};
+use crate::data::{
+	self, BlockIterator, Key, KeyRange, APP_DATA_CF, BLOCK_HEADER_CF, CONFIDENCE_FACTOR_CF,
+	EQUIVOCATION_CF, JUSTIFICATION_CF, KADEMLIA_STORE_CF, SAMPLING_REPORT_CF, STATE_CF,
+};
 use codec::{Decode, Encode};
 use color_eyre::eyre::{eyre, Context, Result};
-use rocksdb::{ColumnFamilyDescriptor, Options};
+use rocksdb::{ColumnFamilyDescriptor, Direction, IteratorMode, Options, WriteBatch};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use super::FINALITY_SYNC_CHECKPOINT_KEY;
+use super::{FINALITY_SYNC_CHECKPOINT_KEY, SCHEMA_VERSION_KEY, STATE_KEY};
+
+mod migrations;
+
+/// Maximum number of deletes written in a single batch when pruning
+const DELETE_BATCH_SIZE: usize = 10_000;
 
 #[derive(Clone)]
 pub struct RocksDB {
@@ -19,6 +27,10 @@ impl RocksDB {
 			ColumnFamilyDescriptor::new(BLOCK_HEADER_CF, Options::default()),
 			ColumnFamilyDescriptor::new(APP_DATA_CF, Options::default()),
 			ColumnFamilyDescriptor::new(STATE_CF, Options::default()),
+			ColumnFamilyDescriptor::new(KADEMLIA_STORE_CF, Options::default()),
+			ColumnFamilyDescriptor::new(SAMPLING_REPORT_CF, Options::default()),
+			ColumnFamilyDescriptor::new(JUSTIFICATION_CF, Options::default()),
+			ColumnFamilyDescriptor::new(EQUIVOCATION_CF, Options::default()),
 		];
 
 		let mut db_opts = Options::default();
@@ -26,18 +38,77 @@ impl RocksDB {
 		db_opts.create_missing_column_families(true);
 
 		let db = rocksdb::DB::open_cf_descriptors(&db_opts, path, cf_opts)?;
-		Ok(RocksDB { db: Arc::new(db) })
+		let db = RocksDB { db: Arc::new(db) };
+		migrations::migrate(&db).wrap_err("Database migration failed")?;
+		Ok(db)
+	}
+
+	/// Returns underlying RocksDB instance, used by the components with custom storage layout
+	pub(crate) fn inner(&self) -> Arc<rocksdb::DB> {
+		self.db.clone()
+	}
+}
+
+pub struct RocksBatch {
+	db: Arc<rocksdb::DB>,
+	batch: WriteBatch,
+}
+
+impl data::WriteBatch for RocksBatch {
+	fn put<T>(&mut self, key: Key, value: T) -> Result<()>
+	where
+		T: Serialize + Encode,
+	{
+		let (column_family, key) = key.into();
+		let Some(cf) = column_family else {
+			self.batch.put(key, <T>::encode(&value));
+			return Ok(());
+		};
+
+		let cf_handle = self
+			.db
+			.cf_handle(cf)
+			.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+		self.batch.put_cf(&cf_handle, key, <T>::encode(&value));
+		Ok(())
+	}
+
+	fn delete(&mut self, key: Key) -> Result<()> {
+		let (column_family, key) = key.into();
+		let Some(cf) = column_family else {
+			self.batch.delete(key);
+			return Ok(());
+		};
+
+		let cf_handle = self
+			.db
+			.cf_handle(cf)
+			.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+		self.batch.delete_cf(&cf_handle, key);
+		Ok(())
 	}
 }
 
 type RocksKey = (Option<&'static str>, Vec<u8>);
 
+/// Decodes block number from the column family key, which is encoded as big endian suffix
+fn key_block_number(column_family: &str, key: &[u8]) -> Option<u32> {
+	match column_family {
+		APP_DATA_CF | CONFIDENCE_FACTOR_CF | BLOCK_HEADER_CF | SAMPLING_REPORT_CF
+		| JUSTIFICATION_CF | EQUIVOCATION_CF => {
+			let suffix = key.get(key.len().checked_sub(4)?..)?;
+			Some(u32::from_be_bytes(suffix.try_into().ok()?))
+		},
+		_ => None,
+	}
+}
+
 impl From<Key> for (Option<&'static str>, Vec<u8>) {
 	fn from(key: Key) -> Self {
 		match key {
 			Key::AppData(app_id, block_number) => (
 				Some(APP_DATA_CF),
-				format!("{app_id}:{block_number}").into_bytes(),
+				[app_id.to_be_bytes(), block_number.to_be_bytes()].concat(),
 			),
 			Key::BlockHeader(block_number) => {
 				(Some(BLOCK_HEADER_CF), block_number.to_be_bytes().to_vec())
@@ -46,16 +117,29 @@ impl From<Key> for (Option<&'static str>, Vec<u8>) {
 				Some(CONFIDENCE_FACTOR_CF),
 				block_number.to_be_bytes().to_vec(),
 			),
+			Key::SamplingReport(block_number) => (
+				Some(SAMPLING_REPORT_CF),
+				block_number.to_be_bytes().to_vec(),
+			),
+			Key::Justification(block_number) => {
+				(Some(JUSTIFICATION_CF), block_number.to_be_bytes().to_vec())
+			},
+			Key::Equivocation(block_number) => {
+				(Some(EQUIVOCATION_CF), block_number.to_be_bytes().to_vec())
+			},
 			Key::FinalitySyncCheckpoint => (
 				Some(STATE_CF),
 				FINALITY_SYNC_CHECKPOINT_KEY.as_bytes().to_vec(),
 			),
+			Key::State => (Some(STATE_CF), STATE_KEY.as_bytes().to_vec()),
+			Key::SchemaVersion => (Some(STATE_CF), SCHEMA_VERSION_KEY.as_bytes().to_vec()),
 		}
 	}
 }
 
 impl data::Database for RocksDB {
 	type Key = RocksKey;
+	type Batch = RocksBatch;
 
 	fn put<T>(&self, key: Key, value: T) -> Result<()>
 	where
@@ -126,4 +210,129 @@ impl data::Database for RocksDB {
 			.delete_cf(&cf_handle, key)
 			.wrap_err("Delete operation with Column Family failed on RocksDB")
 	}
+
+	fn delete_before(&self, column_family: &str, block_number: u32) -> Result<usize> {
+		let cf_handle = self
+			.db
+			.cf_handle(column_family)
+			.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+
+		let write = |batch: WriteBatch| {
+			let count = batch.len();
+			self.db
+				.write(batch)
+				.wrap_err("Delete operation with Column Family failed on RocksDB")
+				.map(|_| count)
+		};
+
+		let mut deleted = 0;
+		let mut batch = WriteBatch::default();
+		let mut iterator = self.db.raw_iterator_cf(&cf_handle);
+		iterator.seek_to_first();
+		while let Some(key) = iterator.key() {
+			match key_block_number(column_family, key) {
+				Some(number) if number < block_number => {
+					batch.delete_cf(&cf_handle, key);
+					iterator.next();
+				},
+				// App data keys are prefixed with the big endian app ID,
+				// so the rest of the app ID keys are retained, and pruning continues with the next app ID
+				Some(_) if column_family == APP_DATA_CF => {
+					let Some(app_id) = key.get(..4).and_then(|app_id| app_id.try_into().ok())
+					else {
+						break;
+					};
+					let Some(next_app_id) = u32::from_be_bytes(app_id).checked_add(1) else {
+						break;
+					};
+					iterator.seek(next_app_id.to_be_bytes());
+				},
+				// Block numbers are big endian encoded, so the rest of the keys are retained
+				Some(_) => break,
+				None => iterator.next(),
+			}
+
+			if batch.len() >= DELETE_BATCH_SIZE {
+				deleted += write(std::mem::take(&mut batch))?;
+			}
+		}
+		iterator
+			.status()
+			.wrap_err("Iterate operation with Column Family failed on RocksDB")?;
+
+		deleted += write(batch)?;
+		Ok(deleted)
+	}
+
+	fn iter<'a, T>(&'a self, range: KeyRange) -> Result<BlockIterator<'a, T>>
+	where
+		for<'de> T: Deserialize<'de> + Decode + 'a,
+	{
+		let (from, to) = range.bounds();
+		let (Some(column_family), from) = RocksKey::from(from) else {
+			return Err(eyre!(
+				"Range iteration is supported only with Column Family"
+			));
+		};
+		let (_, to) = RocksKey::from(to);
+
+		let cf_handle = self
+			.db
+			.cf_handle(column_family)
+			.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+
+		let iterator = self
+			.db
+			.iterator_cf(&cf_handle, IteratorMode::From(&from, Direction::Forward))
+			.take_while(move |entry| !matches!(entry, Ok((key, _)) if key[..] > to[..]))
+			.map(move |entry| {
+				let (key, value) =
+					entry.wrap_err("Iterate operation with Column Family failed on RocksDB")?;
+				let block_number = key_block_number(column_family, &key)
+					.ok_or_else(|| eyre!("Failed decoding block number from the key"))?;
+				let value = <T>::decode(&mut &value[..]).wrap_err("Failed decoding the value")?;
+				Ok((block_number, value))
+			});
+
+		Ok(Box::new(iterator))
+	}
+
+	fn batch(&self) -> Self::Batch {
+		RocksBatch {
+			db: self.db.clone(),
+			batch: WriteBatch::default(),
+		}
+	}
+
+	fn write(&self, batch: Self::Batch) -> Result<()> {
+		self.db
+			.write(batch.batch)
+			.wrap_err("Write batch operation failed on RocksDB")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::RocksDB;
+	use crate::data::{Database, Key, APP_DATA_CF};
+
+	#[test]
+	fn delete_before_prunes_each_app_id() {
+		let path = std::env::temp_dir().join(format!("avail_light_{}", uuid::Uuid::new_v4()));
+		let db = RocksDB::open(path.to_str().unwrap()).unwrap();
+		for app_id in [1, 2, u32::MAX] {
+			for block_number in 1..=5 {
+				db.put(Key::AppData(app_id, block_number), vec![vec![1u8]])
+					.unwrap();
+			}
+		}
+
+		assert_eq!(db.delete_before(APP_DATA_CF, 4).unwrap(), 9);
+		for app_id in [1, 2, u32::MAX] {
+			let pruned = db.get::<Vec<Vec<u8>>>(Key::AppData(app_id, 3)).unwrap();
+			assert!(pruned.is_none());
+			let retained = db.get::<Vec<Vec<u8>>>(Key::AppData(app_id, 4)).unwrap();
+			assert!(retained.is_some());
+		}
+	}
 }
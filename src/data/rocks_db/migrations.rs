@@ -0,0 +1,125 @@
+//! Database schema migrations.
+//!
+//! Schema version is stored in the state column family, and databases created before
+//! versioning was introduced are considered to be on version 0.
+//! Each migration upgrades the database to the next version, and the new version is stored
+//! after the migration succeeds, so interrupted migrations are resumed on the next open.
+
+use super::{RocksDB, RocksKey};
+use crate::data::{Database, Key, APP_DATA_CF};
+use color_eyre::eyre::{eyre, Context, Result};
+use rocksdb::{IteratorMode, WriteBatch};
+use tracing::info;
+
+/// Current database schema version
+pub const SCHEMA_VERSION: u32 = 1;
+
+type Migration = fn(&RocksDB) -> Result<()>;
+
+/// Migrations ordered by the schema version they are upgrading from
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [encode_app_data_keys];
+
+/// Upgrades database to the current schema version.
+/// Fails if the database is created with the newer, unknown schema version.
+pub fn migrate(db: &RocksDB) -> Result<()> {
+	let version = db
+		.get::<u32>(Key::SchemaVersion)
+		.wrap_err("Failed to get database schema version")?
+		.unwrap_or(0);
+
+	if version > SCHEMA_VERSION {
+		return Err(eyre!(
+			"Database schema version {version} is newer than supported version {SCHEMA_VERSION}, upgrade the light client or clean the database"
+		));
+	}
+
+	for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+		let to = from as u32 + 1;
+		info!("Migrating database schema from version {from} to {to}...");
+		migration(db).wrap_err_with(|| format!("Failed to migrate to version {to}"))?;
+		db.put(Key::SchemaVersion, to)
+			.wrap_err("Failed to store database schema version")?;
+	}
+
+	Ok(())
+}
+
+/// Re-encodes app data keys stored as `"{app_id}:{block_number}"` strings,
+/// into the order preserving binary encoding.
+fn encode_app_data_keys(db: &RocksDB) -> Result<()> {
+	let cf_handle = db
+		.db
+		.cf_handle(APP_DATA_CF)
+		.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+
+	let mut batch = WriteBatch::default();
+	for entry in db.db.iterator_cf(&cf_handle, IteratorMode::Start) {
+		let (key, value) = entry?;
+		let Some((app_id, block_number)) = std::str::from_utf8(&key)
+			.ok()
+			.and_then(|key| key.split_once(':'))
+			.and_then(|(app_id, block_number)| {
+				Some((app_id.parse().ok()?, block_number.parse().ok()?))
+			})
+		else {
+			continue;
+		};
+		let (_, new_key) = RocksKey::from(Key::AppData(app_id, block_number));
+		batch.put_cf(&cf_handle, new_key, value);
+		batch.delete_cf(&cf_handle, key);
+	}
+
+	db.db
+		.write(batch)
+		.wrap_err("App data keys migration failed on RocksDB")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{RocksDB, SCHEMA_VERSION};
+	use crate::data::{Database, Key, APP_DATA_CF};
+	use codec::Encode;
+	use std::{fs, path::PathBuf};
+
+	fn temp_path() -> PathBuf {
+		std::env::temp_dir().join(format!("avail_light_{}", uuid::Uuid::new_v4()))
+	}
+
+	#[test]
+	fn migrate_unversioned_database() {
+		let path = temp_path();
+		let path_str = path.to_str().unwrap();
+
+		let db = RocksDB::open(path_str).unwrap();
+		let cf_handle = db.db.cf_handle(APP_DATA_CF).unwrap();
+		db.db
+			.put_cf(&cf_handle, b"1:5", vec![vec![1u8]].encode())
+			.unwrap();
+		drop(cf_handle);
+		db.delete(Key::SchemaVersion).unwrap();
+		drop(db);
+
+		let db = RocksDB::open(path_str).unwrap();
+		let data = db.get::<Vec<Vec<u8>>>(Key::AppData(1, 5)).unwrap();
+		assert_eq!(data, Some(vec![vec![1u8]]));
+		let version = db.get::<u32>(Key::SchemaVersion).unwrap();
+		assert_eq!(version, Some(SCHEMA_VERSION));
+		drop(db);
+
+		fs::remove_dir_all(path).unwrap();
+	}
+
+	#[test]
+	fn refuse_unknown_schema_version() {
+		let path = temp_path();
+		let path_str = path.to_str().unwrap();
+
+		let db = RocksDB::open(path_str).unwrap();
+		db.put(Key::SchemaVersion, SCHEMA_VERSION + 1).unwrap();
+		drop(db);
+
+		assert!(RocksDB::open(path_str).is_err());
+
+		fs::remove_dir_all(path).unwrap();
+	}
+}
@@ -8,6 +8,7 @@
 //! # Notes
 //!
 //! In case delay is configured, block processing is delayed for configured time.
+//! Blocks missed due to the channel lag are handed over to the backfill, which verifies and publishes them.
 
 use async_trait::async_trait;
 use avail_subxt::{primitives::Header, utils::H256};
@@ -21,7 +22,11 @@ use kate_recovery::{
 use kate_recovery::{data::Cell, matrix::RowIndex};
 use mockall::automock;
 use sp_core::blake2_256;
-use std::{sync::Arc, time::Instant};
+use std::{
+	sync::{Arc, Mutex},
+	time::Instant,
+};
+use tokio::sync::broadcast::error::RecvError;
 use tracing::{debug, error, info, warn};
 
 use crate::{
@@ -31,9 +36,10 @@ use crate::{
 		rpc::{Client as RpcClient, Event},
 	},
 	shutdown::Controller,
+	sync_client,
 	telemetry::{MetricCounter, MetricValue, Metrics},
-	types::{BlockVerified, ClientChannels, FatClientConfig},
-	utils::extract_kate,
+	types::{BlockVerified, ClientChannels, FatClientConfig, State},
+	utils::{block_gap, extract_kate},
 };
 
 #[async_trait]
@@ -192,6 +198,7 @@ pub async fn process_block(
 /// * `fat_client` - Fat client implementation
 /// * `cfg` - Fat client configuration
 /// * `metrics` -  Metrics registry
+/// * `state` - Processed blocks state
 /// * `channels` - Communication channels
 /// * `partition` - Assigned fat client partition
 /// * `shutdown` - Shutdown controller
@@ -200,12 +207,15 @@ pub async fn run(
 	db: impl Database + Clone,
 	cfg: FatClientConfig,
 	metrics: Arc<impl Metrics>,
+	state: Arc<Mutex<State>>,
 	mut channels: ClientChannels,
 	partition: Partition,
 	shutdown: Controller<String>,
 ) {
 	info!("Starting fat client...");
 
+	let mut last_block = None;
+
 	loop {
 		let (header, received_at) = match channels.rpc_event_receiver.recv().await {
 			Ok(event) => match event {
@@ -214,12 +224,22 @@ pub async fn run(
 					received_at,
 				} => (header, received_at),
 			},
+			// Missed blocks are detected on the next received header
+			Err(RecvError::Lagged(count)) => {
+				warn!("Fat client lagged behind, {count} messages skipped");
+				continue;
+			},
 			Err(error) => {
 				error!("Cannot receive message: {error}");
 				return;
 			},
 		};
 
+		if let Some(gap) = block_gap(last_block, header.number) {
+			sync_client::backfill(&channels.backfill_sender, &state, gap);
+		}
+		last_block = last_block.max(Some(header.number));
+
 		if let Some(seconds) = cfg.block_processing_delay.sleep_duration(received_at) {
 			if let Err(error) = metrics
 				.record(MetricValue::BlockProcessingDelay(seconds.as_secs_f64()))
@@ -1,14 +1,19 @@
-use std::collections::HashMap;
+use std::{
+	collections::{HashMap, HashSet},
+	fmt,
+};
 
-use codec::Encode;
+use avail_subxt::primitives::{grandpa::ConsensusLog, Header};
+use codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
 use sp_core::{
 	blake2_256,
 	ed25519::{self, Public},
 	Pair, H256,
 };
-use tracing::{info, warn};
+use tracing::{debug, info};
 
-use crate::types::{GrandpaJustification, SignerMessage};
+use crate::types::{Commit, GrandpaJustification, SignedPrecommit, SignerMessage};
 use color_eyre::{eyre::eyre, Result};
 
 #[derive(Clone, Debug)]
@@ -17,80 +22,305 @@ pub struct ValidatorSet {
 	pub validator_set: Vec<Public>,
 }
 
+impl ValidatorSet {
+	/// Returns validator set enacted by the authority set change
+	pub fn next(&self, change: &AuthoritySetChange) -> ValidatorSet {
+		ValidatorSet {
+			set_id: self.set_id + 1,
+			validator_set: change.validator_set.clone(),
+		}
+	}
+}
+
+/// Authority set change signaled in the block header
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthoritySetChange {
+	/// Validator set which finalizes blocks after the activation block
+	pub validator_set: Vec<Public>,
+	/// Last block finalized by the current validator set (signaling block number increased by the change delay)
+	pub activation: u32,
+	/// Forced changes are enacted once the activation block is imported, without being finalized
+	pub forced: bool,
+}
+
+/// Returns authority set changes signaled in the `FRNK` consensus logs of the block header.
+pub fn authority_set_changes(header: &Header) -> Vec<AuthoritySetChange> {
+	header
+		.digest
+		.logs
+		.iter()
+		.filter_map(|log| match log {
+			avail_subxt::config::substrate::DigestItem::Consensus(
+				[b'F', b'R', b'N', b'K'],
+				data,
+			) => match ConsensusLog::<u32>::decode(&mut data.as_slice()) {
+				Ok(ConsensusLog::ScheduledChange(change)) => Some((change, false)),
+				Ok(ConsensusLog::ForcedChange(_, change)) => Some((change, true)),
+				_ => None,
+			},
+			_ => None,
+		})
+		.map(|(change, forced)| AuthoritySetChange {
+			validator_set: change
+				.next_authorities
+				.into_iter()
+				.map(|(authority, _)| ed25519::Public::from_raw(authority.0 .0 .0))
+				.collect(),
+			activation: header.number.saturating_add(change.delay),
+			forced,
+		})
+		.collect()
+}
+
+/// Validator set together with the authority set changes waiting for activation,
+/// modeled on the GRANDPA authority set.
+///
+/// Pending changes are enacted in activation order, once the block after the activation block is processed.
+/// Enacted forced change discards all other pending changes, since they were signaled for the previous validator set.
+#[derive(Clone, Debug)]
+pub struct AuthoritySet {
+	pub current: ValidatorSet,
+	pending: Vec<AuthoritySetChange>,
+}
+
+impl AuthoritySet {
+	pub fn new(current: ValidatorSet) -> Self {
+		AuthoritySet {
+			current,
+			pending: vec![],
+		}
+	}
+
+	/// Returns `true` if there are changes waiting for activation
+	pub fn has_pending_changes(&self) -> bool {
+		!self.pending.is_empty()
+	}
+
+	/// Schedules authority set changes signaled in the block header.
+	/// Block can signal at most one standard and one forced change,
+	/// and only one forced change can be pending at a time.
+	pub fn schedule_changes(&mut self, header: &Header) -> Result<()> {
+		let (forced, standard): (Vec<_>, Vec<_>) = authority_set_changes(header)
+			.into_iter()
+			.partition(|change| change.forced);
+
+		if standard.len() > 1 || forced.len() > 1 {
+			return Err(eyre!(
+				"Block {} signals {} standard and {} forced authority set changes",
+				header.number,
+				standard.len(),
+				forced.len()
+			));
+		}
+
+		if !forced.is_empty() && self.pending.iter().any(|change| change.forced) {
+			return Err(eyre!(
+				"Block {} signals forced authority set change while another one is pending",
+				header.number
+			));
+		}
+
+		for change in standard.into_iter().chain(forced) {
+			debug!(
+				block_number = header.number,
+				activation = change.activation,
+				forced = change.forced,
+				"Authority set change scheduled"
+			);
+			// standard change is enacted before the forced change with the same activation block
+			let key = |change: &AuthoritySetChange| (change.activation, change.forced);
+			let index = self
+				.pending
+				.partition_point(|pending| key(pending) <= key(&change));
+			self.pending.insert(index, change);
+		}
+		Ok(())
+	}
+
+	/// Enacts changes activated before the given block, so the current validator set finalizes the block.
+	/// Returns enacted changes, together with the validator sets which finalized their activation blocks.
+	pub fn enact_changes(&mut self, block_number: u32) -> Vec<(ValidatorSet, AuthoritySetChange)> {
+		let mut enacted = vec![];
+		while self
+			.pending
+			.first()
+			.is_some_and(|change| change.activation < block_number)
+		{
+			let change = self.pending.remove(0);
+			let next = self.current.next(&change);
+			let previous = std::mem::replace(&mut self.current, next);
+			info!(
+				forced = change.forced,
+				"Validator set {} is active after block {}", self.current.set_id, change.activation
+			);
+			if change.forced {
+				self.pending.clear();
+			}
+			enacted.push((previous, change));
+		}
+		enacted
+	}
+}
+
+/// Evidence of the validator signing conflicting precommits in the same round
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct Equivocation {
+	pub set_id: u64,
+	pub round: u64,
+	/// Number of the block justified by the commit with the conflicting precommits
+	pub block_number: u32,
+	pub signer: Public,
+	pub first: SignedPrecommit,
+	pub second: SignedPrecommit,
+}
+
+/// Justification verification error
+#[derive(Debug, PartialEq)]
+pub enum FinalityError {
+	/// Precommit signature doesn't verify against the known set ID,
+	/// either the signature is invalid or the justification is signed by another validator set
+	InvalidSignature { set_id: u64, signer: Public },
+	/// Precommit target is not a descendant of the commit target
+	InvalidAncestry { signer: Public },
+	/// Precommit target number doesn't match the block number in the vote ancestry
+	InvalidTargetNumber { signer: Public, target_number: u32 },
+	/// Vote ancestry contains header which is not on the route of any precommit
+	UnusedAncestry { hash: H256 },
+	/// Validator signed the same precommit more than once
+	DuplicateSigner { signer: Public },
+	/// Validator signed conflicting precommits in the same round
+	Equivocation(Box<Equivocation>),
+	/// Number of precommits signed by the validator set is below the supermajority
+	NoSupermajority {
+		set_id: u64,
+		signatures: usize,
+		validators: usize,
+	},
+}
+
+impl fmt::Display for FinalityError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			FinalityError::InvalidSignature { set_id, signer } => {
+				write!(
+					f,
+					"Signature of {signer:?} is not valid for set ID {set_id}"
+				)
+			},
+			FinalityError::InvalidAncestry { signer } => {
+				write!(
+					f,
+					"Precommit of {signer:?} is not descendant of the commit target"
+				)
+			},
+			FinalityError::InvalidTargetNumber {
+				signer,
+				target_number,
+			} => write!(
+				f,
+				"Precommit of {signer:?} for block {target_number} contradicts the vote ancestry"
+			),
+			FinalityError::UnusedAncestry { hash } => {
+				write!(f, "Vote ancestry {hash:?} is not used by any precommit")
+			},
+			FinalityError::DuplicateSigner { signer } => {
+				write!(f, "Precommit of {signer:?} is duplicated")
+			},
+			FinalityError::Equivocation(equivocation) => write!(
+				f,
+				"Validator {:?} equivocated in round {} of set ID {}",
+				equivocation.signer, equivocation.round, equivocation.set_id
+			),
+			FinalityError::NoSupermajority {
+				set_id,
+				signatures,
+				validators,
+			} => write!(
+				f,
+				"Not signed by supermajority of validator set {set_id} ({signatures}/{validators})"
+			),
+		}
+	}
+}
+
+impl std::error::Error for FinalityError {}
+
+/// Verifies that justification is signed by the supermajority of the validator set.
+/// Signatures are verified against the validator set ID only.
+///
+/// Following GRANDPA rules, each validator can sign only one precommit in the round,
+/// precommit targets have to descend from the commit target through the vote ancestry, with matching block numbers,
+/// and vote ancestry cannot contain headers which are not used by any precommit.
 pub fn check_finality(
 	validator_set: &ValidatorSet,
 	justification: &GrandpaJustification,
-) -> Result<()> {
-	let ancestry_map: HashMap<H256, H256> = justification
+) -> Result<(), FinalityError> {
+	let ancestry_map: HashMap<H256, &Header> = justification
 		.votes_ancestries
 		.iter()
-		.map(|e| (Encode::using_encoded(e, blake2_256).into(), e.parent_hash))
+		.map(|header| (Encode::using_encoded(header, blake2_256).into(), header))
 		.collect();
 
 	if !ancestry_map.is_empty() {
-		info!("Votes ancestries found, mapping: {ancestry_map:?}");
+		debug!(
+			"Votes ancestries found: {:?}",
+			ancestry_map.keys().collect::<Vec<_>>()
+		);
 	}
 
+	let mut signed = HashMap::<Public, &SignedPrecommit>::new();
+	let mut visited = HashSet::<H256>::new();
+
 	// verify all the Signatures of the Justification signs,
 	// verify the hash of the block and extract all the signer addresses
-	let signer_addresses = justification
-		.commit
-		.precommits
-		.iter()
-		.map(|precommit| {
-			// form a message which is signed in the Justification, it's a triplet of a Precommit,
-			// round number and set_id (taken from Substrate code)
-			let signed_message = Encode::encode(&(
-				&SignerMessage::PrecommitMessage(precommit.precommit.clone()),
-				&justification.round,
-				&validator_set.set_id, // Set ID is needed here.
-			));
-			let mut is_ok = <ed25519::Pair as Pair>::verify(
-				&precommit.signature,
-				signed_message,
-				&precommit.id,
-			);
-			if !is_ok {
-				warn!(
-					"Signature verification fails with default set_id {}, trying alternatives.",
-					validator_set.set_id
-				);
-				for set_id_m in (validator_set.set_id - 10)..(validator_set.set_id + 10) {
-					let s_m = Encode::encode(&(
-						&SignerMessage::PrecommitMessage(precommit.precommit.clone()),
-						&justification.round,
-						&set_id_m,
-					));
-					is_ok =
-						<ed25519::Pair as Pair>::verify(&precommit.signature, &s_m, &precommit.id);
-					if is_ok {
-						info!("Signature match with set_id={set_id_m}");
-						break;
-					}
-				}
+	for precommit in &justification.commit.precommits {
+		// form a message which is signed in the Justification, it's a triplet of a Precommit,
+		// round number and set_id (taken from Substrate code)
+		let signed_message = Encode::encode(&(
+			&SignerMessage::PrecommitMessage(precommit.precommit.clone()),
+			&justification.round,
+			&validator_set.set_id,
+		));
+		if !<ed25519::Pair as Pair>::verify(&precommit.signature, signed_message, &precommit.id) {
+			return Err(FinalityError::InvalidSignature {
+				set_id: validator_set.set_id,
+				signer: precommit.id,
+			});
+		}
+
+		// signature is verified, so the conflicting precommits are the evidence of equivocation
+		if let Some(&first) = signed.get(&precommit.id) {
+			if first.precommit == precommit.precommit {
+				return Err(FinalityError::DuplicateSigner {
+					signer: precommit.id,
+				});
 			}
+			return Err(FinalityError::Equivocation(Box::new(Equivocation {
+				set_id: validator_set.set_id,
+				round: justification.round,
+				block_number: justification.commit.target_number,
+				signer: precommit.id,
+				first: first.clone(),
+				second: precommit.clone(),
+			})));
+		}
+		signed.insert(precommit.id, precommit);
 
-			let ancestry = confirm_ancestry(
-				&precommit.precommit.target_hash,
-				&justification.commit.target_hash,
-				&ancestry_map,
-			);
-			(is_ok && ancestry)
-				.then(|| precommit.clone().id)
-				.ok_or_else(|| {
-					eyre!(
-				"Not signed by this signature! Sig id: {:?}, set_id: {}, justification: {:?}",
-				&precommit.id,
-				validator_set.set_id,
-				justification
-			)
-				})
-		})
-		.collect::<Result<Vec<_>>>();
+		confirm_ancestry(
+			precommit,
+			&justification.commit,
+			&ancestry_map,
+			&mut visited,
+		)?;
+	}
+
+	if let Some(&hash) = ancestry_map.keys().find(|hash| !visited.contains(*hash)) {
+		return Err(FinalityError::UnusedAncestry { hash });
+	}
 
 	// match all the Signer addresses to the Current Validator Set
-	let num_matched_addresses = signer_addresses?
-		.iter()
+	let num_matched_addresses = signed
+		.keys()
 		.filter(|x| validator_set.validator_set.iter().any(|e| e.0.eq(&x.0)))
 		.count();
 
@@ -103,7 +333,11 @@ pub fn check_finality(
 
 	is_signed_by_supermajority(num_matched_addresses, validator_set.validator_set.len())
 		.then_some(())
-		.ok_or(eyre!("Not signed by supermajority of validator set!"))
+		.ok_or(FinalityError::NoSupermajority {
+			set_id: validator_set.set_id,
+			signatures: num_matched_addresses,
+			validators: validator_set.validator_set.len(),
+		})
 }
 
 fn is_signed_by_supermajority(num_signatures: usize, validator_set_size: usize) -> bool {
@@ -111,43 +345,353 @@ fn is_signed_by_supermajority(num_signatures: usize, validator_set_size: usize)
 	num_signatures >= supermajority
 }
 
+/// Walks the vote ancestry from the precommit target to the commit target,
+/// checking that block numbers decrease by one on each step. Walked headers are added to the visited set.
 fn confirm_ancestry(
-	child_hash: &H256,
-	root_hash: &H256,
-	ancestry_map: &HashMap<H256, H256>,
-) -> bool {
-	if child_hash == root_hash {
-		return true;
-	}
-
-	let mut curr_hash = child_hash;
+	signed: &SignedPrecommit,
+	commit: &Commit,
+	ancestry_map: &HashMap<H256, &Header>,
+	visited: &mut HashSet<H256>,
+) -> Result<(), FinalityError> {
+	let signer = signed.id;
+	let target_number = signed.precommit.target_number;
+	let invalid_target_number = FinalityError::InvalidTargetNumber {
+		signer,
+		target_number,
+	};
 
-	// We should be able to test it in at most ancestry_map.len() passes
-	for _ in 0..ancestry_map.len() {
-		if let Some(parent_hash) = ancestry_map.get(curr_hash) {
-			if parent_hash == root_hash {
-				return true;
-			}
-			curr_hash = parent_hash;
-		} else {
-			return false;
+	let (mut hash, mut number) = (signed.precommit.target_hash, target_number);
+	// Block numbers strictly decrease, so the walk ends in at most ancestry_map.len() steps
+	while hash != commit.target_hash {
+		let Some(header) = ancestry_map.get(&hash) else {
+			return Err(FinalityError::InvalidAncestry { signer });
+		};
+		if header.number != number {
+			return Err(invalid_target_number);
 		}
+		visited.insert(hash);
+		hash = header.parent_hash;
+		number = number
+			.checked_sub(1)
+			.ok_or(FinalityError::InvalidAncestry { signer })?;
 	}
 
-	false
+	if number != commit.target_number {
+		return Err(invalid_target_number);
+	}
+	Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+	use avail_subxt::{
+		api::runtime_types::avail_core::{
+			data_lookup::compact::CompactDataLookup,
+			header::extension::{v3::HeaderExtension, HeaderExtension::V3},
+			kate_commitment::v3::KateCommitment,
+		},
+		config::substrate::{Digest, DigestItem},
+		primitives::Header,
+	};
 	use codec::Encode;
 	use hex::FromHex;
 	use sp_core::{
+		blake2_256,
 		ed25519::{self, Public, Signature},
-		Pair,
+		Pair, H256,
 	};
 	use test_case::test_case;
 
-	use crate::types::{Precommit, SignerMessage};
+	use super::{
+		authority_set_changes, check_finality, AuthoritySet, AuthoritySetChange, FinalityError,
+		ValidatorSet,
+	};
+	use crate::types::{Commit, GrandpaJustification, Precommit, SignedPrecommit, SignerMessage};
+
+	fn header(number: u32, logs: Vec<DigestItem>) -> Header {
+		Header {
+			parent_hash: H256::zero(),
+			number,
+			state_root: H256::zero(),
+			extrinsics_root: H256::zero(),
+			digest: Digest { logs },
+			extension: V3(HeaderExtension {
+				commitment: KateCommitment {
+					rows: 1,
+					cols: 4,
+					data_root: H256::zero(),
+					commitment: vec![],
+				},
+				app_lookup: CompactDataLookup {
+					size: 1,
+					index: vec![],
+				},
+			}),
+		}
+	}
+
+	fn authority(seed: u8) -> Public {
+		ed25519::Pair::from_seed(&[seed; 32]).public()
+	}
+
+	// `FRNK` logs are encoded as GRANDPA `ConsensusLog` variants,
+	// where scheduled change has index 1 and forced change has index 2
+	fn scheduled_change(seed: u8, delay: u32) -> DigestItem {
+		let authorities = vec![(authority(seed).0, 1u64)];
+		DigestItem::Consensus(*b"FRNK", (1u8, authorities, delay).encode())
+	}
+
+	fn forced_change(seed: u8, median: u32, delay: u32) -> DigestItem {
+		let authorities = vec![(authority(seed).0, 1u64)];
+		DigestItem::Consensus(*b"FRNK", (2u8, median, authorities, delay).encode())
+	}
+
+	fn authority_set() -> AuthoritySet {
+		AuthoritySet::new(ValidatorSet {
+			set_id: 0,
+			validator_set: vec![authority(0)],
+		})
+	}
+
+	#[test]
+	fn authority_set_changes_from_logs() {
+		let logs = vec![
+			DigestItem::Consensus(*b"BABE", vec![1, 2, 3]),
+			scheduled_change(1, 5),
+			forced_change(2, 8, 2),
+		];
+		let expected = vec![
+			AuthoritySetChange {
+				validator_set: vec![authority(1)],
+				activation: 15,
+				forced: false,
+			},
+			AuthoritySetChange {
+				validator_set: vec![authority(2)],
+				activation: 12,
+				forced: true,
+			},
+		];
+		assert_eq!(authority_set_changes(&header(10, logs)), expected);
+	}
+
+	#[test]
+	fn standard_change_is_enacted_after_activation_block() {
+		let mut authority_set = authority_set();
+		let logs = vec![scheduled_change(1, 2)];
+		authority_set.schedule_changes(&header(10, logs)).unwrap();
+
+		assert!(authority_set.enact_changes(12).is_empty());
+		assert_eq!(authority_set.current.set_id, 0);
+
+		let enacted = authority_set.enact_changes(13);
+		assert_eq!(enacted.len(), 1);
+		assert_eq!(enacted[0].0.set_id, 0);
+		assert_eq!(enacted[0].1.activation, 12);
+		assert_eq!(authority_set.current.set_id, 1);
+		assert_eq!(authority_set.current.validator_set, vec![authority(1)]);
+		assert!(!authority_set.has_pending_changes());
+	}
+
+	#[test]
+	fn pending_changes_are_enacted_in_activation_order() {
+		let mut authority_set = authority_set();
+		let logs = vec![scheduled_change(1, 10)];
+		authority_set.schedule_changes(&header(10, logs)).unwrap();
+		let logs = vec![scheduled_change(2, 0)];
+		authority_set.schedule_changes(&header(11, logs)).unwrap();
+
+		authority_set.enact_changes(12);
+		assert_eq!(authority_set.current.set_id, 1);
+		assert_eq!(authority_set.current.validator_set, vec![authority(2)]);
+
+		authority_set.enact_changes(21);
+		assert_eq!(authority_set.current.set_id, 2);
+		assert_eq!(authority_set.current.validator_set, vec![authority(1)]);
+	}
+
+	#[test]
+	fn forced_change_discards_pending_changes() {
+		let mut authority_set = authority_set();
+		let logs = vec![scheduled_change(1, 10)];
+		authority_set.schedule_changes(&header(10, logs)).unwrap();
+		let logs = vec![forced_change(2, 9, 1)];
+		authority_set.schedule_changes(&header(11, logs)).unwrap();
+
+		let enacted = authority_set.enact_changes(13);
+		assert_eq!(enacted.len(), 1);
+		assert!(enacted[0].1.forced);
+		assert_eq!(authority_set.current.set_id, 1);
+		assert_eq!(authority_set.current.validator_set, vec![authority(2)]);
+		assert!(!authority_set.has_pending_changes());
+
+		assert!(authority_set.enact_changes(21).is_empty());
+		assert_eq!(authority_set.current.set_id, 1);
+	}
+
+	#[test]
+	fn overlapping_changes_are_rejected() {
+		let mut authority_set = authority_set();
+		let logs = vec![scheduled_change(1, 0), scheduled_change(2, 0)];
+		assert!(authority_set.schedule_changes(&header(10, logs)).is_err());
+
+		let logs = vec![scheduled_change(1, 5), forced_change(2, 9, 5)];
+		authority_set.schedule_changes(&header(10, logs)).unwrap();
+		let logs = vec![forced_change(3, 9, 5)];
+		assert!(authority_set.schedule_changes(&header(11, logs)).is_err());
+
+		// standard change is enacted before the forced change with the same activation block
+		authority_set.enact_changes(16);
+		assert_eq!(authority_set.current.set_id, 2);
+		assert_eq!(authority_set.current.validator_set, vec![authority(2)]);
+	}
+
+	fn signed_precommit(
+		pair: &ed25519::Pair,
+		precommit: &Precommit,
+		set_id: u64,
+	) -> SignedPrecommit {
+		let round = 1u64;
+		let signed_message = Encode::encode(&(
+			&SignerMessage::PrecommitMessage(precommit.clone()),
+			&round,
+			&set_id,
+		));
+		SignedPrecommit {
+			precommit: precommit.clone(),
+			signature: pair.sign(&signed_message),
+			id: pair.public(),
+		}
+	}
+
+	fn justification(pairs: &[ed25519::Pair], set_id: u64) -> GrandpaJustification {
+		let precommit = Precommit {
+			target_hash: [1u8; 32].into(),
+			target_number: 1,
+		};
+		let precommits = pairs
+			.iter()
+			.map(|pair| signed_precommit(pair, &precommit, set_id))
+			.collect();
+		GrandpaJustification {
+			round: 1,
+			commit: Commit {
+				target_hash: precommit.target_hash,
+				target_number: precommit.target_number,
+				precommits,
+			},
+			votes_ancestries: vec![],
+		}
+	}
+
+	#[test_case(0, 0 => Ok(()); "genesis set")]
+	#[test_case(3, 3 => Ok(()); "known set")]
+	#[test_case(3, 4 => matches Err(FinalityError::InvalidSignature { set_id: 4, .. }); "next set")]
+	#[test_case(3, 2 => matches Err(FinalityError::InvalidSignature { set_id: 2, .. }); "previous set")]
+	fn check_finality_with_set_id(signed_set_id: u64, set_id: u64) -> Result<(), FinalityError> {
+		let pairs = (1..=3u8)
+			.map(|seed| ed25519::Pair::from_seed(&[seed; 32]))
+			.collect::<Vec<_>>();
+		let validator_set = ValidatorSet {
+			set_id,
+			validator_set: pairs.iter().map(Pair::public).collect(),
+		};
+		check_finality(&validator_set, &justification(&pairs, signed_set_id))
+	}
+
+	#[test]
+	fn check_finality_without_supermajority() {
+		let pairs = (1..=3u8)
+			.map(|seed| ed25519::Pair::from_seed(&[seed; 32]))
+			.collect::<Vec<_>>();
+		let validator_set = ValidatorSet {
+			set_id: 1,
+			validator_set: pairs.iter().map(Pair::public).collect(),
+		};
+		let result = check_finality(&validator_set, &justification(&pairs[..2], 1));
+		let expected = FinalityError::NoSupermajority {
+			set_id: 1,
+			signatures: 2,
+			validators: 3,
+		};
+		assert_eq!(result, Err(expected));
+	}
+
+	fn pairs() -> Vec<ed25519::Pair> {
+		(1..=3u8)
+			.map(|seed| ed25519::Pair::from_seed(&[seed; 32]))
+			.collect()
+	}
+
+	fn validator_set(pairs: &[ed25519::Pair]) -> ValidatorSet {
+		ValidatorSet {
+			set_id: 1,
+			validator_set: pairs.iter().map(Pair::public).collect(),
+		}
+	}
+
+	#[test]
+	fn check_finality_with_duplicate_signer() {
+		let pairs = pairs();
+		let mut justification = justification(&pairs, 1);
+		let duplicate = justification.commit.precommits[0].clone();
+		justification.commit.precommits.push(duplicate);
+		let result = check_finality(&validator_set(&pairs), &justification);
+		let expected = FinalityError::DuplicateSigner {
+			signer: pairs[0].public(),
+		};
+		assert_eq!(result, Err(expected));
+	}
+
+	#[test]
+	fn check_finality_with_equivocation() {
+		let pairs = pairs();
+		let mut justification = justification(&pairs, 1);
+		let conflicting = Precommit {
+			target_hash: [2u8; 32].into(),
+			target_number: 2,
+		};
+		let conflicting = signed_precommit(&pairs[1], &conflicting, 1);
+		justification.commit.precommits.push(conflicting.clone());
+
+		let result = check_finality(&validator_set(&pairs), &justification);
+		let Err(FinalityError::Equivocation(equivocation)) = result else {
+			panic!("Equivocation is expected, got {result:?}");
+		};
+		assert_eq!(equivocation.signer, pairs[1].public());
+		assert_eq!((equivocation.set_id, equivocation.round), (1, 1));
+		assert_eq!(equivocation.first, justification.commit.precommits[1]);
+		assert_eq!(equivocation.second, conflicting);
+	}
+
+	#[test_case(2, 1 => Ok(()); "descendant target")]
+	#[test_case(2, 0 => matches Err(FinalityError::InvalidAncestry { .. }); "missing ancestry")]
+	#[test_case(3, 1 => matches Err(FinalityError::InvalidTargetNumber { target_number: 3, .. }); "target number mismatch")]
+	#[test_case(1, 1 => matches Err(FinalityError::InvalidTargetNumber { target_number: 1, .. }); "target number below commit")]
+	#[test_case(2, 2 => matches Err(FinalityError::UnusedAncestry { .. }); "unused ancestry")]
+	fn check_finality_with_ancestry(
+		target_number: u32,
+		ancestry_len: usize,
+	) -> Result<(), FinalityError> {
+		let pairs = pairs();
+		// child of the commit target, and unrelated header
+		let mut child = header(2, vec![]);
+		child.parent_hash = [1u8; 32].into();
+		let ancestry = vec![child.clone(), header(5, vec![])];
+
+		let precommit = Precommit {
+			target_hash: child.using_encoded(blake2_256).into(),
+			target_number,
+		};
+		let mut justification = justification(&pairs, 1);
+		justification.commit.precommits = pairs
+			.iter()
+			.map(|pair| signed_precommit(pair, &precommit, 1))
+			.collect();
+		justification.votes_ancestries = ancestry.into_iter().take(ancestry_len).collect();
+		check_finality(&validator_set(&pairs), &justification)
+	}
+
 	#[test_case(1, 1 => true)]
 	#[test_case(1, 2 => false)]
 	#[test_case(2, 2 => true)]
@@ -1,3 +1,4 @@
+pub mod alerts;
 pub mod api;
 pub mod app_client;
 pub mod consts;
@@ -10,6 +11,7 @@ pub mod light_client;
 pub mod maintenance;
 pub mod network;
 pub mod proof;
+pub mod retention;
 pub mod shutdown;
 pub mod sync_client;
 pub mod sync_finality;
@@ -8,38 +8,55 @@
 //! * Generate random cells for random data sampling (8 cells currently)
 //! * Retrieve cell proofs from a) DHT and/or b) via RPC call from the node, in that order
 //! * Verify proof using the received cells
+//! * Queue the block for re-sampling of unfetched cells, in case not all cells are fetched
 //! * Calculate block confidence and store it in RocksDB
 //! * Insert cells to to DHT for remote fetch
-//! * Notify the consumer (app client) a new block has been verified
+//! * Notify the consumer (app client) a new block has been verified, and again once confidence is achieved on re-sampling
+//! * Hand blocks missed due to the channel lag over to the backfill
 //!
 //! # Notes
 //!
 //! In case delay is configured, block processing is delayed for configured time.
 //! In case RPC is disabled, RPC calls will be skipped.
+//! Up to the configured number of blocks are sampled concurrently, while blocks are stored and verified blocks are notified in block order.
+//! Unfetched cells are re-sampled with exponential back-off, and block is marked as failed once retries are exhausted.
 
 use avail_subxt::{primitives::Header, utils::H256};
 use codec::Encode;
 use color_eyre::{eyre::WrapErr, Result};
-use kate_recovery::{commitments, matrix::Dimensions};
+use futures::stream::{FuturesOrdered, FuturesUnordered, StreamExt};
+use kate_recovery::{
+	commitments, config,
+	matrix::{Dimensions, Position},
+};
 use sp_core::blake2_256;
 use std::{
 	sync::{Arc, Mutex},
 	time::Instant,
 };
-use tracing::{error, info};
+use tokio::sync::{
+	broadcast::{self, error::RecvError},
+	mpsc,
+};
+use tracing::{error, info, warn};
 
 use crate::{
-	data::{Database, Key},
+	alerts::{Alert, AlertSender},
+	data::{Database, Key, WriteBatch},
 	network::{
 		self,
 		rpc::{self, Event},
+		SamplingReport,
 	},
 	shutdown::Controller,
+	sync_client,
 	telemetry::{MetricCounter, MetricValue, Metrics},
-	types::{self, ClientChannels, LightClientConfig, OptionBlockRange, State},
-	utils::{calculate_confidence, extract_kate},
+	types::{self, ClientChannels, LightClientConfig, State},
+	utils::{block_gap, extract_kate},
 };
 
+/// Samples and stores the block, see [`sample_block`] and [`store_block`].
+#[allow(clippy::too_many_arguments)]
 pub async fn process_block(
 	db: impl Database,
 	network_client: &impl network::Client,
@@ -48,14 +65,55 @@ pub async fn process_block(
 	header: Header,
 	received_at: Instant,
 	state: Arc<Mutex<State>>,
+	retries: &mut Vec<SamplingRetry>,
+	alert_sender: &AlertSender,
 ) -> Result<Option<f64>> {
+	let Some(sampled) = sample_block(network_client, metrics, cfg, &header, received_at).await?
+	else {
+		return Ok(None);
+	};
+	store_block(
+		&db,
+		metrics,
+		cfg,
+		header,
+		sampled,
+		&state,
+		retries,
+		alert_sender,
+	)
+	.await
+}
+
+/// Block with fetched and verified cells, waiting to be stored in block order
+pub struct SampledBlock {
+	header_hash: H256,
+	dimensions: Dimensions,
+	commitments: Vec<[u8; config::COMMITMENT_SIZE]>,
+	/// Number of requested cells
+	requested: usize,
+	fetched: usize,
+	unfetched: Vec<Position>,
+	report: SamplingReport,
+}
+
+/// Fetches and verifies random cells of the block.
+/// Blocks are sampled concurrently, so sampling doesn't change the state nor the database.
+/// Returns `None` if block is skipped.
+pub async fn sample_block(
+	network_client: &impl network::Client,
+	metrics: &Arc<impl Metrics>,
+	cfg: &LightClientConfig,
+	header: &Header,
+	received_at: Instant,
+) -> Result<Option<SampledBlock>> {
 	metrics.count(MetricCounter::SessionBlock).await;
 	metrics
 		.record(MetricValue::TotalBlockNumber(header.number))
 		.await?;
 
 	let block_number = header.number;
-	let header_hash: H256 = Encode::using_encoded(&header, blake2_256).into();
+	let header_hash: H256 = Encode::using_encoded(header, blake2_256).into();
 
 	info!(
 		{ block_number, block_delay = received_at.elapsed().as_secs()},
@@ -77,8 +135,9 @@ pub async fn process_block(
 	}
 
 	let commitments = commitments::from_slice(&commitment)?;
-	let cell_count = rpc::cell_count_for_confidence(cfg.confidence);
-	let positions = rpc::generate_random_cells(dimensions, cell_count);
+	let cell_count = cfg.confidence_model.cell_count(cfg.confidence, dimensions);
+	let seed = cfg.sampling_strategy.seed(header_hash);
+	let positions = rpc::generate_random_cells(dimensions, cell_count, seed);
 	info!(
 		block_number,
 		"cells_requested" = positions.len(),
@@ -124,18 +183,143 @@ pub async fn process_block(
 			.await?;
 	}
 
-	if positions.len() > fetched.len() {
+	let report = SamplingReport::new(block_number, seed, &positions, &fetch_stats);
+
+	Ok(Some(SampledBlock {
+		header_hash,
+		dimensions,
+		commitments,
+		requested: positions.len(),
+		fetched: fetched.len(),
+		unfetched,
+		report,
+	}))
+}
+
+/// Stores confidence of the sampled block, or queues the block for re-sampling
+/// in case not all cells are fetched. Blocks are stored in block order.
+#[allow(clippy::too_many_arguments)]
+pub async fn store_block(
+	db: &impl Database,
+	metrics: &Arc<impl Metrics>,
+	cfg: &LightClientConfig,
+	header: Header,
+	sampled: SampledBlock,
+	state: &Arc<Mutex<State>>,
+	retries: &mut Vec<SamplingRetry>,
+	alert_sender: &AlertSender,
+) -> Result<Option<f64>> {
+	let block_number = header.number;
+	let SampledBlock {
+		header_hash,
+		dimensions,
+		commitments,
+		requested,
+		fetched,
+		unfetched,
+		report,
+	} = sampled;
+
+	if requested > fetched {
 		error!(block_number, "Failed to fetch {} cells", unfetched.len());
+		db.put(Key::SamplingReport(block_number), report.clone())
+			.wrap_err("Light Client failed to store Sampling Report")?;
+		if cfg.sampling_retries == 0 {
+			set_confidence_failed(state, alert_sender, block_number);
+			return Ok(None);
+		}
+		state
+			.lock()
+			.unwrap()
+			.confidence_retrying
+			.insert(block_number);
+		retries.push(SamplingRetry {
+			header,
+			header_hash,
+			dimensions,
+			commitments,
+			fetched,
+			unfetched,
+			report,
+			attempts: 0,
+			retry_at: Instant::now() + cfg.sampling_retry_delay,
+		});
 		return Ok(None);
 	}
 
+	let confidence = cfg.confidence_model.confidence(fetched as u32, dimensions);
+	store_confidence(db, metrics, header, fetched, confidence, report, state).await?;
+	Ok(Some(confidence))
+}
+
+/// Block with unfetched cells, waiting for the cells to be re-sampled
+pub struct SamplingRetry {
+	header: Header,
+	header_hash: H256,
+	dimensions: Dimensions,
+	commitments: Vec<[u8; config::COMMITMENT_SIZE]>,
+	/// Number of fetched and verified cells
+	fetched: usize,
+	unfetched: Vec<Position>,
+	report: SamplingReport,
+	attempts: u32,
+	retry_at: Instant,
+}
+
+fn set_confidence_failed(state: &Arc<Mutex<State>>, alert_sender: &AlertSender, block_number: u32) {
+	{
+		let mut state = state.lock().unwrap();
+		state.confidence_retrying.remove(&block_number);
+		state.confidence_failed.insert(block_number);
+	}
+	let _ = alert_sender.send(Alert::ConfidenceFailed { block_number });
+}
+
+/// Stores header, confidence factor and sampling report of the sampled block,
+/// and marks block confidence as achieved.
+async fn store_confidence(
+	db: &impl Database,
+	metrics: &Arc<impl Metrics>,
+	header: Header,
+	fetched: usize,
+	confidence: f64,
+	report: SamplingReport,
+	state: &Arc<Mutex<State>>,
+) -> Result<()> {
+	let block_number = header.number;
+	let mut batch = db.batch();
+
 	// write confidence factor into on-disk database
-	db.put(Key::VerifiedCellCount(block_number), fetched.len() as u32)
+	batch
+		.put(Key::VerifiedCellCount(block_number), fetched as u32)
 		.wrap_err("Light Client failed to store Confidence Factor")?;
 
-	state.lock().unwrap().confidence_achieved.set(block_number);
+	// push latest mined block's header into column family specified
+	// for keeping block headers, to be used
+	// later for verifying DHT stored data
+	//
+	// @note this same data store is also written to in
+	// another competing thread, which syncs all block headers
+	// in range [0, LATEST], where LATEST = latest block number
+	// when this process started
+	batch
+		.put(Key::BlockHeader(block_number), header)
+		.wrap_err("Light Client failed to store Block Header")?;
+
+	batch
+		.put(Key::SamplingReport(block_number), report)
+		.wrap_err("Light Client failed to store Sampling Report")?;
+
+	// confidence factor, header and sampling report are written atomically
+	db.write(batch)
+		.wrap_err("Light Client failed to store processed block")?;
+
+	{
+		let mut state = state.lock().unwrap();
+		state.confidence_retrying.remove(&block_number);
+		state.set_confidence_achieved(block_number);
+	}
 
-	let confidence = calculate_confidence(fetched.len() as u32);
 	info!(
 		block_number,
 		"confidence" = confidence,
@@ -146,22 +330,119 @@ pub async fn process_block(
 		.record(MetricValue::BlockConfidence(confidence))
 		.await?;
 
-	// push latest mined block's header into column family specified
-	// for keeping block headers, to be used
-	// later for verifying DHT stored data
-	//
-	// @note this same data store is also written to in
-	// another competing thread, which syncs all block headers
-	// in range [0, LATEST], where LATEST = latest block number
-	// when this process started
-	db.put(Key::BlockHeader(block_number), header)
-		.wrap_err("Light Client failed to store Block Header")?;
+	Ok(())
+}
 
-	Ok(Some(confidence))
+/// Outcome of the block re-sampling
+pub enum RetryOutcome {
+	/// Some cells are still unfetched, block is queued for the next retry
+	Retry(SamplingRetry),
+	/// Confidence is achieved, header is returned back so verified block can be notified
+	Verified(Header, f64),
+	/// Retries are exhausted and block is marked as failed
+	Failed,
+}
+
+/// Re-samples unfetched cells of the block, fetching from DHT first and then from RPC.
+/// Returns block back if some cells are still unfetched and retries are not exhausted.
+pub async fn retry_sampling(
+	db: &impl Database,
+	network_client: &impl network::Client,
+	metrics: &Arc<impl Metrics>,
+	cfg: &LightClientConfig,
+	mut retry: SamplingRetry,
+	state: &Arc<Mutex<State>>,
+	alert_sender: &AlertSender,
+) -> Result<RetryOutcome> {
+	let block_number = retry.header.number;
+	retry.attempts += 1;
+
+	match network_client
+		.fetch_verified(
+			block_number,
+			retry.header_hash,
+			retry.dimensions,
+			&retry.commitments,
+			&retry.unfetched,
+		)
+		.await
+	{
+		Ok((fetched, unfetched, stats)) => {
+			retry.fetched += fetched.len();
+			retry.unfetched = unfetched;
+			retry.report.update(&stats);
+		},
+		Err(error) => warn!(block_number, "Failed to re-sample cells: {error:#}"),
+	};
+	retry.report.retries = retry.attempts;
+
+	info!(
+		block_number,
+		attempt = retry.attempts,
+		cells_unfetched = retry.unfetched.len(),
+		"Cells re-sampled"
+	);
+
+	if retry.unfetched.is_empty() {
+		let (header, fetched, report) = (retry.header, retry.fetched, retry.report);
+		let confidence = cfg
+			.confidence_model
+			.confidence(fetched as u32, retry.dimensions);
+		store_confidence(
+			db,
+			metrics,
+			header.clone(),
+			fetched,
+			confidence,
+			report,
+			state,
+		)
+		.await?;
+		return Ok(RetryOutcome::Verified(header, confidence));
+	}
+
+	if retry.attempts >= cfg.sampling_retries {
+		error!(
+			block_number,
+			"Failed to fetch {} cells after {} retries",
+			retry.unfetched.len(),
+			retry.attempts
+		);
+		db.put(Key::SamplingReport(block_number), retry.report)
+			.wrap_err("Light Client failed to store Sampling Report")?;
+		set_confidence_failed(state, alert_sender, block_number);
+		return Ok(RetryOutcome::Failed);
+	}
+
+	let backoff = 2u32.saturating_pow(retry.attempts);
+	retry.retry_at = Instant::now() + cfg.sampling_retry_delay.saturating_mul(backoff);
+	Ok(RetryOutcome::Retry(retry))
+}
+
+/// Notifies the clients that the block has been verified.
+fn notify_block_verified(
+	block_sender: &broadcast::Sender<types::BlockVerified>,
+	header: Header,
+	confidence: Option<f64>,
+) {
+	let Ok(client_msg) = types::BlockVerified::try_from((header, confidence)) else {
+		error!("Cannot create message from header");
+		return;
+	};
+
+	// notify dht-based application client
+	// that newly mined block has been received
+	if let Err(error) = block_sender.send(client_msg) {
+		error!("Cannot send block verified message: {error}");
+	}
 }
 
 /// Runs light client.
 ///
+/// Blocks are sampled, stored and re-sampled concurrently,
+/// so storing and re-sampling of the blocks doesn't stall the blocks in flight.
+/// Sampling waits for the storing once the sampled blocks queue is full, so memory stays bounded.
+///
 /// # Arguments
 ///
 /// * `light_client` - Light client implementation
@@ -176,66 +457,165 @@ pub async fn run(
 	cfg: LightClientConfig,
 	metrics: Arc<impl Metrics>,
 	state: Arc<Mutex<State>>,
-	mut channels: ClientChannels,
+	channels: ClientChannels,
 	shutdown: Controller<String>,
 ) {
 	info!("Starting light client...");
 
-	loop {
-		let (header, received_at) = match channels.rpc_event_receiver.recv().await {
-			Ok(event) => match event {
-				Event::HeaderUpdate {
-					header,
-					received_at,
-				} => (header, received_at),
-			},
-			Err(error) => {
-				error!("Cannot receive message: {error}");
-				return;
-			},
-		};
+	let ClientChannels {
+		block_sender,
+		mut rpc_event_receiver,
+		backfill_sender,
+		alert_sender,
+	} = channels;
+	// Sampled blocks waiting to be stored are bounded too, so stalled storing stops receiving of the new headers
+	let (sampled_sender, mut sampled_receiver) = mpsc::channel(cfg.max_blocks_in_flight.get());
+	let (retry_sender, mut retry_receiver) = mpsc::unbounded_channel();
+
+	let sample = |header: Header, received_at: Instant| {
+		let (network_client, metrics, cfg) = (&network_client, &metrics, &cfg);
+		async move {
+			if let Some(seconds) = cfg.block_processing_delay.sleep_duration(received_at) {
+				if let Err(error) = metrics
+					.record(MetricValue::BlockProcessingDelay(seconds.as_secs_f64()))
+					.await
+				{
+					error!("Cannot record block processing delay: {}", error);
+				}
+				info!("Sleeping for {seconds:?} seconds");
+				tokio::time::sleep(seconds).await;
+			}
+			let sampled = sample_block(network_client, metrics, cfg, &header, received_at).await;
+			(header, sampled)
+		}
+	};
 
-		if let Some(seconds) = cfg.block_processing_delay.sleep_duration(received_at) {
-			if let Err(error) = metrics
-				.record(MetricValue::BlockProcessingDelay(seconds.as_secs_f64()))
-				.await
-			{
-				error!("Cannot record block processing delay: {}", error);
+	let resample = |retry: SamplingRetry| {
+		let (db, network_client, metrics, cfg) = (&db, &network_client, &metrics, &cfg);
+		let (state, alert_sender) = (&state, &alert_sender);
+		async move {
+			tokio::time::sleep_until(tokio::time::Instant::from_std(retry.retry_at)).await;
+			retry_sampling(db, network_client, metrics, cfg, retry, state, alert_sender).await
+		}
+	};
+
+	let sampling = async {
+		let mut last_block = None;
+		// Blocks are sampled concurrently, and completed in the order they are received
+		let mut in_flight = FuturesOrdered::new();
+
+		loop {
+			let can_sample = in_flight.len() < cfg.max_blocks_in_flight.get();
+
+			let event = tokio::select! {
+				event = rpc_event_receiver.recv(), if can_sample => event,
+				Some(sampled) = in_flight.next(), if !in_flight.is_empty() => {
+					// Sampled blocks are stored in the order they are received
+					let _ = sampled_sender.send(sampled).await;
+					continue;
+				},
+			};
+
+			let (header, received_at) = match event {
+				Ok(event) => match event {
+					Event::HeaderUpdate {
+						header,
+						received_at,
+					} => (header, received_at),
+				},
+				// Missed blocks are detected on the next received header
+				Err(RecvError::Lagged(count)) => {
+					warn!("Light client lagged behind, {count} messages skipped");
+					continue;
+				},
+				Err(error) => {
+					error!("Cannot receive message: {error}");
+					return;
+				},
+			};
+
+			if let Some(gap) = block_gap(last_block, header.number) {
+				sync_client::backfill(&backfill_sender, &state, gap);
 			}
-			info!("Sleeping for {seconds:?} seconds");
-			tokio::time::sleep(seconds).await;
+			last_block = last_block.max(Some(header.number));
+
+			in_flight.push_back(sample(header, received_at));
 		}
+	};
 
-		let process_block_result = process_block(
-			db.clone(),
-			&network_client,
-			&metrics,
-			&cfg,
-			header.clone(),
-			received_at,
-			state.clone(),
-		)
-		.await;
-		let confidence = match process_block_result {
-			Ok(confidence) => confidence,
-			Err(error) => {
-				error!("Cannot process block: {error}");
-				let _ = shutdown.trigger_shutdown(format!("Cannot process block: {error:#}"));
-				return;
-			},
-		};
+	let storing = async {
+		let mut retries = vec![];
 
-		let Ok(client_msg) = types::BlockVerified::try_from((header, confidence)) else {
-			error!("Cannot create message from header");
-			continue;
-		};
+		while let Some((header, sampled)) = sampled_receiver.recv().await {
+			let result = match sampled {
+				Ok(Some(sampled)) => {
+					let header = header.clone();
+					store_block(
+						&db,
+						&metrics,
+						&cfg,
+						header,
+						sampled,
+						&state,
+						&mut retries,
+						&alert_sender,
+					)
+					.await
+				},
+				Ok(None) => Ok(None),
+				Err(error) => Err(error),
+			};
+			let confidence = match result {
+				Ok(confidence) => confidence,
+				Err(error) => {
+					error!("Cannot process block: {error}");
+					let _ = shutdown.trigger_shutdown(format!("Cannot process block: {error:#}"));
+					return;
+				},
+			};
+
+			for retry in retries.drain(..) {
+				let _ = retry_sender.send(retry);
+			}
+			notify_block_verified(&block_sender, header, confidence);
+		}
+	};
+
+	let retrying = async {
+		// Queued blocks are re-sampled concurrently, each once its back-off expires
+		let mut retrying = FuturesUnordered::new();
+
+		loop {
+			let result = tokio::select! {
+				Some(retry) = retry_receiver.recv() => {
+					retrying.push(resample(retry));
+					continue;
+				},
+				Some(result) = retrying.next(), if !retrying.is_empty() => result,
+				else => return,
+			};
 
-		// notify dht-based application client
-		// that newly mined block has been received
-		if let Err(error) = channels.block_sender.send(client_msg) {
-			error!("Cannot send block verified message: {error}");
-			continue;
+			match result {
+				Ok(RetryOutcome::Retry(retry)) => retrying.push(resample(retry)),
+				// Block is notified again, now with the achieved confidence
+				Ok(RetryOutcome::Verified(header, confidence)) => {
+					notify_block_verified(&block_sender, header, Some(confidence))
+				},
+				Ok(RetryOutcome::Failed) => {},
+				Err(error) => {
+					error!("Cannot retry block sampling: {error}");
+					let _ = shutdown
+						.trigger_shutdown(format!("Cannot retry block sampling: {error:#}"));
+					return;
+				},
+			}
 		}
+	};
+
+	tokio::select! {
+		_ = sampling => {},
+		_ = storing => {},
+		_ = retrying => {},
 	}
 }
 
@@ -248,7 +628,7 @@ mod tests {
 		data::mem_db,
 		network::rpc::{cell_count_for_confidence, CELL_COUNT_99_99},
 		telemetry,
-		types::RuntimeConfig,
+		types::{OptionBlockRange, RuntimeConfig, SamplingStrategy},
 	};
 	use avail_subxt::{
 		api::runtime_types::avail_core::{
@@ -261,6 +641,7 @@ mod tests {
 	use hex_literal::hex;
 	use kate_recovery::{data::Cell, matrix::Position};
 	use test_case::test_case;
+	use tokio::sync::mpsc;
 
 	#[test_case(99.9 => 10)]
 	#[test_case(99.99 => CELL_COUNT_99_99)]
@@ -274,20 +655,8 @@ mod tests {
 		cell_count_for_confidence(confidence)
 	}
 
-	#[tokio::test]
-	async fn test_process_block_with_rpc() {
-		let mut mock_network_client = network::MockClient::new();
-		let db = mem_db::MemoryDB::default();
-		let cfg = LightClientConfig::from(&RuntimeConfig::default());
-		let cells_fetched: Vec<Cell> = vec![];
-		let cells_unfetched = [
-			Position { row: 1, col: 3 },
-			Position { row: 0, col: 0 },
-			Position { row: 1, col: 2 },
-			Position { row: 0, col: 1 },
-		]
-		.to_vec();
-		let header = Header {
+	fn header() -> Header {
+		Header {
 			parent_hash: hex!("c454470d840bc2583fcf881be4fd8a0f6daeac3a20d83b9fd4865737e56c9739")
 				.into(),
 			number: 57,
@@ -321,7 +690,39 @@ mod tests {
 					index: vec![],
 				},
 			}),
-		};
+		}
+	}
+
+	#[test]
+	fn test_seeded_sampling_is_reproducible() {
+		let strategy = SamplingStrategy::Seeded("secret".to_string());
+		let block_hash = H256::repeat_byte(1);
+		let seed = strategy.seed(block_hash);
+		assert_eq!(seed, strategy.seed(block_hash));
+		assert_ne!(seed, strategy.seed(H256::repeat_byte(2)));
+		let other_secret = SamplingStrategy::Seeded("other".to_string());
+		assert_ne!(seed, other_secret.seed(block_hash));
+
+		let dimensions = Dimensions::new(16, 64).unwrap();
+		let positions = rpc::generate_random_cells(dimensions, 10, seed);
+		assert_eq!(positions.len(), 10);
+		assert_eq!(positions, rpc::generate_random_cells(dimensions, 10, seed));
+	}
+
+	#[tokio::test]
+	async fn test_process_block_with_rpc() {
+		let mut mock_network_client = network::MockClient::new();
+		let db = mem_db::MemoryDB::default();
+		let cfg = LightClientConfig::from(&RuntimeConfig::default());
+		let cells_fetched: Vec<Cell> = vec![];
+		let cells_unfetched = [
+			Position { row: 1, col: 3 },
+			Position { row: 0, col: 0 },
+			Position { row: 1, col: 2 },
+			Position { row: 0, col: 1 },
+		]
+		.to_vec();
+		let header = header();
 		let state = Arc::new(Mutex::new(State::default()));
 		let recv = Instant::now();
 		mock_network_client
@@ -342,16 +743,313 @@ mod tests {
 		mock_metrics.expect_count().returning(|_| ());
 		mock_metrics.expect_record().returning(|_| Ok(()));
 		mock_metrics.expect_set_multiaddress().returning(|_| ());
-		process_block(
+		let mut retries = vec![];
+		let (alert_sender, _alert_receiver) = mpsc::unbounded_channel();
+		let confidence = process_block(
 			db,
 			&mock_network_client,
 			&Arc::new(mock_metrics),
 			&cfg,
 			header,
 			recv,
-			state,
+			state.clone(),
+			&mut retries,
+			&alert_sender,
+		)
+		.await
+		.unwrap();
+		assert_eq!(confidence, None);
+		assert_eq!(retries.len(), 1);
+		assert_eq!(retries[0].unfetched.len(), 4);
+		assert!(state.lock().unwrap().confidence_retrying.contains(&57));
+	}
+
+	fn sampling_retry(unfetched: Vec<Position>) -> SamplingRetry {
+		let header = header();
+		let (rows, cols, _, commitment) = extract_kate(&header.extension);
+		SamplingRetry {
+			header_hash: Encode::using_encoded(&header, blake2_256).into(),
+			dimensions: Dimensions::new(rows, cols).unwrap(),
+			commitments: commitments::from_slice(&commitment).unwrap(),
+			header,
+			fetched: 2,
+			report: SamplingReport::new(
+				57,
+				[0u8; 32],
+				&unfetched,
+				&network::FetchStats::new(unfetched.len(), 0, Duration::from_secs(0), None),
+			),
+			unfetched,
+			attempts: 0,
+			retry_at: Instant::now(),
+		}
+	}
+
+	fn mock_fetch(fetched: usize, unfetched: Vec<Position>) -> network::MockClient {
+		let mut mock_network_client = network::MockClient::new();
+		mock_network_client
+			.expect_fetch_verified()
+			.returning(move |_, _, _, _, positions| {
+				let fetched = positions[..fetched]
+					.iter()
+					.map(|&position| Cell {
+						position,
+						content: [0u8; 80],
+					})
+					.collect::<Vec<_>>();
+				let unfetched = unfetched.clone();
+				let mut stats = network::FetchStats::new(
+					positions.len(),
+					fetched.len(),
+					Duration::from_secs(0),
+					None,
+				);
+				stats.cells = fetched
+					.iter()
+					.map(|cell| network::FetchedCell {
+						position: cell.position,
+						source: network::CellSource::Rpc,
+						verified: true,
+					})
+					.collect();
+				Box::pin(async move { Ok((fetched, unfetched, stats)) })
+			});
+		mock_network_client
+	}
+
+	#[tokio::test]
+	async fn test_sampled_blocks_are_stored_in_order() {
+		let db = mem_db::MemoryDB::default();
+		let cfg = LightClientConfig::from(&RuntimeConfig::default());
+		let mock_network_client = mock_fetch(8, vec![]);
+		let mut mock_metrics = telemetry::MockMetrics::new();
+		mock_metrics.expect_count().returning(|_| ());
+		mock_metrics.expect_record().returning(|_| Ok(()));
+		let metrics = Arc::new(mock_metrics);
+		let state = Arc::new(Mutex::new(State::default()));
+
+		let mut headers = vec![header(), header()];
+		headers[1].number = 58;
+		let recv = Instant::now();
+		let (first, second) = futures::join!(
+			sample_block(&mock_network_client, &metrics, &cfg, &headers[1], recv),
+			sample_block(&mock_network_client, &metrics, &cfg, &headers[0], recv),
+		);
+		assert!(db.get::<u32>(Key::VerifiedCellCount(58)).unwrap().is_none());
+		assert!(state.lock().unwrap().confidence_achieved.is_none());
+
+		let mut retries = vec![];
+		let (alert_sender, _alert_receiver) = mpsc::unbounded_channel();
+		for (header, sampled) in headers.into_iter().zip([second, first]) {
+			let sampled = sampled.unwrap().expect("Block should be sampled");
+			let confidence = store_block(
+				&db,
+				&metrics,
+				&cfg,
+				header,
+				sampled,
+				&state,
+				&mut retries,
+				&alert_sender,
+			)
+			.await
+			.unwrap();
+			assert!(confidence.is_some());
+		}
+		assert!(retries.is_empty());
+		assert_eq!(db.get::<u32>(Key::VerifiedCellCount(58)).unwrap(), Some(8));
+		let state = state.lock().unwrap();
+		assert_eq!(state.confidence_achieved.first(), Some(57));
+		assert_eq!(state.confidence_achieved.last(), Some(58));
+	}
+
+	#[tokio::test]
+	async fn test_run_stores_blocks_in_order() {
+		let db = mem_db::MemoryDB::default();
+		let cfg = LightClientConfig::from(&RuntimeConfig::default());
+		let sampled = Arc::new(Mutex::new(vec![]));
+		let mut mock_network_client = network::MockClient::new();
+		let sampled_blocks = sampled.clone();
+		mock_network_client.expect_fetch_verified().returning(
+			move |block_number, _, _, _, positions| {
+				let fetched = positions
+					.iter()
+					.map(|&position| Cell {
+						position,
+						content: [0u8; 80],
+					})
+					.collect::<Vec<_>>();
+				let stats =
+					network::FetchStats::new(positions.len(), fetched.len(), Duration::ZERO, None);
+				let sampled = sampled_blocks.clone();
+				Box::pin(async move {
+					// First block is sampled after the second one
+					if block_number == 57 {
+						tokio::time::sleep(Duration::from_millis(200)).await;
+					}
+					sampled.lock().unwrap().push(block_number);
+					Ok((fetched, vec![], stats))
+				})
+			},
+		);
+		let mut mock_metrics = telemetry::MockMetrics::new();
+		mock_metrics.expect_count().returning(|_| ());
+		mock_metrics.expect_record().returning(|_| Ok(()));
+		let state = Arc::new(Mutex::new(State::default()));
+
+		let (block_sender, mut block_receiver) = broadcast::channel(10);
+		let (rpc_event_sender, rpc_event_receiver) = broadcast::channel(10);
+		let (backfill_sender, _backfill_receiver) = mpsc::unbounded_channel();
+		let (alert_sender, _alert_receiver) = mpsc::unbounded_channel();
+		let channels = ClientChannels {
+			block_sender,
+			rpc_event_receiver,
+			backfill_sender,
+			alert_sender,
+		};
+
+		let mut headers = vec![header(), header()];
+		headers[1].number = 58;
+		for header in headers {
+			let received_at = Instant::now();
+			let event = Event::HeaderUpdate {
+				header,
+				received_at,
+			};
+			rpc_event_sender.send(event).unwrap();
+		}
+
+		let light_client = run(
+			db.clone(),
+			mock_network_client,
+			cfg,
+			Arc::new(mock_metrics),
+			state.clone(),
+			channels,
+			Controller::new(),
+		);
+		let verified = async {
+			let first = block_receiver.recv().await.unwrap();
+			let second = block_receiver.recv().await.unwrap();
+			(first, second)
+		};
+		let (first, second) = tokio::select! {
+			_ = light_client => panic!("Light client should not stop"),
+			verified = verified => verified,
+		};
+
+		assert_eq!(*sampled.lock().unwrap(), vec![58, 57]);
+		assert_eq!((first.block_num, second.block_num), (57, 58));
+		assert!(first.confidence.is_some() && second.confidence.is_some());
+		assert_eq!(db.get::<u32>(Key::VerifiedCellCount(57)).unwrap(), Some(8));
+		assert_eq!(db.get::<u32>(Key::VerifiedCellCount(58)).unwrap(), Some(8));
+		let state = state.lock().unwrap();
+		assert_eq!(state.confidence_achieved.first(), Some(57));
+		assert_eq!(state.confidence_achieved.last(), Some(58));
+	}
+
+	#[tokio::test]
+	async fn test_retry_sampling_achieves_confidence() {
+		let db = mem_db::MemoryDB::default();
+		let cfg = LightClientConfig::from(&RuntimeConfig::default());
+		let unfetched = vec![Position { row: 0, col: 0 }, Position { row: 1, col: 2 }];
+		let mock_network_client = mock_fetch(2, vec![]);
+		let mut mock_metrics = telemetry::MockMetrics::new();
+		mock_metrics.expect_record().returning(|_| Ok(()));
+
+		let mut state = State::default();
+		state.confidence_achieved.set(60);
+		state.confidence_retrying.insert(57);
+		let state = Arc::new(Mutex::new(state));
+
+		let (alert_sender, _alert_receiver) = mpsc::unbounded_channel();
+		let retry = retry_sampling(
+			&db,
+			&mock_network_client,
+			&Arc::new(mock_metrics),
+			&cfg,
+			sampling_retry(unfetched),
+			&state,
+			&alert_sender,
+		)
+		.await
+		.unwrap();
+
+		let RetryOutcome::Verified(header, confidence) = retry else {
+			panic!("Block confidence should be achieved");
+		};
+		assert_eq!(header.number, 57);
+		assert!(confidence > 0.0);
+		let count = db.get::<u32>(Key::VerifiedCellCount(57)).unwrap();
+		assert_eq!(count, Some(4));
+		let report = db.get::<SamplingReport>(Key::SamplingReport(57)).unwrap();
+		let report = report.expect("Sampling report should be stored");
+		assert_eq!(report.retries, 1);
+		assert!(report
+			.cells
+			.iter()
+			.all(|cell| cell.verified && cell.source == Some(network::CellSource::Rpc)));
+		let state = state.lock().unwrap();
+		assert!(state.confidence_retrying.is_empty());
+		assert_eq!(state.confidence_achieved.first(), Some(57));
+		assert_eq!(state.confidence_achieved.last(), Some(60));
+	}
+
+	#[tokio::test]
+	async fn test_retry_sampling_fails_confidence() {
+		let db = mem_db::MemoryDB::default();
+		let cfg = LightClientConfig {
+			sampling_retries: 2,
+			..LightClientConfig::from(&RuntimeConfig::default())
+		};
+		let unfetched = vec![Position { row: 0, col: 0 }];
+		let mock_network_client = mock_fetch(0, unfetched.clone());
+		let metrics = Arc::new(telemetry::MockMetrics::new());
+		let state = Arc::new(Mutex::new(State::default()));
+		state.lock().unwrap().confidence_retrying.insert(57);
+
+		let (alert_sender, mut alert_receiver) = mpsc::unbounded_channel();
+		let retry = sampling_retry(unfetched);
+		let retry = retry_sampling(
+			&db,
+			&mock_network_client,
+			&metrics,
+			&cfg,
+			retry,
+			&state,
+			&alert_sender,
+		)
+		.await
+		.unwrap();
+		let RetryOutcome::Retry(retry) = retry else {
+			panic!("Block should be retried");
+		};
+		assert_eq!(retry.attempts, 1);
+		assert!(retry.retry_at >= Instant::now() + cfg.sampling_retry_delay);
+
+		assert!(alert_receiver.try_recv().is_err());
+
+		let retry = retry_sampling(
+			&db,
+			&mock_network_client,
+			&metrics,
+			&cfg,
+			retry,
+			&state,
+			&alert_sender,
 		)
 		.await
 		.unwrap();
+		assert!(matches!(retry, RetryOutcome::Failed));
+		assert!(db.get::<u32>(Key::VerifiedCellCount(57)).unwrap().is_none());
+		let report = db.get::<SamplingReport>(Key::SamplingReport(57)).unwrap();
+		let report = report.expect("Sampling report should be stored");
+		assert_eq!(report.retries, 2);
+		assert!(!report.cells[0].verified);
+		let state = state.lock().unwrap();
+		assert!(state.confidence_retrying.is_empty());
+		assert!(state.confidence_failed.contains(&57));
+		let alert = alert_receiver.try_recv().unwrap();
+		assert_eq!(alert, Alert::ConfidenceFailed { block_number: 57 });
 	}
 }
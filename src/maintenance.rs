@@ -1,13 +1,14 @@
 use color_eyre::{eyre::WrapErr, Result};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tokio::sync::broadcast;
 use tracing::{debug, error, info};
 
 use crate::{
+	data::{self, Database},
 	network::p2p::Client as P2pClient,
 	shutdown::Controller,
 	telemetry::{MetricValue, Metrics},
-	types::BlockVerified,
+	types::{BlockVerified, State},
 };
 
 #[derive(Clone, Copy)]
@@ -20,10 +21,14 @@ pub struct StaticConfigParams {
 
 pub async fn process_block(
 	block_number: u32,
+	db: &impl Database,
 	p2p_client: &P2pClient,
 	static_config_params: StaticConfigParams,
 	metrics: &Arc<impl Metrics>,
+	state: &Arc<Mutex<State>>,
 ) -> Result<()> {
+	data::store_state(db, &state.lock().unwrap())?;
+
 	if block_number % static_config_params.pruning_interval == 0 {
 		info!(block_number, "Pruning...");
 		match p2p_client.prune_expired_records().await {
@@ -83,10 +88,12 @@ pub async fn process_block(
 }
 
 pub async fn run(
+	db: impl Database,
 	p2p_client: P2pClient,
 	metrics: Arc<impl Metrics>,
 	mut block_receiver: broadcast::Receiver<BlockVerified>,
 	static_config_params: StaticConfigParams,
+	state: Arc<Mutex<State>>,
 	shutdown: Controller<String>,
 ) {
 	info!("Starting maintenance...");
@@ -94,7 +101,15 @@ pub async fn run(
 	loop {
 		let result = match block_receiver.recv().await {
 			Ok(block) => {
-				process_block(block.block_num, &p2p_client, static_config_params, &metrics).await
+				process_block(
+					block.block_num,
+					&db,
+					&p2p_client,
+					static_config_params,
+					&metrics,
+					&state,
+				)
+				.await
 			},
 			Err(error) => Err(error.into()),
 		};
@@ -1,4 +1,5 @@
 use async_trait::async_trait;
+use codec::{Decode, Encode};
 use color_eyre::{eyre::WrapErr, Result};
 use dusk_plonk::prelude::PublicParameters;
 use kate_recovery::{
@@ -7,6 +8,7 @@ use kate_recovery::{
 	matrix::{Dimensions, Position},
 };
 use mockall::automock;
+use serde::{Deserialize, Serialize};
 use sp_core::H256;
 use std::{sync::Arc, time::Duration};
 use tokio::time::Instant;
@@ -36,6 +38,123 @@ pub struct FetchStats {
 	pub dht_fetch_duration: f64,
 	pub rpc_fetched: Option<f64>,
 	pub rpc_fetch_duration: Option<f64>,
+	/// Fetched cells in order of fetching, cells fetched from RPC are after DHT cells
+	pub cells: Vec<FetchedCell>,
+}
+
+#[derive(Serialize, Deserialize, Encode, Decode, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CellSource {
+	Dht,
+	Rpc,
+}
+
+/// Source and proof verification result of the fetched cell
+#[derive(Clone, Copy, Debug)]
+pub struct FetchedCell {
+	pub position: Position,
+	pub source: CellSource,
+	pub verified: bool,
+}
+
+fn fetched_cells(
+	source: CellSource,
+	verified: &[Cell],
+	unverified: &[Position],
+) -> Vec<FetchedCell> {
+	let verified = verified.iter().map(|cell| (cell.position, true));
+	let unverified = unverified.iter().map(|&position| (position, false));
+	verified
+		.chain(unverified)
+		.map(|(position, verified)| FetchedCell {
+			position,
+			source,
+			verified,
+		})
+		.collect()
+}
+
+/// Sampled cell with the source it is fetched from, and the proof verification result.
+/// Source is not set if cell is not fetched.
+#[derive(Serialize, Deserialize, Encode, Decode, Clone, Debug, PartialEq)]
+pub struct SampledCell {
+	pub row: u32,
+	pub col: u16,
+	pub source: Option<CellSource>,
+	pub verified: bool,
+}
+
+/// Per block sampling report, stored for the audit of block availability decisions
+#[derive(Serialize, Deserialize, Encode, Decode, Clone, Debug, PartialEq)]
+pub struct SamplingReport {
+	pub block_number: u32,
+	/// Seed used to generate sampled cell positions
+	pub seed: H256,
+	pub cells: Vec<SampledCell>,
+	/// Duration of fetching cells from DHT, in milliseconds
+	pub dht_fetch_duration: u64,
+	/// Duration of fetching cells from RPC, in milliseconds
+	pub rpc_fetch_duration: Option<u64>,
+	/// Number of re-sampling retries of unfetched cells
+	pub retries: u32,
+}
+
+fn as_millis(seconds: f64) -> u64 {
+	(seconds * 1000.0) as u64
+}
+
+impl SamplingReport {
+	pub fn new(
+		block_number: u32,
+		seed: [u8; 32],
+		positions: &[Position],
+		stats: &FetchStats,
+	) -> Self {
+		let cells = positions
+			.iter()
+			.map(|position| SampledCell {
+				row: position.row,
+				col: position.col,
+				source: None,
+				verified: false,
+			})
+			.collect();
+
+		let mut report = SamplingReport {
+			block_number,
+			seed: H256(seed),
+			cells,
+			dht_fetch_duration: 0,
+			rpc_fetch_duration: None,
+			retries: 0,
+		};
+		report.update(stats);
+		report
+	}
+
+	/// Updates report with the fetched cells and durations, used on re-sampling retries
+	pub fn update(&mut self, stats: &FetchStats) {
+		self.dht_fetch_duration += as_millis(stats.dht_fetch_duration);
+		if let Some(duration) = stats.rpc_fetch_duration {
+			let rpc_fetch_duration = self.rpc_fetch_duration.get_or_insert(0);
+			*rpc_fetch_duration += as_millis(duration);
+		}
+
+		for fetched in &stats.cells {
+			let Some(cell) = self
+				.cells
+				.iter_mut()
+				.find(|cell| cell.row == fetched.position.row && cell.col == fetched.position.col)
+			else {
+				continue;
+			};
+			// Verified cell is not fetched again, so only unverified cells are overridden
+			if !cell.verified {
+				cell.source = Some(fetched.source);
+				cell.verified = fetched.verified;
+			}
+		}
+	}
 }
 
 type RPCFetchStats = (usize, Duration);
@@ -53,6 +172,7 @@ impl FetchStats {
 			dht_fetch_duration: dht_fetch_duration.as_secs_f64(),
 			rpc_fetched: rpc_fetch_stats.map(|(rpc_fetched, _)| rpc_fetched as f64),
 			rpc_fetch_duration: rpc_fetch_stats.map(|(_, duration)| duration.as_secs_f64()),
+			cells: vec![],
 		}
 	}
 }
@@ -73,7 +193,7 @@ impl DHTWithRPCFallbackClient {
 		dimensions: Dimensions,
 		commitments: &Commitments,
 		positions: &[Position],
-	) -> Result<(Vec<Cell>, Vec<Position>, Duration)> {
+	) -> Result<(Vec<Cell>, Vec<Position>, Vec<Position>, Duration)> {
 		let begin = Instant::now();
 
 		let (mut dht_fetched, mut unfetched) = self
@@ -83,7 +203,7 @@ impl DHTWithRPCFallbackClient {
 
 		let fetch_elapsed = begin.elapsed();
 
-		let (verified, mut unverified) = proof::verify(
+		let (verified, unverified) = proof::verify(
 			block_number,
 			dimensions,
 			&dht_fetched,
@@ -104,9 +224,9 @@ impl DHTWithRPCFallbackClient {
 		);
 
 		dht_fetched.retain(|cell| verified.contains(&cell.position));
-		unfetched.append(&mut unverified);
+		unfetched.extend(&unverified);
 
-		Ok((dht_fetched, unfetched, fetch_elapsed))
+		Ok((dht_fetched, unfetched, unverified, fetch_elapsed))
 	}
 
 	async fn fetch_verified_from_rpc(
@@ -161,13 +281,16 @@ impl Client for DHTWithRPCFallbackClient {
 		commitments: &Commitments,
 		positions: &[Position],
 	) -> Result<(Vec<Cell>, Vec<Position>, FetchStats)> {
-		let (dht_fetched, unfetched, dht_fetch_duration) = self
+		let (dht_fetched, unfetched, dht_unverified, dht_fetch_duration) = self
 			.fetch_verified_from_dht(block_number, dimensions, commitments, positions)
 			.await?;
 
+		let mut cells = fetched_cells(CellSource::Dht, &dht_fetched, &dht_unverified);
+
 		if self.disable_rpc {
-			let stats =
+			let mut stats =
 				FetchStats::new(positions.len(), dht_fetched.len(), dht_fetch_duration, None);
+			stats.cells = cells;
 			return Ok((dht_fetched, unfetched, stats));
 		};
 
@@ -189,12 +312,15 @@ impl Client for DHTWithRPCFallbackClient {
 			debug!("Error inserting cells into DHT: {error}");
 		}
 
-		let stats = FetchStats::new(
+		cells.extend(fetched_cells(CellSource::Rpc, &rpc_fetched, &unfetched));
+
+		let mut stats = FetchStats::new(
 			positions.len(),
 			dht_fetched.len(),
 			dht_fetch_duration,
 			Some((rpc_fetched.len(), rpc_fetch_duration)),
 		);
+		stats.cells = cells;
 
 		let mut fetched = vec![];
 		fetched.extend(dht_fetched);
@@ -20,13 +20,15 @@ pub mod analyzer;
 mod client;
 mod event_loop;
 mod kad_mem_store;
+mod kad_rocksdb_store;
+mod kad_store;
 
 use crate::types::{LibP2PConfig, SecretKey};
 pub use client::Client;
 pub use event_loop::EventLoop;
 pub use kad_mem_store::MemoryStoreConfig;
 
-use self::{client::BlockStat, kad_mem_store::MemoryStore};
+use self::{client::BlockStat, kad_store::Store};
 use libp2p_allow_block_list as allow_block_list;
 
 #[derive(Debug)]
@@ -93,7 +95,7 @@ type CommandReceiver = mpsc::UnboundedReceiver<SendableCommand>;
 #[derive(NetworkBehaviour)]
 #[behaviour(event_process = false)]
 pub struct Behaviour {
-	kademlia: kad::Behaviour<MemoryStore>,
+	kademlia: kad::Behaviour<Store>,
 	identify: identify::Behaviour,
 	ping: ping::Behaviour,
 	mdns: mdns::tokio::Behaviour,
@@ -116,7 +118,7 @@ fn generate_config(config: libp2p::swarm::Config, cfg: &LibP2PConfig) -> libp2p:
 async fn build_swarm(
 	cfg: &LibP2PConfig,
 	id_keys: &libp2p::identity::Keypair,
-	kad_store: MemoryStore,
+	kad_store: Store,
 	is_ws_transport: bool,
 ) -> Result<Swarm<Behaviour>> {
 	// create Identify Protocol Config
@@ -1,4 +1,7 @@
-use color_eyre::{eyre::eyre, Result};
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
 use futures::StreamExt;
 use libp2p::{
 	autonat::{self, NatStatus},
@@ -26,10 +29,13 @@ use tokio::{
 use tracing::{debug, error, info, trace, warn};
 
 use crate::{
-	network::p2p::kad_mem_store::MemoryStore,
+	data::rocks_db::RocksDB,
+	network::p2p::{kad_mem_store::MemoryStore, kad_rocksdb_store::RocksDBStore, kad_store::Store},
 	shutdown::Controller,
 	telemetry::{MetricCounter, MetricValue, Metrics},
-	types::{AgentVersion, IdentifyConfig, KademliaMode, LibP2PConfig, TimeToLive},
+	types::{
+		AgentVersion, IdentifyConfig, KademliaMode, KademliaRecordStore, LibP2PConfig, TimeToLive,
+	},
 };
 
 use super::{
@@ -127,17 +133,26 @@ impl EventLoop {
 		id_keys: &Keypair,
 		is_fat_client: bool,
 		is_ws_transport: bool,
+		db: RocksDB,
 		shutdown: Controller<String>,
-	) -> Self {
+	) -> Result<Self> {
 		let bootstrap_interval = cfg.bootstrap_interval;
 		let peer_id = id_keys.public().to_peer_id();
-		let store = MemoryStore::with_config(peer_id, (&cfg).into());
+		let store = match cfg.kademlia.record_store {
+			KademliaRecordStore::Memory => {
+				Store::Memory(MemoryStore::with_config(peer_id, (&cfg).into()))
+			},
+			KademliaRecordStore::Disk => Store::RocksDB(
+				RocksDBStore::with_config(peer_id, (&cfg).into(), db.inner())
+					.wrap_err("Unable to open Kademlia record store")?,
+			),
+		};
 
 		let swarm = build_swarm(&cfg, id_keys, store, is_ws_transport)
 			.await
-			.expect("Unable to build swarm.");
+			.wrap_err("Unable to build swarm")?;
 
-		Self {
+		Ok(Self {
 			swarm,
 			pending_kad_queries: Default::default(),
 			pending_swarm_events: Default::default(),
@@ -158,7 +173,7 @@ impl EventLoop {
 				is_fat_client,
 				kad_record_ttl: TimeToLive(cfg.kademlia.kad_record_ttl),
 			},
-		}
+		})
 	}
 
 	pub async fn run(mut self, metrics: Arc<impl Metrics>, mut command_receiver: CommandReceiver) {
@@ -89,7 +89,6 @@ impl MemoryStore {
 		}
 	}
 
-	#[allow(dead_code)]
 	/// Retains the records satisfying a predicate.
 	pub fn retain<F>(&mut self, f: F)
 	where
@@ -98,14 +97,15 @@ impl MemoryStore {
 		self.records.retain(f);
 	}
 
+	#[allow(dead_code)]
 	// Inserts a record into the record store
 	pub fn put(&mut self, r: Record) -> Result<()> {
 		RecordStore::put(self, r)
 	}
 
-	// Return an iterator over records
-	pub fn records_iter(&mut self) -> std::collections::hash_map::Iter<'_, RecordKey, Record> {
-		self.records.iter()
+	/// Returns number of stored records
+	pub fn records_count(&self) -> usize {
+		self.records.len()
 	}
 
 	/// Shrinks the capacity of hashmap as much as possible
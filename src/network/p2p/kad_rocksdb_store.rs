@@ -0,0 +1,381 @@
+use super::kad_mem_store::{MemoryStore, MemoryStoreConfig};
+use crate::data::KADEMLIA_STORE_CF;
+use codec::{Decode, Encode};
+use color_eyre::eyre::{self, eyre};
+use libp2p::identity::PeerId;
+use libp2p::kad::store::{Error, RecordStore, Result};
+use libp2p::kad::{ProviderRecord, Record, RecordKey};
+use rocksdb::{IteratorMode, WriteBatch};
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{error, trace};
+
+/// Record as stored in the database.
+/// Expiration is stored as wall clock time, since `Instant` is not valid across restarts.
+#[derive(Encode, Decode)]
+struct StoredRecord {
+	value: Vec<u8>,
+	publisher: Option<Vec<u8>>,
+	/// Expiration time in milliseconds since UNIX epoch
+	expires: Option<u64>,
+}
+
+fn to_unix_millis(instant: Instant) -> u64 {
+	let now = Instant::now();
+	let time = if instant >= now {
+		SystemTime::now() + (instant - now)
+	} else {
+		SystemTime::now() - (now - instant)
+	};
+	time.duration_since(UNIX_EPOCH)
+		.map(|duration| duration.as_millis() as u64)
+		.unwrap_or(0)
+}
+
+fn from_unix_millis(millis: u64) -> Instant {
+	let now = Instant::now();
+	let time = UNIX_EPOCH + Duration::from_millis(millis);
+	match time.duration_since(SystemTime::now()) {
+		Ok(remaining) => now + remaining,
+		Err(error) => now.checked_sub(error.duration()).unwrap_or(now),
+	}
+}
+
+fn encode_record(record: &Record) -> Vec<u8> {
+	StoredRecord {
+		value: record.value.clone(),
+		publisher: record.publisher.map(|publisher| publisher.to_bytes()),
+		expires: record.expires.map(to_unix_millis),
+	}
+	.encode()
+}
+
+fn decode_record(key: &[u8], value: &[u8]) -> Option<Record> {
+	let stored = StoredRecord::decode(&mut &value[..]).ok()?;
+	let publisher = match stored.publisher {
+		Some(publisher) => Some(PeerId::from_bytes(&publisher).ok()?),
+		None => None,
+	};
+	Some(Record {
+		key: RecordKey::from(key.to_vec()),
+		value: stored.value,
+		publisher,
+		expires: stored.expires.map(from_unix_millis),
+	})
+}
+
+/// RocksDB implementation of a `RecordStore`.
+///
+/// Records are stored in the dedicated column family and survive restarts,
+/// while provider records are kept in memory.
+pub struct RocksDBStore {
+	/// The configuration of the store.
+	config: MemoryStoreConfig,
+	/// Database holding the records column family.
+	db: Arc<rocksdb::DB>,
+	/// Number of stored records, tracked to avoid counting on each insert.
+	records_count: usize,
+	/// In-memory store of the provider records.
+	providers: MemoryStore,
+	/// Number of records not stored due to the database failures.
+	failed_writes: usize,
+}
+
+impl RocksDBStore {
+	/// Creates a new `RocksDBStore` with the given configuration,
+	/// on top of the records already stored in the database.
+	pub fn with_config(
+		local_id: PeerId,
+		config: MemoryStoreConfig,
+		db: Arc<rocksdb::DB>,
+	) -> eyre::Result<Self> {
+		let records_count = {
+			let cf_handle = db
+				.cf_handle(KADEMLIA_STORE_CF)
+				.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+			db.iterator_cf(&cf_handle, IteratorMode::Start).count()
+		};
+
+		Ok(RocksDBStore {
+			providers: MemoryStore::with_config(local_id, config.clone()),
+			config,
+			db,
+			records_count,
+			failed_writes: 0,
+		})
+	}
+
+	/// Logs and counts the database failure on record write.
+	/// Store errors have no variant for the database failures,
+	/// so failure is counted apart from the store limits and record is reported as not stored.
+	fn write_failed(&mut self, message: &str) -> Error {
+		self.failed_writes += 1;
+		error!(
+			failed_writes = self.failed_writes,
+			"Kademlia record store database failure: {message}"
+		);
+		Error::MaxRecords
+	}
+
+	/// Retains the records satisfying a predicate.
+	/// Records modified by the predicate are written back to the database.
+	pub fn retain<F>(&mut self, mut f: F)
+	where
+		F: FnMut(&RecordKey, &mut Record) -> bool,
+	{
+		let Some(cf_handle) = self.db.cf_handle(KADEMLIA_STORE_CF) else {
+			error!("Couldn't get Column Family handle from RocksDB");
+			return;
+		};
+
+		let mut batch = WriteBatch::default();
+		let mut removed = 0;
+		for entry in self.db.iterator_cf(&cf_handle, IteratorMode::Start) {
+			let (key, value) = match entry {
+				Ok(entry) => entry,
+				Err(error) => {
+					error!("Failed to iterate Kademlia records: {error}");
+					return;
+				},
+			};
+
+			// Records which cannot be decoded are not retained
+			let Some(mut record) = decode_record(&key, &value) else {
+				batch.delete_cf(&cf_handle, key);
+				removed += 1;
+				continue;
+			};
+
+			let original = record.clone();
+			if !f(&original.key, &mut record) {
+				batch.delete_cf(&cf_handle, key);
+				removed += 1;
+			} else if record != original {
+				batch.put_cf(&cf_handle, key, encode_record(&record));
+			}
+		}
+
+		if let Err(error) = self.db.write(batch) {
+			error!("Failed to retain Kademlia records: {error}");
+			return;
+		}
+		self.records_count = self.records_count.saturating_sub(removed);
+	}
+
+	/// Returns number of stored records
+	pub fn records_count(&self) -> usize {
+		self.records_count
+	}
+
+	/// Returns number of records not stored due to the database failures
+	pub fn failed_writes(&self) -> usize {
+		self.failed_writes
+	}
+
+	/// Compacts the records column family, reclaiming the space of removed records
+	pub fn shrink_hashmap(&mut self) {
+		let Some(cf_handle) = self.db.cf_handle(KADEMLIA_STORE_CF) else {
+			error!("Couldn't get Column Family handle from RocksDB");
+			return;
+		};
+		self.db
+			.compact_range_cf(&cf_handle, None::<&[u8]>, None::<&[u8]>);
+
+		trace!("RocksDB store - Len: {:?}", self.records_count);
+	}
+}
+
+impl RecordStore for RocksDBStore {
+	type RecordsIter<'a> = Box<dyn Iterator<Item = Cow<'a, Record>> + 'a>;
+
+	type ProvidedIter<'a> = <MemoryStore as RecordStore>::ProvidedIter<'a>;
+
+	fn get(&self, k: &RecordKey) -> Option<Cow<'_, Record>> {
+		let cf_handle = self.db.cf_handle(KADEMLIA_STORE_CF)?;
+		match self.db.get_pinned_cf(&cf_handle, k) {
+			Ok(value) => value
+				.and_then(|value| decode_record(k.as_ref(), &value))
+				.map(Cow::Owned),
+			Err(error) => {
+				error!("Failed to get Kademlia record: {error}");
+				None
+			},
+		}
+	}
+
+	fn put(&mut self, r: Record) -> Result<()> {
+		if r.value.len() >= self.config.max_value_bytes {
+			return Err(Error::ValueTooLarge);
+		}
+
+		let db = self.db.clone();
+		let Some(cf_handle) = db.cf_handle(KADEMLIA_STORE_CF) else {
+			return Err(self.write_failed("Couldn't get Column Family handle from RocksDB"));
+		};
+
+		let exists = match db.get_pinned_cf(&cf_handle, &r.key) {
+			Ok(value) => value.is_some(),
+			Err(error) => {
+				return Err(self.write_failed(&format!("Failed to get record: {error}")));
+			},
+		};
+
+		if !exists && self.records_count >= self.config.max_records {
+			return Err(Error::MaxRecords);
+		}
+
+		if let Err(error) = db.put_cf(&cf_handle, &r.key, encode_record(&r)) {
+			return Err(self.write_failed(&format!("Failed to put record: {error}")));
+		}
+
+		if !exists {
+			self.records_count += 1;
+		}
+		Ok(())
+	}
+
+	fn remove(&mut self, k: &RecordKey) {
+		let Some(cf_handle) = self.db.cf_handle(KADEMLIA_STORE_CF) else {
+			error!("Couldn't get Column Family handle from RocksDB");
+			return;
+		};
+
+		match self.db.get_pinned_cf(&cf_handle, k) {
+			Ok(Some(_)) => {},
+			Ok(None) => return,
+			Err(error) => {
+				error!("Failed to get Kademlia record: {error}");
+				return;
+			},
+		};
+
+		if let Err(error) = self.db.delete_cf(&cf_handle, k) {
+			error!("Failed to remove Kademlia record: {error}");
+			return;
+		}
+		self.records_count = self.records_count.saturating_sub(1);
+	}
+
+	fn records(&self) -> Self::RecordsIter<'_> {
+		let Some(cf_handle) = self.db.cf_handle(KADEMLIA_STORE_CF) else {
+			error!("Couldn't get Column Family handle from RocksDB");
+			return Box::new(std::iter::empty());
+		};
+
+		let records = self
+			.db
+			.iterator_cf(&cf_handle, IteratorMode::Start)
+			.map_while(|entry| {
+				entry
+					.map_err(|error| error!("Failed to iterate Kademlia records: {error}"))
+					.ok()
+			})
+			.filter_map(|(key, value)| decode_record(&key, &value))
+			.map(Cow::Owned);
+
+		Box::new(records)
+	}
+
+	fn add_provider(&mut self, record: ProviderRecord) -> Result<()> {
+		self.providers.add_provider(record)
+	}
+
+	fn providers(&self, key: &RecordKey) -> Vec<ProviderRecord> {
+		self.providers.providers(key)
+	}
+
+	fn provided(&self) -> Self::ProvidedIter<'_> {
+		self.providers.provided()
+	}
+
+	fn remove_provider(&mut self, key: &RecordKey, provider: &PeerId) {
+		self.providers.remove_provider(key, provider)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::data::rocks_db::RocksDB;
+	use std::fs;
+
+	fn record(key: &str, expires: Instant) -> Record {
+		Record {
+			key: RecordKey::new(&key),
+			value: vec![1, 2, 3],
+			publisher: Some(PeerId::random()),
+			expires: Some(expires),
+		}
+	}
+
+	#[test]
+	fn records_are_persisted_and_retained() {
+		let path = std::env::temp_dir().join(format!("avail_light_{}", uuid::Uuid::new_v4()));
+		let path_str = path.to_str().unwrap();
+		let peer_id = PeerId::random();
+		let config = MemoryStoreConfig {
+			max_records: 2,
+			..Default::default()
+		};
+
+		let now = Instant::now();
+		let live = record("1:0:0", now + Duration::from_secs(3600));
+		let expired = record("1:0:1", now - Duration::from_secs(1));
+
+		let db = RocksDB::open(path_str).unwrap();
+		let mut store = RocksDBStore::with_config(peer_id, config.clone(), db.inner()).unwrap();
+		store.put(live.clone()).unwrap();
+		store.put(expired.clone()).unwrap();
+		assert!(matches!(
+			store.put(record("1:0:2", now)),
+			Err(Error::MaxRecords)
+		));
+		assert_eq!(store.failed_writes(), 0);
+		drop(store);
+		drop(db);
+
+		let db = RocksDB::open(path_str).unwrap();
+		let mut store = RocksDBStore::with_config(peer_id, config, db.inner()).unwrap();
+		assert_eq!(store.records_count(), 2);
+
+		let stored = store.get(&live.key).unwrap().into_owned();
+		assert_eq!(stored.value, live.value);
+		assert_eq!(stored.publisher, live.publisher);
+		assert!(!stored.is_expired(Instant::now()));
+
+		store.retain(|_, record| !record.is_expired(Instant::now()));
+		assert_eq!(store.records_count(), 1);
+		assert!(store.get(&expired.key).is_none());
+		assert_eq!(store.records().count(), 1);
+
+		store.remove(&live.key);
+		assert_eq!(store.records_count(), 0);
+		drop(store);
+		drop(db);
+
+		fs::remove_dir_all(path).unwrap();
+	}
+
+	#[test]
+	fn failed_put_is_reported() {
+		let path = std::env::temp_dir().join(format!("avail_light_{}", uuid::Uuid::new_v4()));
+		let peer_id = PeerId::random();
+		let config = MemoryStoreConfig::default();
+
+		// Database without the records column family
+		let db = Arc::new(rocksdb::DB::open_default(&path).unwrap());
+		let mut store = RocksDBStore {
+			providers: MemoryStore::with_config(peer_id, config.clone()),
+			config,
+			db,
+			records_count: 0,
+		};
+		let now = Instant::now();
+		assert!(store.put(record("1:0:0", now)).is_err());
+		assert_eq!(store.records_count(), 0);
+		drop(store);
+
+		fs::remove_dir_all(path).unwrap();
+	}
+}
@@ -0,0 +1,107 @@
+use super::{kad_mem_store::MemoryStore, kad_rocksdb_store::RocksDBStore};
+use libp2p::identity::PeerId;
+use libp2p::kad::store::{RecordStore, Result};
+use libp2p::kad::{ProviderRecord, Record, RecordKey};
+use std::borrow::Cow;
+
+/// Kademlia record store, either kept in memory or persisted on disk.
+pub enum Store {
+	Memory(MemoryStore),
+	RocksDB(RocksDBStore),
+}
+
+impl Store {
+	/// Retains the records satisfying a predicate.
+	pub fn retain<F>(&mut self, f: F)
+	where
+		F: FnMut(&RecordKey, &mut Record) -> bool,
+	{
+		match self {
+			Store::Memory(store) => store.retain(f),
+			Store::RocksDB(store) => store.retain(f),
+		}
+	}
+
+	// Inserts a record into the record store
+	pub fn put(&mut self, r: Record) -> Result<()> {
+		RecordStore::put(self, r)
+	}
+
+	/// Returns number of stored records
+	pub fn records_count(&self) -> usize {
+		match self {
+			Store::Memory(store) => store.records_count(),
+			Store::RocksDB(store) => store.records_count(),
+		}
+	}
+
+	/// Releases resources held by removed records
+	pub fn shrink_hashmap(&mut self) {
+		match self {
+			Store::Memory(store) => store.shrink_hashmap(),
+			Store::RocksDB(store) => store.shrink_hashmap(),
+		}
+	}
+}
+
+impl RecordStore for Store {
+	type RecordsIter<'a> = Box<dyn Iterator<Item = Cow<'a, Record>> + 'a>;
+
+	type ProvidedIter<'a> = <MemoryStore as RecordStore>::ProvidedIter<'a>;
+
+	fn get(&self, k: &RecordKey) -> Option<Cow<'_, Record>> {
+		match self {
+			Store::Memory(store) => store.get(k),
+			Store::RocksDB(store) => store.get(k),
+		}
+	}
+
+	fn put(&mut self, r: Record) -> Result<()> {
+		match self {
+			Store::Memory(store) => RecordStore::put(store, r),
+			Store::RocksDB(store) => store.put(r),
+		}
+	}
+
+	fn remove(&mut self, k: &RecordKey) {
+		match self {
+			Store::Memory(store) => store.remove(k),
+			Store::RocksDB(store) => store.remove(k),
+		}
+	}
+
+	fn records(&self) -> Self::RecordsIter<'_> {
+		match self {
+			Store::Memory(store) => Box::new(store.records()),
+			Store::RocksDB(store) => store.records(),
+		}
+	}
+
+	fn add_provider(&mut self, record: ProviderRecord) -> Result<()> {
+		match self {
+			Store::Memory(store) => store.add_provider(record),
+			Store::RocksDB(store) => store.add_provider(record),
+		}
+	}
+
+	fn providers(&self, key: &RecordKey) -> Vec<ProviderRecord> {
+		match self {
+			Store::Memory(store) => store.providers(key),
+			Store::RocksDB(store) => store.providers(key),
+		}
+	}
+
+	fn provided(&self) -> Self::ProvidedIter<'_> {
+		match self {
+			Store::Memory(store) => store.provided(),
+			Store::RocksDB(store) => store.provided(),
+		}
+	}
+
+	fn remove_provider(&mut self, key: &RecordKey, provider: &PeerId) {
+		match self {
+			Store::Memory(store) => store.remove_provider(key, provider),
+			Store::RocksDB(store) => store.remove_provider(key, provider),
+		}
+	}
+}
@@ -3,24 +3,28 @@ use avail_subxt::{primitives::Header, utils::H256};
 use codec::Decode;
 use color_eyre::{eyre::eyre, Report, Result};
 use kate_recovery::matrix::{Dimensions, Position};
-use rand::{seq::SliceRandom, thread_rng, Rng};
-use serde::{de, Deserialize};
+use rand::{seq::SliceRandom, thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+use serde::{de, Deserialize, Serialize};
 use sp_core::bytes::from_hex;
 use std::{
 	collections::HashSet,
 	fmt::Display,
 	sync::{Arc, Mutex},
+	time::{Duration, Instant},
 };
 use tokio::{
 	sync::broadcast,
 	time::{self, timeout},
 };
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::{
+	alerts::AlertSender,
 	data::Database,
 	network::rpc,
-	types::{GrandpaJustification, RetryConfig, State},
+	telemetry::{MetricValue, Metrics},
+	types::{GrandpaJustification, RetryConfig, RpcHealthConfig, State},
 };
 
 mod client;
@@ -135,6 +139,147 @@ impl Display for Node {
 	}
 }
 
+/// Weight of the latest sample in the moving averages of the node latency and error rate
+const HEALTH_SMOOTHING: f64 = 0.3;
+
+/// Error rate at which the node is considered unhealthy
+const MAX_ERROR_RATE: f64 = 0.5;
+
+/// Minimum time between the switches to the healthiest node, so failed switch is not retried on every request
+const REROUTE_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Health of the RPC node, tracked from the requests routed to the node and from the background probes
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct NodeHealth {
+	pub host: String,
+	/// Moving average of the request latency in milliseconds, if any request is completed
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub latency: Option<f64>,
+	/// Moving average of the failed requests ratio
+	pub error_rate: f64,
+	/// Latest finalized block number reported by the node
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub finalized_head: Option<u32>,
+	/// Number of blocks the node is behind the best finalized head of all nodes
+	pub head_lag: u32,
+	pub healthy: bool,
+}
+
+impl NodeHealth {
+	fn new(host: String) -> Self {
+		NodeHealth {
+			host,
+			latency: None,
+			error_rate: 0.0,
+			finalized_head: None,
+			head_lag: 0,
+			healthy: true,
+		}
+	}
+
+	/// Ranking key, healthy nodes with the lowest head lag and latency first.
+	/// Nodes without completed requests are ranked after the ones with known latency.
+	fn rank(&self) -> (bool, u32, u64) {
+		let latency = self.latency.map_or(u64::MAX, |latency| latency as u64);
+		(!self.healthy, self.head_lag, latency)
+	}
+}
+
+/// Health of all configured RPC nodes, used to route requests to the healthiest node
+#[derive(Clone, Debug, Default)]
+pub struct NodesHealth {
+	max_head_lag: u32,
+	pub nodes: Vec<NodeHealth>,
+	/// Time of the last switch to the healthiest node
+	last_reroute: Option<Instant>,
+}
+
+impl NodesHealth {
+	pub fn new(nodes: &Nodes, max_head_lag: u32) -> Self {
+		NodesHealth {
+			max_head_lag,
+			nodes: nodes
+				.iter()
+				.map(|node| NodeHealth::new(node.host.clone()))
+				.collect(),
+			last_reroute: None,
+		}
+	}
+
+	fn get(&self, host: &str) -> Option<&NodeHealth> {
+		self.nodes.iter().find(|node| node.host == host)
+	}
+
+	fn update<F: FnOnce(&mut NodeHealth)>(&mut self, host: &str, f: F) {
+		if let Some(node) = self.nodes.iter_mut().find(|node| node.host == host) {
+			f(node);
+		}
+
+		// head lag is relative to the best finalized head, so it is updated for all nodes
+		let best_head = self
+			.nodes
+			.iter()
+			.filter_map(|node| node.finalized_head)
+			.max();
+		for node in &mut self.nodes {
+			node.head_lag = best_head
+				.zip(node.finalized_head)
+				.map(|(best_head, head)| best_head.saturating_sub(head))
+				.unwrap_or(0);
+			node.healthy = node.error_rate < MAX_ERROR_RATE && node.head_lag <= self.max_head_lag;
+		}
+	}
+
+	/// Records successful request and its latency
+	pub fn record_success(&mut self, host: &str, latency: Duration) {
+		let latency = latency.as_secs_f64() * 1000.0;
+		self.update(host, |node| {
+			node.error_rate *= 1.0 - HEALTH_SMOOTHING;
+			node.latency = Some(match node.latency {
+				Some(average) => average + HEALTH_SMOOTHING * (latency - average),
+				None => latency,
+			});
+		});
+	}
+
+	/// Records failed request
+	pub fn record_failure(&mut self, host: &str) {
+		self.update(host, |node| {
+			node.error_rate += HEALTH_SMOOTHING * (1.0 - node.error_rate);
+		});
+	}
+
+	/// Records finalized head reported by the node
+	pub fn record_head(&mut self, host: &str, finalized_head: u32) {
+		self.update(host, |node| node.finalized_head = Some(finalized_head));
+	}
+
+	/// Returns `true` if the node is healthy, nodes which are not tracked are considered healthy
+	pub fn is_healthy(&self, host: &str) -> bool {
+		self.get(host).map(|node| node.healthy).unwrap_or(true)
+	}
+
+	/// Returns `true` if the given node is unhealthy, and there is a healthy node to switch to.
+	/// Switch is not attempted again until the cooldown after the last switch passes.
+	pub fn should_reroute(&self, current_host: &str) -> bool {
+		let is_cooled_down = self
+			.last_reroute
+			.map(|last_reroute| last_reroute.elapsed() >= REROUTE_COOLDOWN)
+			.unwrap_or(true);
+		is_cooled_down
+			&& !self.is_healthy(current_host)
+			&& self
+				.nodes
+				.iter()
+				.any(|node| node.healthy && node.host != current_host)
+	}
+
+	/// Records the switch to the healthiest node, successful or not
+	pub fn record_reroute(&mut self) {
+		self.last_reroute = Some(Instant::now());
+	}
+}
+
 #[derive(Clone)]
 pub struct Nodes {
 	list: Vec<Node>,
@@ -157,13 +302,13 @@ impl Nodes {
 		}
 	}
 
-	/// Shuffles the list of available Nodes, excluding the host used for the current Subxt client creation.
+	/// Ranks the list of available Nodes by health, excluding the host used for the current Subxt client creation.
 	///
-	/// This method returns a new shuffled list of Nodes from the original list, excluding the Node
+	/// This method returns a new list of Nodes from the original list, excluding the Node
 	/// associated with the current Subxt client host.
 	/// The purpose of this exclusion is to prevent accidentally reconnecting to the same host in case of errors.
-	/// If there's a need to switch to a different host, the shuffled list provides a randomized order of available Nodes.
-	fn shuffle(&self, current_host: String) -> Vec<Node> {
+	/// Healthy Nodes with the lowest head lag and latency come first, Nodes with the same rank are shuffled.
+	fn ranked(&self, health: &NodesHealth, current_host: &str) -> Vec<Node> {
 		if self.list.len() <= 1 {
 			return self.list.clone();
 		}
@@ -171,10 +316,12 @@ impl Nodes {
 		let mut list = self
 			.list
 			.iter()
-			.filter(|&Node { host, .. }| host != &current_host)
+			.filter(|&Node { host, .. }| host != current_host)
 			.cloned()
 			.collect::<Vec<Node>>();
 		list.shuffle(&mut thread_rng());
+		// sort is stable, so the shuffled order is kept for the Nodes with the same rank
+		list.sort_by_key(|Node { host, .. }| health.get(host).map(NodeHealth::rank));
 		list
 	}
 
@@ -201,25 +348,80 @@ impl<'a> Iterator for NodesIterator<'a> {
 	}
 }
 
-pub async fn init<T: Database>(
+pub async fn init<T: Database, M: Metrics>(
 	db: T,
+	metrics: Arc<M>,
 	state: Arc<Mutex<State>>,
 	nodes: &[String],
 	genesis_hash: &str,
 	retry_config: RetryConfig,
-) -> Result<(Client, broadcast::Sender<Event>, SubscriptionLoop<T>)> {
-	let rpc_client =
-		Client::new(state.clone(), Nodes::new(nodes), genesis_hash, retry_config).await?;
+	health_config: RpcHealthConfig,
+	header_quorum: Option<usize>,
+	justification_sender: broadcast::Sender<(u32, GrandpaJustification)>,
+	alert_sender: AlertSender,
+) -> Result<(Client, broadcast::Sender<Event>, SubscriptionLoop<T, M>)> {
+	let rpc_client = Client::new(
+		state.clone(),
+		Nodes::new(nodes),
+		genesis_hash,
+		retry_config,
+		health_config.max_head_lag,
+		alert_sender.clone(),
+	)
+	.await?;
 	// create output channel for RPC Subscription Events
 	let (event_sender, _) = broadcast::channel(1000);
-	let subscriptions =
-		SubscriptionLoop::new(state, db, rpc_client.clone(), event_sender.clone()).await?;
+	let subscriptions = SubscriptionLoop::new(
+		state,
+		db,
+		metrics,
+		rpc_client.clone(),
+		event_sender.clone(),
+		justification_sender,
+		alert_sender,
+		header_quorum,
+	)
+	.await?;
 
 	Ok((rpc_client, event_sender, subscriptions))
 }
 
-/// Generates random cell positions for sampling
-pub fn generate_random_cells(dimensions: Dimensions, cell_count: u32) -> Vec<Position> {
+/// Periodically probes health of the configured RPC nodes, and records it into the metrics.
+pub async fn run_health_probes(client: Client, metrics: Arc<impl Metrics>, cfg: RpcHealthConfig) {
+	info!("Starting RPC node health probes...");
+
+	let mut interval = time::interval(cfg.probe_interval);
+	loop {
+		interval.tick().await;
+		client.probe_nodes().await;
+
+		for node in client.nodes_health() {
+			let host = node.host;
+			let values = [
+				MetricValue::RPCNodeErrorRate(host.clone(), node.error_rate),
+				MetricValue::RPCNodeHeadLag(host.clone(), node.head_lag),
+			]
+			.into_iter()
+			.chain(
+				node.latency
+					.map(|latency| MetricValue::RPCNodeLatency(host.clone(), latency)),
+			);
+			for value in values {
+				if let Err(error) = metrics.record(value).await {
+					warn!(host, "Failed to record node health: {error:#}");
+				}
+			}
+		}
+	}
+}
+
+/// Generates random cell positions for sampling.
+/// Positions are generated deterministically from the seed, so the same seed yields the same positions.
+pub fn generate_random_cells(
+	dimensions: Dimensions,
+	cell_count: u32,
+	seed: [u8; 32],
+) -> Vec<Position> {
 	let max_cells = dimensions.extended_size();
 	let count = if max_cells < cell_count {
 		debug!("Max cells count {max_cells} is lesser than cell_count {cell_count}");
@@ -227,15 +429,19 @@ pub fn generate_random_cells(dimensions: Dimensions, cell_count: u32) -> Vec<Pos
 	} else {
 		cell_count
 	};
-	let mut rng = thread_rng();
+	let mut rng = ChaChaRng::from_seed(seed);
 	let mut indices = HashSet::new();
-	while (indices.len() as u16) < count as u16 {
+	let mut positions = Vec::with_capacity(count as usize);
+	while (positions.len() as u16) < count as u16 {
 		let col = rng.gen_range(0..dimensions.cols().into());
 		let row = rng.gen_range(0..dimensions.extended_rows());
-		indices.insert(Position { row, col });
+		let position = Position { row, col };
+		if indices.insert(position) {
+			positions.push(position);
+		}
 	}
 
-	indices.into_iter().collect::<Vec<_>>()
+	positions
 }
 
 /* @note: fn to take the number of cells needs to get equal to or greater than
@@ -283,3 +489,52 @@ pub async fn wait_for_finalized_header(
 		Err(_) => Err(eyre!("Timeout on waiting for first finalized header")),
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::{Nodes, NodesHealth};
+	use std::time::Duration;
+
+	fn hosts(nodes: Vec<super::Node>) -> Vec<String> {
+		nodes.into_iter().map(|node| node.host).collect()
+	}
+
+	#[test]
+	fn nodes_are_ranked_by_health() {
+		let nodes = Nodes::new(&["a".to_string(), "b".to_string(), "c".to_string()]);
+		let mut health = NodesHealth::new(&nodes, 2);
+		health.record_success("a", Duration::from_millis(300));
+		health.record_success("b", Duration::from_millis(100));
+		health.record_success("c", Duration::from_millis(200));
+		assert_eq!(hosts(nodes.ranked(&health, "")), vec!["b", "c", "a"]);
+		assert_eq!(hosts(nodes.ranked(&health, "b")), vec!["c", "a"]);
+
+		// node lagging behind the best finalized head is unhealthy
+		health.record_head("a", 10);
+		health.record_head("b", 7);
+		assert!(!health.is_healthy("b"));
+		assert_eq!(health.nodes[1].head_lag, 3);
+		assert_eq!(hosts(nodes.ranked(&health, "")), vec!["c", "a", "b"]);
+		assert!(health.should_reroute("b"));
+		assert!(!health.should_reroute("a"));
+	}
+
+	#[test]
+	fn failing_node_is_unhealthy() {
+		let nodes = Nodes::new(&["a".to_string(), "b".to_string()]);
+		let mut health = NodesHealth::new(&nodes, 2);
+		health.record_failure("a");
+		assert!(health.is_healthy("a"));
+		health.record_failure("a");
+		assert!(!health.is_healthy("a"));
+		assert!(health.should_reroute("a"));
+
+		// switch is not attempted again during the cooldown
+		health.record_reroute();
+		assert!(!health.should_reroute("a"));
+
+		// successful requests restore the node health
+		health.record_success("a", Duration::from_millis(100));
+		assert!(health.is_healthy("a"));
+	}
+}
@@ -13,7 +13,14 @@ use sp_core::{
 	bytes::from_hex,
 	ed25519::{self, Public},
 };
-use std::sync::{Arc, Mutex};
+use std::{
+	collections::HashMap,
+	sync::{
+		atomic::{AtomicU32, Ordering},
+		Arc, Mutex,
+	},
+	time::{Duration, Instant},
+};
 use subxt::{
 	rpc::{types::BlockNumber, RpcParams},
 	rpc_params,
@@ -21,13 +28,19 @@ use subxt::{
 	tx::{PairSigner, SubmittableExtrinsic},
 	utils::AccountId32,
 };
-use tokio::sync::RwLock;
+use tokio::{
+	sync::{Mutex as AsyncMutex, RwLock},
+	time::timeout,
+};
 use tokio_retry::Retry;
 use tokio_stream::StreamExt;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
-use super::{Node, Nodes, Subscription, WrappedProof, CELL_WITH_PROOF_SIZE};
+use super::{
+	Node, NodeHealth, Nodes, NodesHealth, Subscription, WrappedProof, CELL_WITH_PROOF_SIZE,
+};
 use crate::{
+	alerts::{Alert, AlertSender},
 	consts::ExpectedNodeVariant,
 	types::{RetryConfig, RuntimeVersion, State, DEV_FLAG_GENHASH},
 };
@@ -39,20 +52,39 @@ pub struct Client {
 	nodes: Nodes,
 	retry_config: RetryConfig,
 	expected_genesis_hash: String,
+	alert_sender: AlertSender,
+	/// Number of consecutive failovers to another node
+	failovers: Arc<AtomicU32>,
+	/// Held while switching to the healthiest node, so only one switch is done at a time
+	routing: Arc<AsyncMutex<()>>,
+	/// Connections to the quorum nodes, reused across the block hash requests
+	quorum_clients: Arc<AsyncMutex<HashMap<String, avail::Client>>>,
 }
 
+/// Number of consecutive failovers after which the alert is raised
+const REPEATED_FAILOVERS: u32 = 2;
+
+/// Maximum duration of the node health probe, including the connection
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum duration of the block hash request to the quorum node, including the connection
+const QUORUM_TIMEOUT: Duration = Duration::from_secs(10);
+
 impl Client {
 	pub async fn new(
 		state: Arc<Mutex<State>>,
 		nodes: Nodes,
 		expected_genesis_hash: &str,
 		retry_config: RetryConfig,
+		max_head_lag: u32,
+		alert_sender: AlertSender,
 	) -> Result<Self> {
+		let health = NodesHealth::new(&nodes, max_head_lag);
 		// try and connect appropriate Node from the provided list
 		// will do retries with the provided Retry Config
 		let (client, node, _) = Retry::spawn(retry_config.clone(), || async {
 			Self::try_connect_and_execute(
-				nodes.shuffle(Default::default()),
+				nodes.ranked(&health, ""),
 				ExpectedNodeVariant::new(),
 				expected_genesis_hash,
 				|_| futures::future::ok(()),
@@ -62,7 +94,11 @@ impl Client {
 		.await?;
 
 		// update application wide State with the newly connected Node
-		state.lock().unwrap().connected_node = node;
+		{
+			let mut state = state.lock().unwrap();
+			state.connected_node = node;
+			state.rpc_nodes = health;
+		}
 
 		Ok(Self {
 			subxt_client: Arc::new(RwLock::new(client)),
@@ -70,6 +106,10 @@ impl Client {
 			nodes,
 			retry_config,
 			expected_genesis_hash: expected_genesis_hash.to_string(),
+			alert_sender,
+			failovers: Arc::new(AtomicU32::new(0)),
+			routing: Arc::new(AsyncMutex::new(())),
+			quorum_clients: Arc::new(AsyncMutex::new(HashMap::new())),
 		})
 	}
 
@@ -163,23 +203,218 @@ impl Client {
 		F: FnMut(avail::Client) -> Fut + Copy,
 		Fut: std::future::Future<Output = Result<T, subxt::error::Error>>,
 	{
+		self.route().await;
 		// try and execute the passed function, use the Retry strategy if needed
 		if let Ok(result) = Retry::spawn(self.retry_config.clone(), move || async move {
-			f(self.current_client().await).await
+			let started = Instant::now();
+			let result = f(self.current_client().await).await;
+			self.record_request(started.elapsed(), result.is_ok());
+			result
 		})
 		.await
 		{
 			// this was successful, return early
+			self.failovers.store(0, Ordering::Relaxed);
 			return Ok(result);
 		}
 		// if not, find another Node where this could still be done
-		let connected_node = self.state.lock().unwrap().connected_node.clone();
+		let host = self.state.lock().unwrap().connected_node.host.clone();
 		warn!(
-			"Executing RPC call with host: {} failed. Trying to create a new RPC connection.",
-			connected_node.host
+			"Executing RPC call with host: {host} failed. Trying to create a new RPC connection."
 		);
-		// shuffle nodes, if possible
-		let nodes = self.nodes.shuffle(connected_node.host);
+		self.failover(f).await
+	}
+
+	/// Records latency and result of the request executed on the connected node.
+	fn record_request(&self, latency: Duration, is_ok: bool) {
+		let mut state = self.state.lock().unwrap();
+		let host = state.connected_node.host.clone();
+		if is_ok {
+			state.rpc_nodes.record_success(&host, latency);
+		} else {
+			state.rpc_nodes.record_failure(&host);
+		}
+	}
+
+	/// Switches connection to the healthiest node, if the connected node is unhealthy and a healthy node is available.
+	/// Concurrent requests keep using the connected node while the switch is in progress,
+	/// and switch is attempted at most once per cooldown.
+	async fn route(&self) {
+		let Ok(_routing) = self.routing.try_lock() else {
+			return;
+		};
+
+		let nodes = {
+			let mut state = self.state.lock().unwrap();
+			let current_host = state.connected_node.host.clone();
+			if !state.rpc_nodes.should_reroute(&current_host) {
+				return;
+			}
+			state.rpc_nodes.record_reroute();
+			self.nodes
+				.ranked(&state.rpc_nodes, &current_host)
+				.into_iter()
+				.filter(|node| state.rpc_nodes.is_healthy(&node.host))
+				.collect::<Vec<_>>()
+		};
+
+		match Self::try_connect_and_execute(
+			nodes,
+			ExpectedNodeVariant::new(),
+			&self.expected_genesis_hash,
+			|_| futures::future::ok(()),
+		)
+		.await
+		{
+			Ok((client, node, _)) => {
+				info!(
+					host = node.host,
+					"Routing RPC requests to the healthiest node"
+				);
+				*self.subxt_client.write().await = client;
+				self.state.lock().unwrap().connected_node = node;
+			},
+			Err(error) => warn!("Failed to switch to the healthiest node: {error:#}"),
+		}
+	}
+
+	/// Returns health of all configured nodes
+	pub fn nodes_health(&self) -> Vec<NodeHealth> {
+		self.state.lock().unwrap().rpc_nodes.nodes.clone()
+	}
+
+	/// Probes health of all configured nodes, measuring request latency and finalized head of each node.
+	/// Connected node is probed with the current client, other nodes with a new connection.
+	pub async fn probe_nodes(&self) {
+		let connected_host = self.state.lock().unwrap().connected_node.host.clone();
+		for Node { host, .. } in self.nodes.iter() {
+			let probe = async {
+				let client = if *host == connected_host {
+					self.current_client().await
+				} else {
+					let expected_node = ExpectedNodeVariant::new();
+					Self::create_subxt_client(host, expected_node, &self.expected_genesis_hash)
+						.await?
+						.0
+				};
+				Self::probe(client).await
+			};
+
+			let result = timeout(PROBE_TIMEOUT, probe)
+				.await
+				.unwrap_or_else(|_| Err(eyre!("Probe timed out")));
+
+			let mut state = self.state.lock().unwrap();
+			match result {
+				Ok((finalized_head, latency)) => {
+					state.rpc_nodes.record_success(host, latency);
+					state.rpc_nodes.record_head(host, finalized_head);
+				},
+				Err(error) => {
+					debug!(host, "Node health probe failed: {error:#}");
+					state.rpc_nodes.record_failure(host);
+				},
+			}
+		}
+	}
+
+	/// Returns finalized head of the node, and latency of the finalized head request
+	async fn probe(client: avail::Client) -> Result<(u32, Duration)> {
+		let started = Instant::now();
+		let hash = client.rpc().finalized_head().await?;
+		let latency = started.elapsed();
+		let header = client
+			.rpc()
+			.header(Some(hash))
+			.await?
+			.ok_or_else(|| eyre!("Finalized header {hash:?} not found"))?;
+		Ok((header.number, latency))
+	}
+
+	/// Returns connection to the quorum node, connecting to it if there is no open connection.
+	async fn quorum_client(&self, host: &str) -> Result<avail::Client> {
+		if let Some(client) = self.quorum_clients.lock().await.get(host) {
+			return Ok(client.clone());
+		}
+		let expected_node = ExpectedNodeVariant::new();
+		let (client, _) =
+			Self::create_subxt_client(host, expected_node, &self.expected_genesis_hash).await?;
+		self.quorum_clients
+			.lock()
+			.await
+			.insert(host.to_string(), client.clone());
+		Ok(client)
+	}
+
+	/// Fetches hash of the block from the given number of nodes other than the connected one, healthiest first.
+	/// Nodes which fail to respond are skipped, and error is returned if not enough nodes responded.
+	/// Connections to the nodes are reused, and dropped once the node fails to respond.
+	pub async fn get_block_hashes(
+		&self,
+		block_number: u32,
+		count: usize,
+	) -> Result<Vec<(String, H256)>> {
+		let nodes = {
+			let state = self.state.lock().unwrap();
+			let connected_host = &state.connected_node.host;
+			self.nodes
+				.ranked(&state.rpc_nodes, connected_host)
+				.into_iter()
+				.filter(|node| node.host != *connected_host)
+				.collect::<Vec<_>>()
+		};
+
+		let mut hashes = vec![];
+		for Node { host, .. } in nodes {
+			if hashes.len() >= count {
+				break;
+			}
+			let request = async {
+				self.quorum_client(&host)
+					.await?
+					.rpc()
+					.block_hash(Some(BlockNumber::from(block_number)))
+					.await?
+					.ok_or_else(|| eyre!("Block with number: {block_number} not found"))
+			};
+
+			match timeout(QUORUM_TIMEOUT, request)
+				.await
+				.unwrap_or_else(|_| Err(eyre!("Request timed out")))
+			{
+				Ok(hash) => hashes.push((host, hash)),
+				Err(error) => {
+					warn!(
+						host,
+						"Failed to fetch block hash from the quorum node: {error:#}"
+					);
+					self.quorum_clients.lock().await.remove(&host);
+					self.state.lock().unwrap().rpc_nodes.record_failure(&host);
+				},
+			}
+		}
+
+		if hashes.len() < count {
+			return Err(eyre!(
+				"Only {} of {count} nodes returned hash of block {block_number}",
+				hashes.len()
+			));
+		}
+		Ok(hashes)
+	}
+
+	/// Connects to another node and executes the passed function call on it.
+	async fn failover<F, Fut, T>(&self, mut f: F) -> Result<T>
+	where
+		F: FnMut(avail::Client) -> Fut + Copy,
+		Fut: std::future::Future<Output = Result<T, subxt::error::Error>>,
+	{
+		let connected_node = self.state.lock().unwrap().connected_node.clone();
+		// rank nodes by health, if possible
+		let nodes = {
+			let state = self.state.lock().unwrap();
+			self.nodes.ranked(&state.rpc_nodes, &connected_node.host)
+		};
 		// go through available Nodes, try to connect, Retry connecting if needed
 		let (client, node, result) = Retry::spawn(self.retry_config.clone(), move || {
 			let nodes = nodes.clone();
@@ -199,9 +434,22 @@ impl Client {
 		*self.subxt_client.write().await = client;
 		self.state.lock().unwrap().connected_node = node;
 
+		let failovers = self.failovers.fetch_add(1, Ordering::Relaxed) + 1;
+		if failovers >= REPEATED_FAILOVERS {
+			let host = connected_node.host;
+			let _ = self
+				.alert_sender
+				.send(Alert::RpcFailover { host, failovers });
+		}
+
 		Ok(result)
 	}
 
+	/// Switches connection to another node, e.g. after the connected node sent invalid data.
+	pub async fn switch_node(&self) -> Result<()> {
+		self.failover(|_| futures::future::ok(())).await
+	}
+
 	async fn create_subxt_subscriptions(
 		client: avail::Client,
 	) -> Result<impl Stream<Item = Result<Subscription, subxt::error::Error>>, subxt::error::Error>
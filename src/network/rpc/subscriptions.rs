@@ -1,25 +1,30 @@
-use avail_subxt::primitives::{grandpa::AuthorityId, Header};
+use async_trait::async_trait;
+use avail_subxt::{primitives::Header, utils::H256};
 use codec::Encode;
-use color_eyre::{eyre::eyre, Result};
-use sp_core::{
-	blake2_256,
-	ed25519::{self, Public},
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Report, Result,
 };
+use futures::stream::BoxStream;
+use mockall::automock;
+use sp_core::{blake2_256, ed25519::Public};
 use std::{
 	sync::{Arc, Mutex},
 	time::Instant,
 };
 use tokio::sync::broadcast::Sender;
 use tokio_stream::StreamExt;
-use tracing::{debug, info, trace};
+use tracing::{debug, error, info, trace, warn};
 
 use super::{Client, Subscription};
 use crate::{
+	alerts::{Alert, AlertSender},
 	data::Database,
 	data::{FinalitySyncCheckpoint, Key},
-	finality::{check_finality, ValidatorSet},
+	finality::{check_finality, AuthoritySet, Equivocation, FinalityError, ValidatorSet},
+	shutdown::Controller,
+	telemetry::{MetricCounter, Metrics},
 	types::{GrandpaJustification, OptionBlockRange, State},
-	utils::filter_auth_set_changes,
 };
 
 #[derive(Clone, Debug)]
@@ -30,28 +35,124 @@ pub enum Event {
 	},
 }
 
+/// RPC calls used by the subscription loop
+#[async_trait]
+#[automock]
+pub trait SubscriptionClient {
+	async fn get_finalized_head_hash(&self) -> Result<H256>;
+	async fn get_validator_set_by_hash(&self, block_hash: H256) -> Result<Vec<Public>>;
+	async fn fetch_set_id_at(&self, block_hash: H256) -> Result<u64>;
+	async fn get_header_by_hash(&self, block_hash: H256) -> Result<Header>;
+	async fn get_header_by_block_number(&self, block_number: u32) -> Result<(Header, H256)>;
+	async fn get_block_hashes(
+		&self,
+		block_number: u32,
+		count: usize,
+	) -> Result<Vec<(String, H256)>>;
+	async fn subscription_stream(&self) -> BoxStream<'static, Result<Subscription>>;
+	async fn switch_node(&self) -> Result<()>;
+}
+
+#[async_trait]
+impl SubscriptionClient for Client {
+	async fn get_finalized_head_hash(&self) -> Result<H256> {
+		self.get_finalized_head_hash().await
+	}
+
+	async fn get_validator_set_by_hash(&self, block_hash: H256) -> Result<Vec<Public>> {
+		self.get_validator_set_by_hash(block_hash).await
+	}
+
+	async fn fetch_set_id_at(&self, block_hash: H256) -> Result<u64> {
+		self.fetch_set_id_at(block_hash).await
+	}
+
+	async fn get_header_by_hash(&self, block_hash: H256) -> Result<Header> {
+		self.get_header_by_hash(block_hash).await
+	}
+
+	async fn get_header_by_block_number(&self, block_number: u32) -> Result<(Header, H256)> {
+		self.get_header_by_block_number(block_number).await
+	}
+
+	async fn get_block_hashes(
+		&self,
+		block_number: u32,
+		count: usize,
+	) -> Result<Vec<(String, H256)>> {
+		self.get_block_hashes(block_number, count).await
+	}
+
+	async fn subscription_stream(&self) -> BoxStream<'static, Result<Subscription>> {
+		Box::pin(self.clone().subscription_stream().await)
+	}
+
+	async fn switch_node(&self) -> Result<()> {
+		self.switch_node().await
+	}
+}
+
+/// Subscription loop failure, classified by the way loop recovers from it
+#[derive(Debug)]
+enum Failure {
+	/// Failed step is repeated with the next subscription item (RPC calls are already retried by the client)
+	Retry(Report),
+	/// Connected node sent invalid data, loop continues with the subscriptions from another node
+	SwitchNode(Report),
+	/// Loop cannot continue, shutdown is triggered
+	Fatal(Report),
+}
+
 struct BlockData {
 	justifications: Vec<GrandpaJustification>,
-	unverified_headers: Vec<(Header, Instant, ValidatorSet)>,
-	current_valset: ValidatorSet,
-	next_valset: Option<ValidatorSet>,
+	/// Headers waiting for justification, with the validator set which finalizes them,
+	/// and whether finality sync can resume from them (no earlier signaled changes are pending)
+	unverified_headers: Vec<(Header, Instant, ValidatorSet, bool)>,
+	authority_set: AuthoritySet,
 	last_finalized_block_header: Option<Header>,
+	/// Number of the last header checked for authority set changes
+	last_tracked_header: Option<u32>,
 }
 
-pub struct SubscriptionLoop<T: Database> {
-	rpc_client: Client,
+/// Checks that the header is a child of the previous header in the chain
+fn verify_parent_hash(previous: &Header, header: &Header) -> Result<()> {
+	let previous_hash: H256 = Encode::using_encoded(previous, blake2_256).into();
+	if header.parent_hash == previous_hash {
+		return Ok(());
+	}
+	Err(eyre!(
+		"Header {} with parent hash {:?} doesn't link to the previous header {} with hash {:?}",
+		header.number,
+		header.parent_hash,
+		previous.number,
+		previous_hash
+	))
+}
+
+pub struct SubscriptionLoop<T: Database, M: Metrics, C: SubscriptionClient = Client> {
+	rpc_client: C,
 	event_sender: Sender<Event>,
+	/// Channel used to publish verified justifications with their block numbers
+	justification_sender: Sender<(u32, GrandpaJustification)>,
 	state: Arc<Mutex<State>>,
 	db: T,
+	metrics: Arc<M>,
+	alert_sender: AlertSender,
+	/// Number of nodes which have to report the same hash of the skipped header, if quorum mode is enabled
+	header_quorum: Option<usize>,
 	block_data: BlockData,
 }
 
-impl<T: Database> SubscriptionLoop<T> {
+impl<T: Database, M: Metrics, C: SubscriptionClient> SubscriptionLoop<T, M, C> {
 	pub async fn new(
 		state: Arc<Mutex<State>>,
 		db: T,
-		rpc_client: Client,
+		metrics: Arc<M>,
+		rpc_client: C,
 		event_sender: Sender<Event>,
+		justification_sender: Sender<(u32, GrandpaJustification)>,
+		alert_sender: AlertSender,
+		header_quorum: Option<usize>,
 	) -> Result<Self> {
 		// get the Hash of the Finalized Head [with Retries]
 		let last_finalized_block_hash = rpc_client.get_finalized_head_hash().await?;
@@ -74,79 +175,99 @@ impl<T: Database> SubscriptionLoop<T> {
 		Ok(Self {
 			rpc_client,
 			event_sender,
+			justification_sender,
 			state,
 			db,
+			metrics,
+			alert_sender,
+			header_quorum,
 			block_data: BlockData {
 				justifications: Default::default(),
 				unverified_headers: Default::default(),
-				current_valset: ValidatorSet {
+				authority_set: AuthoritySet::new(ValidatorSet {
 					set_id,
 					validator_set,
-				},
-				next_valset: None,
+				}),
+				last_tracked_header: Some(last_finalized_block_header.number),
 				last_finalized_block_header: Some(last_finalized_block_header),
 			},
 		})
 	}
 
-	pub async fn run(mut self) -> Result<()> {
-		// create subscriptions stream
-		let subscriptions = self.rpc_client.clone().subscription_stream().await;
-		futures::pin_mut!(subscriptions);
-
-		while let Some(result) = subscriptions.next().await {
-			match result {
-				Ok(sub) => {
-					self.handle_new_subscription(sub).await;
-				},
-				Err(err) => return Err(eyre!(err)),
-			};
+	/// Runs subscription loop, triggering shutdown with the reason if loop fails.
+	pub async fn run(mut self, shutdown: Controller<String>) -> Result<()> {
+		let result = self.run_subscriptions().await;
+		if let Err(error) = &result {
+			let _ = shutdown.trigger_shutdown(format!("Subscription loop failed: {error:#}"));
 		}
+		result
+	}
 
-		Ok(())
+	async fn run_subscriptions(&mut self) -> Result<()> {
+		'subscriptions: loop {
+			// create subscriptions stream
+			let mut subscriptions = self.rpc_client.subscription_stream().await;
+
+			while let Some(result) = subscriptions.next().await {
+				let result = match result {
+					Ok(sub) => self.handle_new_subscription(sub).await,
+					// subscriptions are not created after all nodes were retried
+					Err(error) => Err(Failure::Fatal(error)),
+				};
+
+				match result {
+					Ok(()) => {},
+					Err(Failure::Retry(error)) => {
+						warn!("Subscription item failed, retrying with the next one: {error:#}");
+						self.metrics.count(MetricCounter::SubscriptionRetry).await;
+					},
+					Err(Failure::SwitchNode(error)) => {
+						warn!("Subscription item failed, switching node: {error:#}");
+						self.metrics
+							.count(MetricCounter::SubscriptionNodeSwitch)
+							.await;
+						self.rpc_client
+							.switch_node()
+							.await
+							.wrap_err("Failed to switch node")?;
+						continue 'subscriptions;
+					},
+					Err(Failure::Fatal(error)) => {
+						error!("Subscription item failed: {error:#}");
+						self.metrics.count(MetricCounter::SubscriptionFatal).await;
+						return Err(error);
+					},
+				}
+			}
+
+			return Ok(());
+		}
 	}
 
-	async fn handle_new_subscription(&mut self, subscription: Subscription) {
+	async fn handle_new_subscription(&mut self, subscription: Subscription) -> Result<(), Failure> {
 		match subscription {
 			Subscription::Header(header) => {
 				let received_at = Instant::now();
 				self.state.lock().unwrap().latest = header.clone().number;
 				info!("Header no.: {}", header.number);
 
-				// if new validator set becomes active, replace the current one
-				if self.block_data.next_valset.is_some() {
-					self.block_data.current_valset = self.block_data.next_valset.take().unwrap();
-				}
-
-				// push new Unverified Header
-				self.block_data.unverified_headers.push((
-					header.clone(),
-					received_at,
-					self.block_data.current_valset.clone(),
-				));
-
-				// search the header logs for validator set change
-				let mut new_auths = filter_auth_set_changes(&header);
-				// if the event exists, send the new auths over the message channel.
-				if !new_auths.is_empty() {
-					// TODO: Handle this in a proper fashion
-					assert!(
-						new_auths.len() == 1,
-						"There should be only one valset change!"
-					);
-					let auths: Vec<(AuthorityId, u64)> = new_auths.pop().unwrap();
-					let new_valset = auths
-						.into_iter()
-						.map(|(a, _)| ed25519::Public::from_raw(a.0 .0 .0))
-						.collect::<Vec<Public>>();
-
-					self.block_data.next_valset = Some(ValidatorSet {
-						set_id: self.block_data.current_valset.set_id + 1,
-						validator_set: new_valset,
-					});
-
-					debug!("Validator set change: {:?}", self.block_data.next_valset);
+				// authority set changes can be signaled in the blocks skipped by the subscription
+				if let Some(last_tracked) = self.block_data.last_tracked_header {
+					for block_number in (last_tracked + 1)..header.number {
+						debug!("Fetching skipped header {block_number} from RPC");
+						// last tracked header is not updated, so failed header is fetched again with the next one
+						let (skipped, _) = self
+							.rpc_client
+							.get_header_by_block_number(block_number)
+							.await
+							.map_err(Failure::Retry)?;
+						// authority set changes are signaled in the headers sent by the node
+						self.track_header(skipped, Instant::now())
+							.map_err(Failure::SwitchNode)?;
+					}
 				}
+				self.track_header(header, received_at)
+					.map_err(Failure::SwitchNode)?;
 			},
 			Subscription::Justification(justification) => {
 				info!(
@@ -157,104 +278,790 @@ impl<T: Database> SubscriptionLoop<T> {
 			},
 		}
 		// check headers
-		self.verify_and_output_block_headers().await;
+		self.verify_and_output_block_headers().await
 	}
 
-	async fn verify_and_output_block_headers(&mut self) {
-		let mut finality_synced = false;
-		while let Some(justification) = self.block_data.justifications.pop() {
-			// iterate through Headers and try to find a matching one
-			if let Some(pos) = self
+	/// Queues header for finality verification, together with the validator set which finalizes it.
+	/// Scheduled authority set changes are enacted once the header after their activation block is received.
+	/// Header with invalid authority set changes is rejected without changing the tracked authority set.
+	fn track_header(&mut self, header: Header, received_at: Instant) -> Result<()> {
+		let block_data = &mut self.block_data;
+
+		// changes are applied to the copy, which replaces the tracked authority set once header is validated
+		let mut authority_set = block_data.authority_set.clone();
+		authority_set.enact_changes(header.number);
+		let validator_set = authority_set.current.clone();
+		let is_checkpoint = !authority_set.has_pending_changes();
+
+		// search the header logs for validator set changes
+		authority_set
+			.schedule_changes(&header)
+			.wrap_err("Invalid authority set changes")?;
+
+		block_data.authority_set = authority_set;
+		block_data.last_tracked_header = block_data.last_tracked_header.max(Some(header.number));
+		block_data
+			.unverified_headers
+			.push((header, received_at, validator_set, is_checkpoint));
+		Ok(())
+	}
+
+	/// Checks that the header links to the previously emitted header, if any.
+	/// Non-linking header is counted and returned as an error, since emitted headers have to form a continuous chain.
+	async fn verify_chain(&self, previous: Option<&Header>, header: &Header) -> Result<()> {
+		let Some(previous) = previous else {
+			return Ok(());
+		};
+		if let Err(error) = verify_parent_hash(previous, header) {
+			error!("Node sent non-linking header: {error}");
+			self.metrics.count(MetricCounter::NonLinkingHeader).await;
+			return Err(error);
+		}
+		Ok(())
+	}
+
+	fn unverified_header_position(&self, hash: H256) -> Option<usize> {
+		self.block_data
+			.unverified_headers
+			.iter()
+			.map(|(h, ..)| Encode::using_encoded(h, blake2_256).into())
+			.position(|header_hash: H256| header_hash == hash)
+	}
+
+	/// Sends header as output event, and sets it as the last finalized header
+	fn send_header(&mut self, header: Header, received_at: Instant) {
+		let block_number = header.number;
+		self.block_data.last_finalized_block_header = Some(header.clone());
+		let event = Event::HeaderUpdate {
+			header,
+			received_at,
+		};
+		// there are no receivers only during startup or shutdown, so header can be dropped
+		if self.event_sender.send(event).is_err() {
+			warn!(block_number, "No receivers for the verified header");
+		}
+	}
+
+	/// Verifies that the other quorum nodes report the same hash of the skipped header as the connected node.
+	/// Divergence is raised as a security event, and the header is not emitted until the nodes agree.
+	/// Unreachable quorum nodes are raised separately, since they stall the finality without diverging.
+	async fn verify_quorum(&self, header: &Header, quorum: usize) -> Result<(), Failure> {
+		let block_number = header.number;
+		let hash: H256 = Encode::using_encoded(header, blake2_256).into();
+		// connected node is part of the quorum
+		let hashes = match self
+			.rpc_client
+			.get_block_hashes(block_number, quorum.saturating_sub(1))
+			.await
+		{
+			Ok(hashes) => hashes,
+			Err(error) => {
+				error!(
+					block_number,
+					"RPC nodes are unavailable for the header quorum: {error:#}"
+				);
+				self.metrics
+					.count(MetricCounter::HeaderQuorumUnavailable)
+					.await;
+				let alert = Alert::HeaderQuorumUnavailable {
+					block_number,
+					error: format!("{error:#}"),
+				};
+				let _ = self.alert_sender.send(alert);
+				return Err(Failure::Retry(error));
+			},
+		};
+
+		if hashes.iter().all(|(_, node_hash)| *node_hash == hash) {
+			return Ok(());
+		}
+
+		error!(block_number, "RPC nodes diverge on the skipped header hash");
+		self.metrics.count(MetricCounter::HeaderQuorumFailed).await;
+		let alert = Alert::HeaderQuorumFailed {
+			block_number,
+			hash: format!("{hash:?}"),
+			hashes: hashes
+				.into_iter()
+				.map(|(host, hash)| (host, format!("{hash:?}")))
+				.collect(),
+		};
+		let _ = self.alert_sender.send(alert);
+		Err(Failure::Retry(eyre!(
+			"RPC nodes diverge on the hash of the skipped block {block_number}"
+		)))
+	}
+
+	/// Collects headers skipped between the last finalized header and the justified header,
+	/// and verifies that they link the last finalized header to the justified header.
+	/// Skipped headers are taken from the unverified headers, or fetched from RPC.
+	/// In quorum mode, header hashes are cross-checked with the other configured nodes.
+	/// Headers are neither sent nor removed from the unverified headers, so verification can be repeated on failure.
+	async fn verify_skipped_headers(
+		&self,
+		header: &Header,
+	) -> Result<Vec<(Header, Instant)>, Failure> {
+		let Some(last_finalized) = self.block_data.last_finalized_block_header.as_ref() else {
+			return Ok(vec![]);
+		};
+
+		let mut skipped = vec![];
+		for bl_num in (last_finalized.number + 1)..header.number {
+			let unverified = self
 				.block_data
 				.unverified_headers
 				.iter()
-				.map(|(h, _, _)| Encode::using_encoded(h, blake2_256).into())
-				.position(|hash| justification.commit.target_hash == hash)
-			{
-				// basically, pop it out of the collection
-				let (header, received_at, valset) =
-					self.block_data.unverified_headers.swap_remove(pos);
+				.find(|(h, ..)| h.number == bl_num);
+			match unverified {
+				Some((unverified, received_at, ..)) => {
+					info!("Fetching skipped header {bl_num} from unverified headers");
+					skipped.push((unverified.clone(), *received_at));
+				},
+				None => {
+					info!("Fetching skipped header {bl_num} from RPC");
+					let (fetched, _) = self
+						.rpc_client
+						.get_header_by_block_number(bl_num)
+						.await
+						.map_err(Failure::Retry)?;
+					skipped.push((fetched, Instant::now()));
+				},
+			};
+		}
 
-				let is_final = check_finality(&valset, &justification);
+		// emitted headers are linked to the last finalized header through their parent hashes,
+		// up to the justified header
+		let mut previous = last_finalized;
+		for (skipped_header, _) in &skipped {
+			self.verify_chain(Some(previous), skipped_header)
+				.await
+				.map_err(Failure::SwitchNode)?;
+			previous = skipped_header;
+		}
+		self.verify_chain(Some(previous), header)
+			.await
+			.map_err(Failure::SwitchNode)?;
 
-				is_final.expect("Finality check failed");
+		if let Some(quorum) = self.header_quorum {
+			for (skipped_header, _) in &skipped {
+				self.verify_quorum(skipped_header, quorum).await?;
+			}
+		}
+		Ok(skipped)
+	}
 
-				// To avoid locking the global state all the time, after finality is synced, it will not be necessary to read the state
-				if !finality_synced {
-					finality_synced = self.state.lock().unwrap().finality_synced;
-				}
-				// store Finality Checkpoint if finality is synced
-				if finality_synced {
-					info!("Storing finality checkpoint at block {}", header.number);
-					self.db
-						.put(
-							Key::FinalitySyncCheckpoint,
-							FinalitySyncCheckpoint {
-								set_id: self.block_data.current_valset.set_id,
-								number: header.number,
-								validator_set: self.block_data.current_valset.validator_set.clone(),
-							},
-						)
-						.unwrap();
-				}
+	/// Sends verified skipped headers, removing them from the unverified headers
+	fn send_skipped_headers(&mut self, skipped: Vec<(Header, Instant)>) {
+		for (header, received_at) in skipped {
+			info!("Sending skipped block {}", header.number);
+			let unverified_headers = &mut self.block_data.unverified_headers;
+			if let Some(pos) = unverified_headers
+				.iter()
+				.position(|(h, ..)| h.number == header.number)
+			{
+				unverified_headers.swap_remove(pos);
+			}
+			self.send_header(header, received_at);
+		}
+	}
 
-				// try and get get all the skipped blocks, if they exist
-				if let Some(last_header) = self.block_data.last_finalized_block_header.as_ref() {
-					for bl_num in (last_header.number + 1)..header.number {
-						info!("Sending skipped block {bl_num}");
-						let (header, received_at) = match self
-							.block_data
-							.unverified_headers
-							.iter()
-							.position(|(h, _, _)| h.number == bl_num)
-						{
-							Some(pos) => {
-								info!("Fetching header from unverified headers");
-								let p = self.block_data.unverified_headers.swap_remove(pos);
-								(p.0, p.1)
-							},
-							None => {
-								info!("Fetching header from RPC");
-								let a = self
-									.rpc_client
-									.get_header_by_block_number(bl_num)
-									.await
-									.unwrap()
-									.0;
-								(a, Instant::now())
-							},
-						};
-						// send as output event
-						self.event_sender
-							.send(Event::HeaderUpdate {
-								header,
-								received_at,
-							})
-							.unwrap();
-					}
-				}
+	fn store_justification(
+		&self,
+		block_number: u32,
+		justification: &GrandpaJustification,
+	) -> Result<()> {
+		self.db
+			.put(Key::Justification(block_number), justification)
+			.wrap_err("Failed to store justification")
+	}
 
-				info!("Sending finalized block {}", header.number);
-				// reset Last Finalized Block Header
-				self.block_data.last_finalized_block_header = Some(header.clone());
-
-				// finally, send the Verified Block Header
-				self.state
-					.lock()
-					.unwrap()
-					.header_verified
-					.set(header.number);
-				self.event_sender
-					.send(Event::HeaderUpdate {
-						header,
-						received_at,
-					})
-					.unwrap();
-			} else {
+	/// Stores evidence of the equivocation, so it can be served over the API
+	async fn report_equivocation(&self, equivocation: &Equivocation) {
+		self.metrics.count(MetricCounter::Equivocation).await;
+		let block_number = equivocation.block_number;
+		if let Err(error) = self.db.put(Key::Equivocation(block_number), equivocation) {
+			warn!(block_number, "Failed to store equivocation: {error:#}");
+		}
+	}
+
+	fn store_checkpoint(&self, checkpoint: FinalitySyncCheckpoint) -> Result<()> {
+		info!("Storing finality checkpoint at block {}", checkpoint.number);
+		self.db
+			.put(Key::FinalitySyncCheckpoint, checkpoint)
+			.wrap_err("Failed to store finality checkpoint")
+	}
+
+	async fn verify_and_output_block_headers(&mut self) -> Result<(), Failure> {
+		let mut finality_synced = false;
+		while let Some(justification) = self.block_data.justifications.pop() {
+			let target_hash = justification.commit.target_hash;
+			// iterate through Headers and try to find a matching one
+			let Some(pos) = self.unverified_header_position(target_hash) else {
 				trace!("Matched pair of header/justification not found.");
 				self.block_data.justifications.push(justification);
 				break;
+			};
+
+			// basically, pop it out of the collection
+			let unverified = self.block_data.unverified_headers.swap_remove(pos);
+			let (header, received_at, valset, is_checkpoint) = unverified.clone();
+
+			if let Err(error) = check_finality(&valset, &justification) {
+				let block_number = header.number;
+				error!(block_number, "Finality check failed: {error}");
+				if let FinalityError::Equivocation(equivocation) = &error {
+					self.report_equivocation(equivocation).await;
+				}
+				let alert = Alert::FinalityVerificationFailed {
+					block_number,
+					error: error.to_string(),
+				};
+				let _ = self.alert_sender.send(alert);
+				// header stays unverified, so it can be verified with the justification from another node
+				self.block_data.unverified_headers.push(unverified);
+				let error = eyre!(error).wrap_err("Finality check failed");
+				return Err(Failure::SwitchNode(error));
+			}
+
+			// whole chain up to the justified header is verified before any header is sent
+			let skipped = match self.verify_skipped_headers(&header).await {
+				Ok(skipped) => skipped,
+				Err(failure) => {
+					// header stays unverified, so it can be verified with the headers from another node
+					self.block_data.unverified_headers.push(unverified);
+					self.block_data.justifications.push(justification);
+					return Err(failure);
+				},
+			};
+
+			// justification is stored before the header is sent, so it is available once header is verified
+			if let Err(error) = self.store_justification(header.number, &justification) {
+				self.block_data.unverified_headers.push(unverified);
+				self.block_data.justifications.push(justification);
+				return Err(Failure::Retry(error));
 			}
+
+			// skipped headers are sent first, since they are finalized by the justified header
+			self.send_skipped_headers(skipped);
+
+			// To avoid locking the global state all the time, after finality is synced, it will not be necessary to read the state
+			if !finality_synced {
+				finality_synced = self.state.lock().unwrap().finality_synced;
+			}
+			// store Finality Checkpoint if finality is synced,
+			// with the validator set which finalized the header
+			if finality_synced && is_checkpoint {
+				let checkpoint = FinalitySyncCheckpoint {
+					set_id: valset.set_id,
+					number: header.number,
+					validator_set: valset.validator_set,
+				};
+				// checkpoint is stored again with the next verified header, so failure is only logged
+				if let Err(error) = self.store_checkpoint(checkpoint) {
+					warn!("{error:#}");
+				}
+			}
+
+			info!("Sending finalized block {}", header.number);
+			// finally, send the Verified Block Header
+			self.state
+				.lock()
+				.unwrap()
+				.header_verified
+				.set(header.number);
+			let block_number = header.number;
+			self.send_header(header, received_at);
+			// justification is published only if there are subscribers
+			let _ = self
+				.justification_sender
+				.send((block_number, justification));
 		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		verify_parent_hash, BlockData, Event, Failure, MockSubscriptionClient, SubscriptionLoop,
+	};
+	use crate::{
+		alerts::Alert,
+		data::{mem_db::MemoryDB, Database, Key},
+		finality::{AuthoritySet, Equivocation, ValidatorSet},
+		network::rpc::Subscription,
+		shutdown::Controller,
+		telemetry::{MetricCounter, MockMetrics},
+		types::{Commit, GrandpaJustification, Precommit, SignedPrecommit, SignerMessage, State},
+	};
+	use avail_subxt::{
+		api::runtime_types::avail_core::{
+			data_lookup::compact::CompactDataLookup,
+			header::extension::{v3::HeaderExtension, HeaderExtension::V3},
+			kate_commitment::v3::KateCommitment,
+		},
+		config::substrate::{Digest, DigestItem},
+		primitives::Header,
+		utils::H256,
+	};
+	use codec::Encode;
+	use color_eyre::eyre::eyre;
+	use sp_core::{blake2_256, ed25519, Pair};
+	use std::{
+		sync::{Arc, Mutex},
+		time::Instant,
+	};
+	use tokio::sync::{broadcast, mpsc};
+
+	fn header(number: u32, parent_hash: H256) -> Header {
+		Header {
+			parent_hash,
+			number,
+			state_root: H256::zero(),
+			extrinsics_root: H256::zero(),
+			digest: Digest { logs: vec![] },
+			extension: V3(HeaderExtension {
+				commitment: KateCommitment {
+					rows: 1,
+					cols: 4,
+					data_root: H256::zero(),
+					commitment: vec![],
+				},
+				app_lookup: CompactDataLookup {
+					size: 1,
+					index: vec![],
+				},
+			}),
+		}
+	}
+
+	#[test]
+	fn verify_parent_hash_links_headers() {
+		let previous = header(1, H256::zero());
+		let previous_hash = Encode::using_encoded(&previous, blake2_256).into();
+		assert!(verify_parent_hash(&previous, &header(2, previous_hash)).is_ok());
+		assert!(verify_parent_hash(&previous, &header(2, H256::repeat_byte(1))).is_err());
+	}
+
+	fn hash(header: &Header) -> H256 {
+		Encode::using_encoded(header, blake2_256).into()
+	}
+
+	fn justification(signers: &[&ed25519::Pair], header: &Header) -> GrandpaJustification {
+		let precommit = Precommit {
+			target_hash: hash(header),
+			target_number: header.number,
+		};
+		let (round, set_id) = (1u64, 1u64);
+		let signed_message = Encode::encode(&(
+			&SignerMessage::PrecommitMessage(precommit.clone()),
+			&round,
+			&set_id,
+		));
+		let precommits = signers
+			.iter()
+			.map(|pair| SignedPrecommit {
+				precommit: precommit.clone(),
+				signature: pair.sign(&signed_message),
+				id: pair.public(),
+			})
+			.collect();
+		GrandpaJustification {
+			round,
+			commit: Commit {
+				target_hash: precommit.target_hash,
+				target_number: precommit.target_number,
+				precommits,
+			},
+			votes_ancestries: vec![],
+		}
+	}
+
+	// Loop with the last finalized block 1, finalized by the set 1 with the given validator
+	fn subscription_loop(
+		rpc_client: MockSubscriptionClient,
+		metrics: MockMetrics,
+		validator: &ed25519::Pair,
+	) -> (
+		SubscriptionLoop<MemoryDB, MockMetrics, MockSubscriptionClient>,
+		broadcast::Receiver<Event>,
+		mpsc::UnboundedReceiver<Alert>,
+	) {
+		let (event_sender, event_receiver) = broadcast::channel(10);
+		let (alert_sender, alert_receiver) = mpsc::unbounded_channel();
+		let validator_set = ValidatorSet {
+			set_id: 1,
+			validator_set: vec![validator.public()],
+		};
+		let subscription_loop = SubscriptionLoop {
+			rpc_client,
+			event_sender,
+			justification_sender: broadcast::channel(10).0,
+			state: Arc::new(Mutex::new(State::default())),
+			db: MemoryDB::default(),
+			metrics: Arc::new(metrics),
+			alert_sender,
+			header_quorum: None,
+			block_data: BlockData {
+				justifications: vec![],
+				unverified_headers: vec![],
+				authority_set: AuthoritySet::new(validator_set),
+				last_finalized_block_header: Some(header(1, H256::zero())),
+				last_tracked_header: Some(1),
+			},
+		};
+		(subscription_loop, event_receiver, alert_receiver)
+	}
+
+	fn received_header(receiver: &mut broadcast::Receiver<Event>) -> u32 {
+		let Event::HeaderUpdate { header, .. } = receiver.try_recv().unwrap();
+		header.number
+	}
+
+	#[tokio::test]
+	async fn invalid_justification_switches_node() {
+		let validator = ed25519::Pair::from_seed(&[1u8; 32]);
+		let header_2 = header(2, hash(&header(1, H256::zero())));
+		let unsigned = justification(&[], &header_2);
+
+		let mut streams = vec![vec![
+			Ok(Subscription::Header(header_2)),
+			Ok(Subscription::Justification(unsigned)),
+		]]
+		.into_iter();
+		let mut rpc_client = MockSubscriptionClient::new();
+		rpc_client
+			.expect_subscription_stream()
+			.times(2)
+			.returning(move || Box::pin(futures::stream::iter(streams.next().unwrap_or_default())));
+		rpc_client
+			.expect_switch_node()
+			.times(1)
+			.returning(|| Ok(()));
+		let mut metrics = MockMetrics::new();
+		metrics
+			.expect_count()
+			.withf(|counter| matches!(counter, MetricCounter::SubscriptionNodeSwitch))
+			.times(1)
+			.returning(|_| ());
+
+		let (mut subscription_loop, mut events, mut alerts) =
+			subscription_loop(rpc_client, metrics, &validator);
+		subscription_loop.run_subscriptions().await.unwrap();
+
+		let alert = alerts.try_recv().unwrap();
+		assert!(matches!(
+			alert,
+			Alert::FinalityVerificationFailed {
+				block_number: 2,
+				..
+			}
+		));
+		assert!(events.try_recv().is_err());
+		// header can still be verified with the justification from another node
+		assert_eq!(subscription_loop.block_data.unverified_headers.len(), 1);
+	}
+
+	#[tokio::test]
+	async fn equivocation_is_stored() {
+		let validator = ed25519::Pair::from_seed(&[1u8; 32]);
+		let header_2 = header(2, hash(&header(1, H256::zero())));
+		let header_3 = header(3, hash(&header_2));
+		let mut equivocated = justification(&[&validator], &header_2);
+		let conflicting = justification(&[&validator], &header_3).commit.precommits;
+		equivocated.commit.precommits.extend(conflicting);
+
+		let mut metrics = MockMetrics::new();
+		metrics
+			.expect_count()
+			.withf(|counter| matches!(counter, MetricCounter::Equivocation))
+			.times(1)
+			.returning(|_| ());
+
+		let (mut subscription_loop, mut events, _alerts) =
+			subscription_loop(MockSubscriptionClient::new(), metrics, &validator);
+		let validator_set = subscription_loop.block_data.authority_set.current.clone();
+		let block_data = &mut subscription_loop.block_data;
+		block_data.last_tracked_header = Some(2);
+		block_data
+			.unverified_headers
+			.push((header_2, Instant::now(), validator_set, false));
+		block_data.justifications.push(equivocated);
+
+		let result = subscription_loop.verify_and_output_block_headers().await;
+		assert!(matches!(result, Err(Failure::SwitchNode(_))));
+		assert!(events.try_recv().is_err());
+
+		let equivocation = subscription_loop
+			.db
+			.get::<Equivocation>(Key::Equivocation(2))
+			.unwrap()
+			.unwrap();
+		assert_eq!(equivocation.signer, validator.public());
+		assert_eq!(equivocation.second.precommit.target_number, 3);
+	}
+
+	#[tokio::test]
+	async fn skipped_header_fetch_is_retried() {
+		let validator = ed25519::Pair::from_seed(&[1u8; 32]);
+		let header_2 = header(2, hash(&header(1, H256::zero())));
+		let header_3 = header(3, hash(&header_2));
+
+		let mut calls = 0;
+		let mut rpc_client = MockSubscriptionClient::new();
+		rpc_client
+			.expect_get_header_by_block_number()
+			.times(2)
+			.returning(move |_| {
+				calls += 1;
+				match calls {
+					1 => Err(eyre!("Connection failed")),
+					_ => Ok((header_2.clone(), H256::zero())),
+				}
+			});
+
+		let (mut subscription_loop, mut events, _alerts) =
+			subscription_loop(rpc_client, MockMetrics::new(), &validator);
+		let validator_set = subscription_loop.block_data.authority_set.current.clone();
+		let block_data = &mut subscription_loop.block_data;
+		block_data.last_tracked_header = Some(3);
+		block_data
+			.unverified_headers
+			.push((header_3.clone(), Instant::now(), validator_set, true));
+		block_data
+			.justifications
+			.push(justification(&[&validator], &header_3));
+
+		let result = subscription_loop.verify_and_output_block_headers().await;
+		assert!(matches!(result, Err(Failure::Retry(_))));
+		assert!(events.try_recv().is_err());
+
+		subscription_loop
+			.verify_and_output_block_headers()
+			.await
+			.unwrap();
+		assert_eq!(received_header(&mut events), 2);
+		assert_eq!(received_header(&mut events), 3);
+		assert!(subscription_loop.block_data.unverified_headers.is_empty());
+
+		// Only the justification of the target block is stored
+		let db = &subscription_loop.db;
+		let stored = db.get::<GrandpaJustification>(Key::Justification(3));
+		assert!(stored.unwrap().is_some());
+		let stored = db.get::<GrandpaJustification>(Key::Justification(2));
+		assert!(stored.unwrap().is_none());
+	}
+
+	#[tokio::test]
+	async fn skipped_header_not_linking_to_target_is_not_emitted() {
+		let validator = ed25519::Pair::from_seed(&[1u8; 32]);
+		// skipped header links to the last finalized header, but not to the justified header
+		let header_2 = header(2, hash(&header(1, H256::zero())));
+		let header_3 = header(3, H256::repeat_byte(1));
+
+		let mut rpc_client = MockSubscriptionClient::new();
+		rpc_client
+			.expect_get_header_by_block_number()
+			.times(1)
+			.returning(move |_| Ok((header_2.clone(), H256::zero())));
+		let mut metrics = MockMetrics::new();
+		metrics
+			.expect_count()
+			.withf(|counter| matches!(counter, MetricCounter::NonLinkingHeader))
+			.times(1)
+			.returning(|_| ());
+
+		let (mut subscription_loop, mut events, _alerts) =
+			subscription_loop(rpc_client, metrics, &validator);
+		let validator_set = subscription_loop.block_data.authority_set.current.clone();
+		let block_data = &mut subscription_loop.block_data;
+		block_data.last_tracked_header = Some(3);
+		block_data
+			.unverified_headers
+			.push((header_3.clone(), Instant::now(), validator_set, true));
+		block_data
+			.justifications
+			.push(justification(&[&validator], &header_3));
+
+		let result = subscription_loop.verify_and_output_block_headers().await;
+		assert!(matches!(result, Err(Failure::SwitchNode(_))));
+		assert!(events.try_recv().is_err());
+
+		// header can still be verified with the headers from another node
+		let block_data = &subscription_loop.block_data;
+		assert_eq!(block_data.unverified_headers.len(), 1);
+		assert_eq!(block_data.justifications.len(), 1);
+		let last_finalized = block_data.last_finalized_block_header.as_ref();
+		assert_eq!(last_finalized.map(|header| header.number), Some(1));
+	}
+
+	#[tokio::test]
+	async fn diverging_quorum_is_not_emitted() {
+		let validator = ed25519::Pair::from_seed(&[1u8; 32]);
+		let header_2 = header(2, hash(&header(1, H256::zero())));
+		let header_3 = header(3, hash(&header_2));
+		let header_2_hash = hash(&header_2);
+
+		let mut rpc_client = MockSubscriptionClient::new();
+		rpc_client
+			.expect_get_header_by_block_number()
+			.times(2)
+			.returning(move |_| Ok((header_2.clone(), header_2_hash)));
+		let mut calls = 0;
+		rpc_client
+			.expect_get_block_hashes()
+			.withf(|&block_number, &count| block_number == 2 && count == 1)
+			.times(2)
+			.returning(move |_, _| {
+				calls += 1;
+				let node_hash = match calls {
+					1 => H256::repeat_byte(1),
+					_ => header_2_hash,
+				};
+				Ok(vec![("ws://node-2".to_string(), node_hash)])
+			});
+		let mut metrics = MockMetrics::new();
+		metrics
+			.expect_count()
+			.withf(|counter| matches!(counter, MetricCounter::HeaderQuorumFailed))
+			.times(1)
+			.returning(|_| ());
+
+		let (mut subscription_loop, mut events, mut alerts) =
+			subscription_loop(rpc_client, metrics, &validator);
+		subscription_loop.header_quorum = Some(2);
+		let validator_set = subscription_loop.block_data.authority_set.current.clone();
+		let block_data = &mut subscription_loop.block_data;
+		block_data.last_tracked_header = Some(3);
+		block_data
+			.unverified_headers
+			.push((header_3.clone(), Instant::now(), validator_set, true));
+		block_data
+			.justifications
+			.push(justification(&[&validator], &header_3));
+
+		let result = subscription_loop.verify_and_output_block_headers().await;
+		assert!(matches!(result, Err(Failure::Retry(_))));
+		assert!(events.try_recv().is_err());
+		let Alert::HeaderQuorumFailed {
+			block_number,
+			hash,
+			hashes,
+		} = alerts.try_recv().unwrap()
+		else {
+			panic!("Header quorum alert expected");
+		};
+		assert_eq!(block_number, 2);
+		assert_eq!(hash, format!("{header_2_hash:?}"));
+		let expected = format!("{:?}", H256::repeat_byte(1));
+		assert_eq!(hashes.get("ws://node-2"), Some(&expected));
+
+		// header is emitted once nodes agree
+		subscription_loop
+			.verify_and_output_block_headers()
+			.await
+			.unwrap();
+		assert_eq!(received_header(&mut events), 2);
+		assert_eq!(received_header(&mut events), 3);
+	}
+
+	#[tokio::test]
+	async fn unavailable_quorum_is_alerted() {
+		let validator = ed25519::Pair::from_seed(&[1u8; 32]);
+		let header_2 = header(2, hash(&header(1, H256::zero())));
+		let header_3 = header(3, hash(&header_2));
+		let header_2_hash = hash(&header_2);
+
+		let mut rpc_client = MockSubscriptionClient::new();
+		rpc_client
+			.expect_get_header_by_block_number()
+			.times(1)
+			.returning(move |_| Ok((header_2.clone(), header_2_hash)));
+		rpc_client
+			.expect_get_block_hashes()
+			.times(1)
+			.returning(|_, _| Err(eyre!("Only 0 of 1 nodes returned hash of block 2")));
+		let mut metrics = MockMetrics::new();
+		metrics
+			.expect_count()
+			.withf(|counter| matches!(counter, MetricCounter::HeaderQuorumUnavailable))
+			.times(1)
+			.returning(|_| ());
+
+		let (mut subscription_loop, mut events, mut alerts) =
+			subscription_loop(rpc_client, metrics, &validator);
+		subscription_loop.header_quorum = Some(2);
+		let validator_set = subscription_loop.block_data.authority_set.current.clone();
+		let block_data = &mut subscription_loop.block_data;
+		block_data.last_tracked_header = Some(3);
+		block_data
+			.unverified_headers
+			.push((header_3.clone(), Instant::now(), validator_set, true));
+		block_data
+			.justifications
+			.push(justification(&[&validator], &header_3));
+
+		let result = subscription_loop.verify_and_output_block_headers().await;
+		assert!(matches!(result, Err(Failure::Retry(_))));
+		assert!(events.try_recv().is_err());
+		assert!(matches!(
+			alerts.try_recv().unwrap(),
+			Alert::HeaderQuorumUnavailable {
+				block_number: 2,
+				..
+			}
+		));
+	}
+
+	// Scheduled change is encoded as the GRANDPA `ConsensusLog` variant with index 1
+	fn scheduled_change(delay: u32) -> DigestItem {
+		let authorities = vec![([1u8; 32], 1u64)];
+		DigestItem::Consensus(*b"FRNK", (1u8, authorities, delay).encode())
+	}
+
+	#[tokio::test]
+	async fn invalid_authority_set_changes_switch_node() {
+		let validator = ed25519::Pair::from_seed(&[1u8; 32]);
+		// block can signal only one standard change
+		let mut header_2 = header(2, hash(&header(1, H256::zero())));
+		header_2.digest.logs = vec![scheduled_change(1), scheduled_change(2)];
+
+		let (mut subscription_loop, _events, _alerts) = subscription_loop(
+			MockSubscriptionClient::new(),
+			MockMetrics::new(),
+			&validator,
+		);
+		let result = subscription_loop
+			.handle_new_subscription(Subscription::Header(header_2))
+			.await;
+		assert!(matches!(result, Err(Failure::SwitchNode(_))));
+
+		// rejected header doesn't change the tracked headers nor the authority set
+		let block_data = &subscription_loop.block_data;
+		assert!(block_data.unverified_headers.is_empty());
+		assert_eq!(block_data.last_tracked_header, Some(1));
+		assert!(!block_data.authority_set.has_pending_changes());
+	}
+
+	#[tokio::test]
+	async fn failed_subscriptions_trigger_shutdown() {
+		let validator = ed25519::Pair::from_seed(&[1u8; 32]);
+		let mut rpc_client = MockSubscriptionClient::new();
+		rpc_client.expect_subscription_stream().returning(|| {
+			let failed = Err(eyre!("Failed to connect any appropriate working node"));
+			Box::pin(futures::stream::iter(vec![failed]))
+		});
+		let mut metrics = MockMetrics::new();
+		metrics
+			.expect_count()
+			.withf(|counter| matches!(counter, MetricCounter::SubscriptionFatal))
+			.times(1)
+			.returning(|_| ());
+
+		let (subscription_loop, _events, _alerts) =
+			subscription_loop(rpc_client, metrics, &validator);
+		let shutdown = Controller::new();
+		assert!(subscription_loop.run(shutdown.clone()).await.is_err());
+		assert!(shutdown.is_shutdown_triggered());
 	}
 }
@@ -0,0 +1,231 @@
+//! Database retention, prunes stored block data according to the configured retention policies.
+//!
+//! # Flow
+//!
+//! * On every pruning interval, for each column family with a configured retention policy:
+//! * Marks blocks before the first retained block as pruned in the state
+//! * Deletes block data before the first retained block and records number of deleted values
+//! * Sampling reports are pruned together with the confidence factors
+//! * Justifications and equivocations are pruned together with the block headers
+
+use color_eyre::{eyre::WrapErr, Result};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tracing::{error, info};
+
+use crate::{
+	data::{
+		Database, APP_DATA_CF, BLOCK_HEADER_CF, CONFIDENCE_FACTOR_CF, EQUIVOCATION_CF,
+		JUSTIFICATION_CF, SAMPLING_REPORT_CF,
+	},
+	shutdown::Controller,
+	telemetry::{MetricValue, Metrics},
+	types::{BlockVerified, RetentionConfig, RetentionPolicy, State},
+};
+
+/// Prunes column family and returns number of pruned values.
+/// Pruned block marker is updated before deletion, so pruned blocks are not served meanwhile.
+fn prune(
+	db: &impl Database,
+	column_family: &str,
+	retention: RetentionPolicy,
+	block_number: u32,
+	pruned: &mut Option<u32>,
+) -> Result<usize> {
+	let first_retained = retention.first_retained(block_number);
+	if first_retained == 0 || *pruned >= Some(first_retained - 1) {
+		return Ok(0);
+	}
+	pruned.replace(first_retained - 1);
+	db.delete_before(column_family, first_retained)
+		.wrap_err_with(|| format!("Failed to prune {column_family}"))
+}
+
+pub async fn process_block(
+	db: &impl Database,
+	metrics: &Arc<impl Metrics>,
+	cfg: RetentionConfig,
+	block_number: u32,
+	state: &Arc<Mutex<State>>,
+) -> Result<()> {
+	if block_number % cfg.pruning_interval != 0 {
+		return Ok(());
+	}
+
+	if let Some(retention) = cfg.confidence {
+		let mut pruned = state.lock().unwrap().confidence_pruned;
+		// Sampling reports are retained as long as the confidence factors
+		let mut reports_pruned = pruned;
+		prune(
+			db,
+			SAMPLING_REPORT_CF,
+			retention,
+			block_number,
+			&mut reports_pruned,
+		)?;
+		let count = prune(
+			db,
+			CONFIDENCE_FACTOR_CF,
+			retention,
+			block_number,
+			&mut pruned,
+		)?;
+		{
+			let mut state = state.lock().unwrap();
+			state.confidence_pruned = pruned;
+			state
+				.confidence_failed
+				.retain(|&block| Some(block) > pruned);
+		}
+		info!(block_number, count, "Pruned confidence factors");
+		metrics
+			.record(MetricValue::PrunedConfidenceFactors(count))
+			.await?;
+	}
+
+	if let Some(retention) = cfg.block_header {
+		let mut pruned = state.lock().unwrap().header_pruned;
+		// Justifications and equivocations are retained as long as the block headers
+		for column_family in [JUSTIFICATION_CF, EQUIVOCATION_CF] {
+			let mut column_family_pruned = pruned;
+			prune(
+				db,
+				column_family,
+				retention,
+				block_number,
+				&mut column_family_pruned,
+			)?;
+		}
+		let count = prune(db, BLOCK_HEADER_CF, retention, block_number, &mut pruned)?;
+		state.lock().unwrap().header_pruned = pruned;
+		info!(block_number, count, "Pruned block headers");
+		metrics
+			.record(MetricValue::PrunedBlockHeaders(count))
+			.await?;
+	}
+
+	if let Some(retention) = cfg.app_data {
+		let mut pruned = state.lock().unwrap().data_pruned;
+		let count = prune(db, APP_DATA_CF, retention, block_number, &mut pruned)?;
+		state.lock().unwrap().data_pruned = pruned;
+		info!(block_number, count, "Pruned application data");
+		metrics.record(MetricValue::PrunedAppData(count)).await?;
+	}
+
+	Ok(())
+}
+
+pub async fn run(
+	db: impl Database,
+	metrics: Arc<impl Metrics>,
+	mut block_receiver: broadcast::Receiver<BlockVerified>,
+	cfg: RetentionConfig,
+	state: Arc<Mutex<State>>,
+	shutdown: Controller<String>,
+) {
+	info!("Starting retention...");
+
+	loop {
+		let result = match block_receiver.recv().await {
+			Ok(block) => process_block(&db, &metrics, cfg, block.block_num, &state).await,
+			Err(error) => Err(error.into()),
+		};
+
+		if let Err(error) = result {
+			error!("Retention failed: {error:#}");
+			let _ = shutdown.trigger_shutdown(format!("{error:#}"));
+			break;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::process_block;
+	use crate::{
+		data::{mem_db::MemoryDB, Database, Key},
+		telemetry::MockMetrics,
+		types::{RetentionConfig, RetentionPolicy, State},
+	};
+	use std::sync::{Arc, Mutex};
+
+	#[tokio::test]
+	async fn test_process_block_prunes_retained_blocks() {
+		let db = MemoryDB::default();
+		for block_number in 1..=10 {
+			db.put(Key::VerifiedCellCount(block_number), 10u32).unwrap();
+			db.put(Key::AppData(1, block_number), Vec::<Vec<u8>>::new())
+				.unwrap();
+		}
+
+		let mut metrics = MockMetrics::new();
+		metrics.expect_record().times(2).returning(|_| Ok(()));
+		let metrics = Arc::new(metrics);
+
+		let cfg = RetentionConfig {
+			confidence: Some(RetentionPolicy::Blocks(3)),
+			block_header: None,
+			app_data: Some(RetentionPolicy::Blocks(5)),
+			pruning_interval: 5,
+		};
+		let mut state = State::default();
+		state.confidence_failed.extend([5, 9]);
+		let state = Arc::new(Mutex::new(state));
+
+		process_block(&db, &metrics, cfg, 9, &state).await.unwrap();
+		assert!(db.get::<u32>(Key::VerifiedCellCount(1)).unwrap().is_some());
+
+		process_block(&db, &metrics, cfg, 10, &state).await.unwrap();
+		for block_number in 1..=7 {
+			let count = db.get::<u32>(Key::VerifiedCellCount(block_number));
+			assert!(count.unwrap().is_none());
+		}
+		for block_number in 8..=10 {
+			let count = db.get::<u32>(Key::VerifiedCellCount(block_number));
+			assert!(count.unwrap().is_some());
+		}
+		let data = db.get::<Vec<Vec<u8>>>(Key::AppData(1, 5)).unwrap();
+		assert!(data.is_none());
+		let data = db.get::<Vec<Vec<u8>>>(Key::AppData(1, 6)).unwrap();
+		assert!(data.is_some());
+
+		let state = state.lock().unwrap();
+		assert_eq!(state.confidence_pruned, Some(7));
+		assert_eq!(state.header_pruned, None);
+		assert_eq!(state.data_pruned, Some(5));
+		assert_eq!(state.confidence_failed.iter().collect::<Vec<_>>(), vec![&9]);
+	}
+
+	#[tokio::test]
+	async fn test_process_block_prunes_headers_with_justifications_and_equivocations() {
+		let db = MemoryDB::default();
+		for block_number in 1..=10 {
+			db.put(Key::BlockHeader(block_number), block_number)
+				.unwrap();
+			db.put(Key::Justification(block_number), block_number)
+				.unwrap();
+			db.put(Key::Equivocation(block_number), block_number)
+				.unwrap();
+		}
+
+		let mut metrics = MockMetrics::new();
+		metrics.expect_record().times(1).returning(|_| Ok(()));
+		let metrics = Arc::new(metrics);
+
+		let cfg = RetentionConfig {
+			confidence: None,
+			block_header: Some(RetentionPolicy::Blocks(3)),
+			app_data: None,
+			pruning_interval: 5,
+		};
+		let state = Arc::new(Mutex::new(State::default()));
+
+		process_block(&db, &metrics, cfg, 10, &state).await.unwrap();
+		let keys: [fn(u32) -> Key; 3] = [Key::BlockHeader, Key::Justification, Key::Equivocation];
+		for key in keys {
+			assert!(db.get::<u32>(key(7)).unwrap().is_none());
+			assert_eq!(db.get::<u32>(key(8)).unwrap(), Some(8));
+		}
+		assert_eq!(state.lock().unwrap().header_pruned, Some(7));
+	}
+}
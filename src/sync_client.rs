@@ -4,25 +4,28 @@
 //!
 //! # Flow
 //!
-//! * For each block, fetches block header from RPC and stores it into database
+//! * For each block, fetches block header from database or RPC
 //! * Generate random cells for random data sampling
 //! * Retrieve cell proofs from a) DHT and/or b) via RPC call from the node, in that order
 //! * Verify proof using the received cells
-//! * Calculate block confidence and store it in RocksDB
+//! * Calculate block confidence and store it in RocksDB, atomically with the sampling report
 //! * Insert cells to to DHT for remote fetch
 //!
 //! # Notes
 //!
 //! In case RPC is disabled, RPC calls will be skipped.
+//! Blocks missed by the clients due to the channel lag are backfilled the same way, see [`run_backfill`].
 
 use crate::{
-	data::{Database, Key},
+	alerts::{Alert, AlertSender},
+	data::{Database, Key, KeyRange, WriteBatch},
 	network::{
 		self,
 		rpc::{self, Client as RpcClient},
+		SamplingReport,
 	},
 	types::{BlockVerified, OptionBlockRange, State, SyncClientConfig},
-	utils::{calculate_confidence, extract_app_lookup, extract_kate},
+	utils::{extract_app_lookup, extract_kate},
 };
 
 use async_trait::async_trait;
@@ -36,19 +39,21 @@ use kate_recovery::{commitments, matrix::Dimensions};
 use mockall::automock;
 use sp_core::blake2_256;
 use std::{
+	collections::HashSet,
 	ops::Range,
 	sync::{Arc, Mutex},
 	time::Instant,
 };
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{error, info, warn};
 
 #[async_trait]
 #[automock]
 pub trait Client {
 	async fn get_header_by_block_number(&self, block_number: u32) -> Result<(DaHeader, H256)>;
-	fn is_confidence_stored(&self, block_number: u32) -> Result<bool>;
-	fn store_confidence(&self, count: u32, block_number: u32) -> Result<()>;
+	fn get_confidence_stored_blocks(&self, range: Range<u32>) -> Result<HashSet<u32>>;
+	fn store_block(&self, block_number: u32, count: u32, report: &SamplingReport) -> Result<()>;
+	fn store_sampling_report(&self, report: &SamplingReport) -> Result<()>;
 }
 
 #[derive(Clone)]
@@ -75,7 +80,7 @@ impl<T: Database + Sync> Client for SyncClient<T> {
 			return Ok((header, hash));
 		}
 
-		let (header, hash) = match self
+		let (header, hash) = self
 			.rpc_client
 			.get_header_by_block_number(block_number)
 			.await
@@ -83,29 +88,43 @@ impl<T: Database + Sync> Client for SyncClient<T> {
 				format!(
 					"Sync Client failed to get Block {block_number:#?} by Block Number from storage",
 				)
-			}) {
-			Ok(value) => value,
-			Err(error) => return Err(error),
-		};
+			})?;
 
+		// header is stored on fetch, since header verified range is set before the block is processed
 		self.db
-			.put(Key::BlockHeader(block_number), header.clone())
+			.put(Key::BlockHeader(block_number), &header)
 			.wrap_err("Sync Client failed to store Block Header")?;
 
 		Ok((header, hash))
 	}
 
-	fn is_confidence_stored(&self, block_number: u32) -> Result<bool> {
+	fn get_confidence_stored_blocks(&self, range: Range<u32>) -> Result<HashSet<u32>> {
+		let Some(last) = range.end.checked_sub(1) else {
+			return Ok(HashSet::new());
+		};
 		self.db
-			.get(Key::VerifiedCellCount(block_number))
-			.wrap_err("Sync Client failed to check if Confidence Factor is stored")
-			.map(|c: Option<u32>| c.is_some())
+			.iter::<u32>(KeyRange::VerifiedCellCount(range.start..=last))
+			.and_then(|values| values.map(|value| value.map(|(block, _)| block)).collect())
+			.wrap_err("Sync Client failed to get blocks with stored Confidence Factor")
 	}
 
-	fn store_confidence(&self, count: u32, block_number: u32) -> Result<()> {
-		self.db
+	fn store_block(&self, block_number: u32, count: u32, report: &SamplingReport) -> Result<()> {
+		let mut batch = self.db.batch();
+		batch
 			.put(Key::VerifiedCellCount(block_number), count)
-			.wrap_err("Sync Client failed to store Confidence Factor")
+			.wrap_err("Sync Client failed to store Confidence Factor")?;
+		batch
+			.put(Key::SamplingReport(block_number), report)
+			.wrap_err("Sync Client failed to store Sampling Report")?;
+		self.db
+			.write(batch)
+			.wrap_err("Sync Client failed to store processed block")
+	}
+
+	fn store_sampling_report(&self, report: &SamplingReport) -> Result<()> {
+		self.db
+			.put(Key::SamplingReport(report.block_number), report)
+			.wrap_err("Sync Client failed to store Sampling Report")
 	}
 }
 
@@ -116,7 +135,7 @@ async fn process_block(
 	header_hash: H256,
 	cfg: &SyncClientConfig,
 	block_verified_sender: broadcast::Sender<BlockVerified>,
-) -> Result<()> {
+) -> Result<Option<f64>> {
 	let block_number = header.number;
 	let begin = Instant::now();
 
@@ -131,10 +150,11 @@ async fn process_block(
 	let commitments = commitments::from_slice(&commitment)?;
 
 	// now this is in `u64`
-	let cell_count = rpc::cell_count_for_confidence(cfg.confidence);
-	let positions = rpc::generate_random_cells(dimensions, cell_count);
+	let cell_count = cfg.confidence_model.cell_count(cfg.confidence, dimensions);
+	let seed = cfg.sampling_strategy.seed(header_hash);
+	let positions = rpc::generate_random_cells(dimensions, cell_count, seed);
 
-	let (fetched, unfetched, _fetch_stats) = network_client
+	let (fetched, unfetched, fetch_stats) = network_client
 		.fetch_verified(
 			block_number,
 			header_hash,
@@ -144,23 +164,28 @@ async fn process_block(
 		)
 		.await?;
 
+	let report = SamplingReport::new(block_number, seed, &positions, &fetch_stats);
+
 	if positions.len() > fetched.len() {
 		error!(block_number, "Failed to fetch {} cells", unfetched.len());
-		return Ok(());
+		client.store_sampling_report(&report)?;
+		return Ok(None);
 	}
 
-	// write confidence factor into on-disk database
-	client.store_confidence(fetched.len().try_into()?, block_number)?;
+	// write confidence factor and sampling report into on-disk database
+	client.store_block(block_number, fetched.len().try_into()?, &report)?;
 
-	let confidence = Some(calculate_confidence(fetched.len() as u32));
-	let client_msg =
-		BlockVerified::try_from((header, confidence)).wrap_err("converting to message failed")?;
+	let confidence = cfg
+		.confidence_model
+		.confidence(fetched.len() as u32, dimensions);
+	let client_msg = BlockVerified::try_from((header, Some(confidence)))
+		.wrap_err("converting to message failed")?;
 
 	if let Err(error) = block_verified_sender.send(client_msg) {
 		error!("Cannot send block verified message: {error}");
 	}
 
-	Ok(())
+	Ok(Some(confidence))
 }
 
 /// Runs sync client.
@@ -188,19 +213,21 @@ pub async fn run(
 		warn!("In order to process {sync_blocks_depth} blocks behind latest block, connected nodes needs to be archive nodes!");
 	}
 
+	// TODO: This is still an ambiguous check since data fetch can fail.
+	// We should write block status in DB explicitly.
+	let stored_blocks = match client.get_confidence_stored_blocks(sync_range.clone()) {
+		Ok(stored_blocks) => stored_blocks,
+		Err(error) => {
+			error!("Cannot sync blocks for range {sync_range:?}: {error:#}");
+			return;
+		},
+	};
+
 	info!("Syncing block headers for {sync_range:?}");
 	for block_number in sync_range {
-		// TODO: This is still an ambiguous check since data fetch can fail.
-		// We should write block status in DB explicitly.
-		match client.is_confidence_stored(block_number) {
-			Ok(false) => (),
-			Ok(true) => continue,
-			Err(error) => {
-				// TODO: Is it valid to have skipped block?
-				error!(block_number, "Cannot process block: {error:#}");
-				continue;
-			},
-		};
+		if stored_blocks.contains(&block_number) {
+			continue;
+		}
 
 		let (header, header_hash) = match client.get_header_by_block_number(block_number).await {
 			Ok(value) => value,
@@ -241,6 +268,96 @@ pub async fn run(
 	}
 }
 
+/// Backfills block, sampling it if confidence is not stored.
+/// Blocks with stored confidence are published again, for the clients which missed them.
+/// Returns `true` if block confidence is achieved.
+async fn backfill_block(
+	client: &impl Client,
+	network_client: &impl network::Client,
+	cfg: &SyncClientConfig,
+	block_number: u32,
+	block_verified_sender: &broadcast::Sender<BlockVerified>,
+) -> Result<bool> {
+	let (header, header_hash) = client.get_header_by_block_number(block_number).await?;
+
+	let stored_blocks = client.get_confidence_stored_blocks(block_number..block_number + 1)?;
+	if !stored_blocks.contains(&block_number) {
+		let sender = block_verified_sender.clone();
+		let confidence =
+			process_block(client, network_client, header, header_hash, cfg, sender).await?;
+		return Ok(confidence.is_some());
+	}
+
+	let client_msg =
+		BlockVerified::try_from((header, None)).wrap_err("converting to message failed")?;
+	if let Err(error) = block_verified_sender.send(client_msg) {
+		error!("Cannot send block verified message: {error}");
+	}
+	Ok(true)
+}
+
+/// Runs backfill of the blocks missed by the clients due to the channel lag.
+///
+/// # Arguments
+///
+/// * `cfg` - Sync client configuration
+/// * `gap_receiver` - Channel used to receive ranges of missed blocks
+/// * `block_verified_sender` - Channel used to send backfilled blocks
+/// * `state` - Processed blocks state
+/// * `alert_sender` - Channel used to raise alerts on blocks failing confidence
+pub async fn run_backfill(
+	client: impl Client,
+	network_client: impl network::Client,
+	cfg: SyncClientConfig,
+	mut gap_receiver: mpsc::UnboundedReceiver<Range<u32>>,
+	block_verified_sender: broadcast::Sender<BlockVerified>,
+	state: Arc<Mutex<State>>,
+	alert_sender: AlertSender,
+) {
+	info!("Starting backfill...");
+
+	while let Some(gap) = gap_receiver.recv().await {
+		info!("Backfilling missed blocks {gap:?}");
+		for block_number in gap {
+			let result = backfill_block(
+				&client,
+				&network_client,
+				&cfg,
+				block_number,
+				&block_verified_sender,
+			)
+			.await;
+
+			let mut state = state.lock().unwrap();
+			state.backfilling.remove(&block_number);
+			match result {
+				Ok(true) => {
+					state.set_confidence_achieved(block_number);
+					continue;
+				},
+				Ok(false) => {},
+				Err(error) => error!(block_number, "Cannot backfill block: {error:#}"),
+			}
+			state.confidence_failed.insert(block_number);
+			let _ = alert_sender.send(Alert::ConfidenceFailed { block_number });
+		}
+	}
+}
+
+/// Hands blocks missed due to the channel lag over to the backfill,
+/// marking them as backfilling until processed.
+pub fn backfill(
+	gap_sender: &mpsc::UnboundedSender<Range<u32>>,
+	state: &Arc<Mutex<State>>,
+	gap: Range<u32>,
+) {
+	warn!("Blocks {gap:?} missed, scheduling backfill");
+	state.lock().unwrap().backfilling.extend(gap.clone());
+	if let Err(error) = gap_sender.send(gap) {
+		error!("Cannot schedule backfill: {error}");
+	}
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -367,13 +484,16 @@ mod tests {
 				Box::pin(async move { Ok((fetched, unfetched, stats)) })
 			});
 		mock_client
-			.expect_is_confidence_stored()
-			.with(eq(2))
-			.returning(|_| Ok(true));
+			.expect_get_confidence_stored_blocks()
+			.with(eq(2..3))
+			.returning(|_| Ok(HashSet::from([2])));
+		mock_client
+			.expect_store_block()
+			.withf(move |&block_number, _, report| block_number == 2 && report.block_number == 2)
+			.returning(move |_, _, _| Ok(()));
 		mock_client
-			.expect_store_confidence()
-			.withf(move |_, block_number| *block_number == 2)
-			.returning(move |_, _| Ok(()));
+			.expect_store_sampling_report()
+			.returning(|_| Ok(()));
 		process_block(
 			&mock_client,
 			&mock_network_client,
@@ -457,9 +577,12 @@ mod tests {
 			});
 
 		mock_client
-			.expect_store_confidence()
-			.withf(move |_, block_number| *block_number == 2)
-			.returning(move |_, _| Ok(()));
+			.expect_store_block()
+			.withf(move |&block_number, _, report| block_number == 2 && report.block_number == 2)
+			.returning(move |_, _, _| Ok(()));
+		mock_client
+			.expect_store_sampling_report()
+			.returning(|_| Ok(()));
 		process_block(
 			&mock_client,
 			&mock_network_client,
@@ -471,4 +594,49 @@ mod tests {
 		.await
 		.unwrap();
 	}
+
+	#[tokio::test]
+	pub async fn test_backfill_publishes_stored_blocks() {
+		let (block_tx, mut block_rx) = broadcast::channel::<types::BlockVerified>(10);
+		let (gap_tx, gap_rx) = mpsc::unbounded_channel();
+		let (alert_tx, mut alert_rx) = mpsc::unbounded_channel();
+		let cfg = SyncClientConfig::from(&RuntimeConfig::default());
+		let mock_network_client = network::MockClient::new();
+		let mut mock_client = MockClient::new();
+		let header = default_header();
+		let header_hash: H256 =
+			hex!("3767f8955d6f7306b1e55701b6316fa1163daa8d4cffdb05c3b25db5f5da1723").into();
+
+		mock_client
+			.expect_get_header_by_block_number()
+			.with(eq(2))
+			.returning(move |_| Ok((header.clone(), header_hash)));
+		mock_client
+			.expect_get_confidence_stored_blocks()
+			.with(eq(2..3))
+			.returning(|_| Ok(HashSet::from([2])));
+
+		let state = Arc::new(Mutex::new(State::default()));
+		backfill(&gap_tx, &state, 2..3);
+		assert!(state.lock().unwrap().backfilling.contains(&2));
+		drop(gap_tx);
+
+		run_backfill(
+			mock_client,
+			mock_network_client,
+			cfg,
+			gap_rx,
+			block_tx,
+			state.clone(),
+			alert_tx,
+		)
+		.await;
+
+		assert_eq!(block_rx.recv().await.unwrap().block_num, 2);
+		let state = state.lock().unwrap();
+		assert!(state.backfilling.is_empty());
+		assert!(state.confidence_achieved.contains(2));
+		assert!(state.confidence_failed.is_empty());
+		assert!(alert_rx.try_recv().is_err());
+	}
 }
@@ -1,3 +1,17 @@
+//! Finality sync, verifies the authority set changes from the checkpoint (or genesis) up to the first received block.
+//!
+//! # Flow
+//!
+//! * Warp sync jumps between the authority set changes, requesting finality proof for the first block of the current set
+//! * Each proof justifies the last block of the set, whose header (or its ancestor in the proof unknown headers) signals the set change
+//! * Once the proof doesn't justify the set change, remaining blocks are synced block by block
+//!
+//! # Notes
+//!
+//! Warp sync stores only the headers of the checkpoint blocks, so checkpoints can be exported.
+//! Other skipped headers are fetched and stored lazily, once requested by the API or the sync client.
+//! In case warp sync fails (e.g. the node cannot prove the finality of old blocks), all blocks are synced block by block.
+
 use async_trait::async_trait;
 use avail_subxt::primitives::Header;
 use codec::Encode;
@@ -6,6 +20,7 @@ use color_eyre::{
 	Result,
 };
 use futures::future::join_all;
+use mockall::automock;
 use sp_core::{
 	blake2_256,
 	ed25519::{self},
@@ -16,18 +31,20 @@ use std::{
 	sync::{Arc, Mutex},
 };
 use subxt::{storage::StorageKey, utils::AccountId32};
-use tracing::{error, info, trace};
+use tracing::{error, info, trace, warn};
 
 use crate::{
 	data::{Database, FinalitySyncCheckpoint, Key},
-	finality::{check_finality, ValidatorSet},
-	network::rpc::{self, WrappedProof},
+	finality::{
+		authority_set_changes, check_finality, AuthoritySet, AuthoritySetChange, ValidatorSet,
+	},
+	network::rpc::{self, FinalityProof, WrappedProof},
 	shutdown::Controller,
-	types::State,
-	utils::filter_auth_set_changes,
+	types::{State, TrustedCheckpoint},
 };
 
 #[async_trait]
+#[automock]
 pub trait Client {
 	fn store_block_header(&self, block_number: u32, header: Header) -> Result<()>;
 	fn get_checkpoint(&self) -> Result<Option<FinalitySyncCheckpoint>>;
@@ -212,13 +229,172 @@ async fn get_valset_at_genesis(
 	Ok(validator_set)
 }
 
+/// Returns authority set change enacted at the justified block.
+/// Change is signaled in the justified header, or in its ancestor linked through the parent hashes.
+/// Headers are ordered, and the last one is the justified header.
+fn enacted_change(
+	headers: &[Header],
+	target_hash: H256,
+	target_number: u32,
+) -> Option<AuthoritySetChange> {
+	let mut hash = target_hash;
+	for header in headers.iter().rev() {
+		if H256::from(header.using_encoded(blake2_256)) != hash {
+			return None;
+		}
+		let changes = authority_set_changes(header);
+		if let Some(change) = changes
+			.into_iter()
+			.find(|change| change.activation == target_number)
+		{
+			return Some(change);
+		}
+		hash = header.parent_hash;
+	}
+	None
+}
+
+/// Jumps between the authority set changes, starting from the first block of the current set.
+/// Validator set and block number are updated after each verified change, so the sync can continue from there.
+async fn warp_sync(
+	client: &impl Client,
+	validator_set: &mut ValidatorSet,
+	block_number: &mut u32,
+	last_block_number: u32,
+) -> Result<()> {
+	while *block_number <= last_block_number {
+		let WrappedProof(FinalityProof {
+			justification,
+			mut unknown_headers,
+			..
+		}) = client
+			.request_finality_proof(*block_number)
+			.await
+			.wrap_err(format!(
+				"Couldn't get finality proof for block no. {block_number}"
+			))?;
+		let justification = justification.0;
+		let target_hash = justification.commit.target_hash;
+		let target_number = justification.commit.target_number;
+		if target_number < *block_number {
+			break;
+		}
+
+		check_finality(validator_set, &justification).context("Warp sync check failed")?;
+
+		// unknown headers can be truncated, in which case only the justified header is fetched
+		let is_target =
+			|header: &Header| H256::from(header.using_encoded(blake2_256)) == target_hash;
+		if !unknown_headers.last().is_some_and(is_target) {
+			let header = client
+				.get_header_by_hash(target_hash)
+				.await
+				.wrap_err(format!("Couldn't get header for {target_hash}"))?;
+			unknown_headers = vec![header];
+		}
+
+		// proof of the current set justifies the latest finalized block instead of the set change
+		let Some(change) = enacted_change(&unknown_headers, target_hash, target_number) else {
+			break;
+		};
+
+		*validator_set = validator_set.next(&change);
+		*block_number = target_number + 1;
+		info!(
+			"Warp synced to validator set {} at block no. {}",
+			validator_set.set_id, block_number
+		);
+		// block after the latest received block is not finalized yet, its header is stored by the block by block sync
+		if *block_number <= last_block_number {
+			store_checkpoint_header(client, *block_number, target_hash).await?;
+		}
+		client.store_checkpoint(FinalitySyncCheckpoint {
+			number: *block_number,
+			set_id: validator_set.set_id,
+			validator_set: validator_set.validator_set.clone(),
+		})?;
+	}
+	Ok(())
+}
+
+/// Stores header of the checkpoint block, which has to be a child of the justified block.
+async fn store_checkpoint_header(
+	client: &impl Client,
+	block_number: u32,
+	parent_hash: H256,
+) -> Result<()> {
+	let hash = client
+		.get_block_hash(block_number)
+		.await
+		.wrap_err(format!("Couldn't get hash for block no. {block_number}"))?;
+	let header = client
+		.get_header_by_hash(hash)
+		.await
+		.wrap_err(format!("Couldn't get header for {hash}"))?;
+	if header.parent_hash != parent_hash {
+		return Err(eyre!(
+			"Header of block no. {block_number} doesn't link to the justified block {parent_hash:?}"
+		));
+	}
+	client.store_block_header(block_number, header)
+}
+
+/// Returns the checkpoint to resume finality sync from.
+/// Trusted checkpoint is used if no checkpoint is stored, or if it is later than the stored one,
+/// and only if its block hash matches the block hash on the chain.
+/// Trusted checkpoint at the genesis block is rejected.
+async fn resolve_checkpoint(
+	client: &impl Client,
+	trusted_checkpoint: Option<TrustedCheckpoint>,
+) -> Result<Option<FinalitySyncCheckpoint>> {
+	let stored = client.get_checkpoint()?;
+	let Some(trusted) = trusted_checkpoint else {
+		return Ok(stored);
+	};
+	// sync resumes from the checkpoint block, verifying it links to its parent
+	if trusted.number == 0 {
+		return Err(eyre!(
+			"Trusted checkpoint cannot be the genesis block, sync from genesis is used without checkpoint"
+		));
+	}
+	if stored
+		.as_ref()
+		.is_some_and(|stored| stored.number >= trusted.number)
+	{
+		return Ok(stored);
+	}
+
+	let hash = client
+		.get_block_hash(trusted.number)
+		.await
+		.wrap_err(format!(
+			"Couldn't get hash for block no. {}",
+			trusted.number
+		))?;
+	if hash != trusted.hash {
+		return Err(eyre!(
+			"Trusted checkpoint hash {:?} doesn't match hash {hash:?} of block no. {}",
+			trusted.hash,
+			trusted.number
+		));
+	}
+
+	info!("Using trusted checkpoint at block no {}", trusted.number);
+	Ok(Some(FinalitySyncCheckpoint {
+		number: trusted.number,
+		set_id: trusted.set_id,
+		validator_set: trusted.validator_set,
+	}))
+}
+
 pub async fn run(
 	client: impl Client,
 	shutdown: Controller<String>,
 	state: Arc<Mutex<State>>,
 	from_header: Header,
+	trusted_checkpoint: Option<TrustedCheckpoint>,
 ) {
-	if let Err(error) = sync(client, state, from_header).await {
+	if let Err(error) = sync(client, state, from_header, trusted_checkpoint).await {
 		error!("Cannot sync finality {error}");
 		let _ = shutdown.trigger_shutdown(format!("Cannot sync finality {error:#}"));
 	};
@@ -228,44 +404,57 @@ pub async fn sync(
 	client: impl Client,
 	state: Arc<Mutex<State>>,
 	mut from_header: Header,
+	trusted_checkpoint: Option<TrustedCheckpoint>,
 ) -> Result<()> {
 	let gen_hash = client.get_genesis_hash().await?;
 
-	let checkpoint = client.get_checkpoint()?;
+	let checkpoint = resolve_checkpoint(&client, trusted_checkpoint).await?;
 
 	info!("Starting finality validation sync.");
-	let mut set_id: u64;
 	let mut curr_block_num = 1u32;
-	let mut validator_set: Vec<ed25519::Public>;
-	if let Some(ch) = checkpoint {
+	let mut validator_set = if let Some(ch) = checkpoint {
 		info!("Continuing from block no {}", ch.number);
-		set_id = ch.set_id;
-		validator_set = ch.validator_set;
 		curr_block_num = ch.number;
+		ValidatorSet {
+			set_id: ch.set_id,
+			validator_set: ch.validator_set,
+		}
 	} else {
 		info!("No checkpoint found, starting from genesis.");
-		validator_set = get_valset_at_genesis(&client, gen_hash).await?;
+		let validator_set = get_valset_at_genesis(&client, gen_hash).await?;
 		// get set_id from genesis. Should be 0.
-		set_id = client
+		let set_id = client
 			.fetch_set_id_at(gen_hash)
 			.await
 			.wrap_err(format!("Couldn't get set_id at {}", gen_hash))?;
 		info!("Set ID at genesis is {set_id}");
-	}
+		ValidatorSet {
+			set_id,
+			validator_set,
+		}
+	};
 
 	let last_block_num = from_header.number;
 
+	info!("Warp syncing finality from {curr_block_num} up to block no. {last_block_num}");
+	let warp = warp_sync(
+		&client,
+		&mut validator_set,
+		&mut curr_block_num,
+		last_block_num,
+	);
+	if let Err(error) = warp.await {
+		warn!("Warp sync failed, syncing block by block: {error:#}");
+	}
+	let mut authority_set = AuthoritySet::new(validator_set);
+
 	info!("Syncing finality from {curr_block_num} up to block no. {last_block_num}");
 
 	let mut prev_hash = client
 		.get_block_hash(curr_block_num - 1)
 		.await
 		.wrap_err("Hash doesn't exist?")?;
-	loop {
-		if curr_block_num == last_block_num + 1 {
-			info!("Finished verifying finality up to block no. {last_block_num}!");
-			break;
-		}
+	while curr_block_num <= last_block_num {
 		let hash = client
 			.get_block_hash(curr_block_num)
 			.await
@@ -285,46 +474,389 @@ pub async fn sync(
 		);
 		prev_hash = from_header.using_encoded(blake2_256).into();
 
-		let next_validator_set = filter_auth_set_changes(&from_header);
-		if next_validator_set.is_empty() {
-			curr_block_num += 1;
-			continue;
+		let enacted = authority_set.enact_changes(curr_block_num);
+		let is_enacted = !enacted.is_empty();
+		for (valset, change) in enacted {
+			// forced changes are enacted without finalizing the activation block
+			if change.forced {
+				warn!(
+					"Forced authority set change enacted without justification at block no. {}",
+					change.activation
+				);
+				continue;
+			}
+
+			// standard change is justified by the validator set it replaces
+			let proof: WrappedProof = client
+				.request_finality_proof(change.activation)
+				.await
+				.wrap_err(format!(
+					"Couldn't get finality proof for block no. {}",
+					change.activation
+				))?;
+			let proof_block_hash = proof.0.block;
+			let p_h = client
+				.get_header_by_hash(proof_block_hash)
+				.await
+				.wrap_err(format!("Couldn't get header for {}", proof_block_hash))?;
+
+			check_finality(&valset, &proof.0.justification.0)
+				.context("Finality sync check failed")?;
+
+			trace!("Proof in block: {}", p_h.number);
 		}
 
-		let proof: WrappedProof = client
-			.request_finality_proof(curr_block_num)
-			.await
+		// sync can resume only from blocks without earlier signaled changes pending
+		if is_enacted && !authority_set.has_pending_changes() {
+			client.store_checkpoint(FinalitySyncCheckpoint {
+				number: curr_block_num,
+				set_id: authority_set.current.set_id,
+				validator_set: authority_set.current.validator_set.clone(),
+			})?;
+		}
+
+		authority_set
+			.schedule_changes(&from_header)
 			.wrap_err(format!(
-				"Couldn't get finality proof for block no. {}",
+				"Couldn't schedule authority set changes at block no. {}",
 				curr_block_num
 			))?;
-		let proof_block_hash = proof.0.block;
-		let p_h = client
-			.get_header_by_hash(proof_block_hash)
+		curr_block_num += 1;
+	}
+	info!("Finished verifying finality up to block no. {last_block_num}!");
+	state.lock().unwrap().finality_synced = true;
+	info!("Finality is fully synced.");
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{enacted_change, resolve_checkpoint, warp_sync, MockClient};
+	use crate::{
+		data::{inspect, mem_db::MemoryDB, Database, FinalitySyncCheckpoint, Key},
+		finality::ValidatorSet,
+		network::rpc::{FinalityProof, WrappedJustification, WrappedProof},
+		types::{
+			Commit, FinalityCheckpoint, GrandpaJustification, Precommit, SignedPrecommit,
+			SignerMessage, TrustedCheckpoint,
+		},
+	};
+	use avail_subxt::{
+		api::runtime_types::avail_core::{
+			data_lookup::compact::CompactDataLookup,
+			header::extension::{v3::HeaderExtension, HeaderExtension::V3},
+			kate_commitment::v3::KateCommitment,
+		},
+		config::substrate::{Digest, DigestItem},
+		primitives::Header,
+	};
+	use codec::Encode;
+	use mockall::predicate::eq;
+	use sp_core::{blake2_256, ed25519, Pair, H256};
+
+	fn header(number: u32, parent_hash: H256, logs: Vec<DigestItem>) -> Header {
+		Header {
+			parent_hash,
+			number,
+			state_root: H256::zero(),
+			extrinsics_root: H256::zero(),
+			digest: Digest { logs },
+			extension: V3(HeaderExtension {
+				commitment: KateCommitment {
+					rows: 1,
+					cols: 4,
+					data_root: H256::zero(),
+					commitment: vec![],
+				},
+				app_lookup: CompactDataLookup {
+					size: 1,
+					index: vec![],
+				},
+			}),
+		}
+	}
+
+	// Scheduled change is encoded as the GRANDPA `ConsensusLog` variant with index 1
+	fn scheduled_change(delay: u32) -> DigestItem {
+		scheduled_change_to([1u8; 32], delay)
+	}
+
+	fn scheduled_change_to(authority: [u8; 32], delay: u32) -> DigestItem {
+		let authorities = vec![(authority, 1u64)];
+		DigestItem::Consensus(*b"FRNK", (1u8, authorities, delay).encode())
+	}
+
+	fn chain(logs: Vec<Vec<DigestItem>>) -> Vec<Header> {
+		let mut parent_hash = H256::zero();
+		let mut headers = vec![];
+		for (number, logs) in (10..).zip(logs) {
+			let header = header(number, parent_hash, logs);
+			parent_hash = header.using_encoded(blake2_256).into();
+			headers.push(header);
+		}
+		headers
+	}
+
+	fn target_hash(headers: &[Header]) -> H256 {
+		headers.last().unwrap().using_encoded(blake2_256).into()
+	}
+
+	#[test]
+	fn enacted_change_in_justified_header() {
+		let headers = chain(vec![vec![], vec![], vec![scheduled_change(0)]]);
+		let change = enacted_change(&headers, target_hash(&headers), 12).unwrap();
+		assert_eq!(change.activation, 12);
+		assert!(!change.forced);
+	}
+
+	#[test]
+	fn enacted_change_in_linked_ancestor() {
+		let headers = chain(vec![vec![scheduled_change(2)], vec![], vec![]]);
+		let change = enacted_change(&headers, target_hash(&headers), 12).unwrap();
+		assert_eq!(change.activation, 12);
+
+		// change which is not enacted at the justified block is ignored
+		let headers = chain(vec![vec![scheduled_change(1)], vec![], vec![]]);
+		assert!(enacted_change(&headers, target_hash(&headers), 12).is_none());
+	}
+
+	#[test]
+	fn enacted_change_in_unlinked_header() {
+		let mut headers = chain(vec![vec![scheduled_change(2)], vec![], vec![]]);
+		let target_hash = target_hash(&headers);
+		headers[1].parent_hash = H256::repeat_byte(1);
+		assert!(enacted_change(&headers, target_hash, 12).is_none());
+		assert!(enacted_change(&headers, H256::repeat_byte(1), 12).is_none());
+	}
+
+	fn trusted_checkpoint(number: u32) -> TrustedCheckpoint {
+		TrustedCheckpoint {
+			number,
+			hash: H256::repeat_byte(1),
+			set_id: 2,
+			validator_set: vec![],
+		}
+	}
+
+	fn stored_checkpoint(number: u32) -> FinalitySyncCheckpoint {
+		FinalitySyncCheckpoint {
+			number,
+			set_id: 1,
+			validator_set: vec![],
+		}
+	}
+
+	#[tokio::test]
+	async fn resolve_later_trusted_checkpoint() {
+		let mut client = MockClient::new();
+		client
+			.expect_get_checkpoint()
+			.returning(|| Ok(Some(stored_checkpoint(5))));
+		client
+			.expect_get_block_hash()
+			.with(eq(10))
+			.returning(|_| Ok(H256::repeat_byte(1)));
+
+		let checkpoint = resolve_checkpoint(&client, Some(trusted_checkpoint(10)))
 			.await
-			.wrap_err(format!("Couldn't get header for {}", proof_block_hash))?;
+			.unwrap()
+			.unwrap();
+		assert_eq!(checkpoint.number, 10);
+		assert_eq!(checkpoint.set_id, 2);
+	}
 
-		let valset = ValidatorSet {
-			set_id,
-			validator_set,
+	#[tokio::test]
+	async fn resolve_later_stored_checkpoint() {
+		let mut client = MockClient::new();
+		client
+			.expect_get_checkpoint()
+			.returning(|| Ok(Some(stored_checkpoint(20))));
+		client.expect_get_block_hash().never();
+
+		let checkpoint = resolve_checkpoint(&client, Some(trusted_checkpoint(10)))
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(checkpoint.number, 20);
+		assert_eq!(checkpoint.set_id, 1);
+	}
+
+	#[tokio::test]
+	async fn resolve_trusted_checkpoint_with_wrong_hash() {
+		let mut client = MockClient::new();
+		client.expect_get_checkpoint().returning(|| Ok(None));
+		client
+			.expect_get_block_hash()
+			.with(eq(10))
+			.returning(|_| Ok(H256::repeat_byte(2)));
+
+		let result = resolve_checkpoint(&client, Some(trusted_checkpoint(10))).await;
+		assert!(result.is_err());
+	}
+
+	#[tokio::test]
+	async fn resolve_genesis_trusted_checkpoint() {
+		let mut client = MockClient::new();
+		client.expect_get_checkpoint().returning(|| Ok(None));
+		client.expect_get_block_hash().never();
+
+		let result = resolve_checkpoint(&client, Some(trusted_checkpoint(0))).await;
+		assert!(result.is_err());
+	}
+
+	#[tokio::test]
+	async fn exported_checkpoint_is_resolved() {
+		let validator = ed25519::Pair::from_seed(&[1u8; 32]);
+		let header = header(5, H256::repeat_byte(1), vec![]);
+		let header_hash = hash(&header);
+
+		// export from the synced node
+		let db = MemoryDB::default();
+		let stored = FinalitySyncCheckpoint {
+			number: 5,
+			set_id: 1,
+			validator_set: vec![validator.public()],
 		};
-		check_finality(&valset, &proof.0.justification.0).context("Finality sync check failed")?;
+		db.put(Key::FinalitySyncCheckpoint, stored).unwrap();
+		db.put(Key::BlockHeader(5), header).unwrap();
+		let exported = inspect::checkpoint(&db).unwrap();
+		assert_eq!(exported.hash, header_hash);
+
+		let path = std::env::temp_dir().join(format!("avail_light_{}.json", uuid::Uuid::new_v4()));
+		std::fs::write(&path, serde_json::to_string_pretty(&exported).unwrap()).unwrap();
+		let configured = FinalityCheckpoint::File(path.to_string_lossy().to_string());
+		let loaded = configured.load().unwrap();
+		std::fs::remove_file(&path).unwrap();
+		assert_eq!(loaded, exported);
+
+		// bootstrap of the new node
+		let mut client = MockClient::new();
+		client.expect_get_checkpoint().returning(|| Ok(None));
+		client
+			.expect_get_block_hash()
+			.with(eq(5))
+			.returning(move |_| Ok(header_hash));
+
+		let checkpoint = resolve_checkpoint(&client, Some(loaded))
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(checkpoint.number, 5);
+		assert_eq!(checkpoint.set_id, 1);
+		assert_eq!(checkpoint.validator_set, vec![validator.public()]);
+	}
 
-		trace!("Proof in block: {}", p_h.number);
-		curr_block_num += 1;
+	fn hash(header: &Header) -> H256 {
+		header.using_encoded(blake2_256).into()
+	}
 
-		validator_set = next_validator_set[0]
-			.iter()
-			.map(|a| ed25519::Public::from_raw(a.0 .0 .0 .0))
-			.collect();
-		set_id += 1;
-		client.store_checkpoint(FinalitySyncCheckpoint {
-			number: curr_block_num,
-			set_id,
-			validator_set: validator_set.clone(),
-		})?;
+	// Finality proof of the last header, signed by the validator of the given set
+	fn finality_proof(
+		signer: &ed25519::Pair,
+		set_id: u64,
+		header: &Header,
+		unknown_headers: Vec<Header>,
+	) -> WrappedProof {
+		let precommit = Precommit {
+			target_hash: hash(header),
+			target_number: header.number,
+		};
+		let round = 1u64;
+		let signed_message = Encode::encode(&(
+			&SignerMessage::PrecommitMessage(precommit.clone()),
+			&round,
+			&set_id,
+		));
+		let justification = GrandpaJustification {
+			round,
+			commit: Commit {
+				target_hash: precommit.target_hash,
+				target_number: precommit.target_number,
+				precommits: vec![SignedPrecommit {
+					precommit: precommit.clone(),
+					signature: signer.sign(&signed_message),
+					id: signer.public(),
+				}],
+			},
+			votes_ancestries: vec![],
+		};
+		WrappedProof(FinalityProof {
+			block: precommit.target_hash,
+			justification: WrappedJustification(justification),
+			unknown_headers,
+		})
+	}
+
+	#[tokio::test]
+	async fn warp_sync_jumps_to_the_latest_set() {
+		let validator_1 = ed25519::Pair::from_seed(&[1u8; 32]);
+		let validator_2 = ed25519::Pair::from_seed(&[2u8; 32]);
+		// change to the validator 2 is signaled at block 10 and enacted at block 12
+		let headers = chain(vec![
+			vec![scheduled_change_to(validator_2.public().0, 2)],
+			vec![],
+			vec![],
+		]);
+		let checkpoint_header = header(13, hash(&headers[2]), vec![]);
+		let latest_header = header(20, H256::repeat_byte(1), vec![]);
+
+		let mut client = MockClient::new();
+		let proof = finality_proof(&validator_1, 1, &headers[2], headers.clone());
+		client
+			.expect_request_finality_proof()
+			.with(eq(10))
+			.times(1)
+			.returning(move |_| Ok(proof.clone()));
+		// proof of the current set justifies the latest block, so warp sync stops
+		let proof = finality_proof(&validator_2, 2, &latest_header, vec![]);
+		client
+			.expect_request_finality_proof()
+			.with(eq(13))
+			.times(1)
+			.returning(move |_| Ok(proof.clone()));
+		let checkpoint_hash = hash(&checkpoint_header);
+		client
+			.expect_get_block_hash()
+			.with(eq(13))
+			.returning(move |_| Ok(checkpoint_hash));
+		client
+			.expect_get_header_by_hash()
+			.with(eq(checkpoint_hash))
+			.returning(move |_| Ok(checkpoint_header.clone()));
+		let latest_hash = hash(&latest_header);
+		client
+			.expect_get_header_by_hash()
+			.with(eq(latest_hash))
+			.times(1)
+			.returning(move |_| Ok(latest_header.clone()));
+		client
+			.expect_store_block_header()
+			.withf(|&block_number, header| block_number == 13 && header.number == 13)
+			.times(1)
+			.returning(|_, _| Ok(()));
+		let validators_2 = vec![validator_2.public()];
+		client
+			.expect_store_checkpoint()
+			.withf(move |checkpoint| {
+				checkpoint.number == 13
+					&& checkpoint.set_id == 2
+					&& checkpoint.validator_set == validators_2
+			})
+			.times(1)
+			.returning(|_| Ok(()));
+
+		let mut validator_set = ValidatorSet {
+			set_id: 1,
+			validator_set: vec![validator_1.public()],
+		};
+		let mut block_number = 10;
+		warp_sync(&client, &mut validator_set, &mut block_number, 20)
+			.await
+			.unwrap();
+
+		// block by block sync continues from the first block of the latest set
+		assert_eq!(block_number, 13);
+		assert_eq!(validator_set.set_id, 2);
+		assert_eq!(validator_set.validator_set, vec![validator_2.public()]);
 	}
-	state.lock().unwrap().finality_synced = true;
-	info!("Finality is fully synced.");
-	Ok(())
 }
@@ -18,6 +18,13 @@ pub enum MetricCounter {
 	ConnectionEstablished,
 	IncomingPutRecord,
 	IncomingGetRecord,
+	NonLinkingHeader,
+	SubscriptionRetry,
+	SubscriptionNodeSwitch,
+	SubscriptionFatal,
+	Equivocation,
+	HeaderQuorumFailed,
+	HeaderQuorumUnavailable,
 }
 
 impl Display for MetricCounter {
@@ -30,6 +37,15 @@ impl Display for MetricCounter {
 			MetricCounter::ConnectionEstablished => write!(f, "established_connections"),
 			MetricCounter::IncomingPutRecord => write!(f, "incoming_put_record_counter"),
 			MetricCounter::IncomingGetRecord => write!(f, "incoming_get_record_counter"),
+			MetricCounter::NonLinkingHeader => write!(f, "non_linking_header_counter"),
+			MetricCounter::SubscriptionRetry => write!(f, "subscription_retry_counter"),
+			MetricCounter::SubscriptionNodeSwitch => write!(f, "subscription_node_switch_counter"),
+			MetricCounter::SubscriptionFatal => write!(f, "subscription_fatal_counter"),
+			MetricCounter::Equivocation => write!(f, "equivocation_counter"),
+			MetricCounter::HeaderQuorumFailed => write!(f, "header_quorum_failed_counter"),
+			MetricCounter::HeaderQuorumUnavailable => {
+				write!(f, "header_quorum_unavailable_counter")
+			},
 		}
 	}
 }
@@ -45,6 +61,13 @@ impl MetricCounter {
 			MetricCounter::ConnectionEstablished,
 			MetricCounter::IncomingPutRecord,
 			MetricCounter::IncomingGetRecord,
+			MetricCounter::NonLinkingHeader,
+			MetricCounter::SubscriptionRetry,
+			MetricCounter::SubscriptionNodeSwitch,
+			MetricCounter::SubscriptionFatal,
+			MetricCounter::Equivocation,
+			MetricCounter::HeaderQuorumFailed,
+			MetricCounter::HeaderQuorumUnavailable,
 		] {
 			counter_map.insert(
 				counter.to_string(),
@@ -73,6 +96,12 @@ pub enum MetricValue {
 	PingLatency(f64),
 	ReplicationFactor(u16),
 	QueryTimeout(u32),
+	PrunedConfidenceFactors(usize),
+	PrunedBlockHeaders(usize),
+	PrunedAppData(usize),
+	RPCNodeLatency(String, f64),
+	RPCNodeErrorRate(String, f64),
+	RPCNodeHeadLag(String, u32),
 	#[cfg(feature = "crawl")]
 	CrawlCellsSuccessRate(f64),
 	#[cfg(feature = "crawl")]
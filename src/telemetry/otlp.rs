@@ -69,6 +69,18 @@ impl Metrics {
 		Ok(())
 	}
 
+	/// Records gauge value of the RPC node, with the node host as an additional attribute
+	async fn record_node_f64(&self, name: &'static str, host: String, value: f64) -> Result<()> {
+		let instrument = self.meter.f64_observable_gauge(name).try_init()?;
+		let mut attributes = self.attributes().await.to_vec();
+		attributes.push(KeyValue::new("host", host));
+		self.meter
+			.register_callback(&[instrument.as_any()], move |observer| {
+				observer.observe_f64(&instrument, value, &attributes)
+			})?;
+		Ok(())
+	}
+
 	async fn set_multiaddress(&self, multiaddr: String) {
 		let mut m = self.attributes.multiaddress.write().await;
 		*m = multiaddr;
@@ -135,6 +147,29 @@ impl super::Metrics for Metrics {
 			super::MetricValue::PingLatency(number) => {
 				self.record_f64("ping_latency", number).await?;
 			},
+			super::MetricValue::PrunedConfidenceFactors(number) => {
+				self.record_u64("pruned_confidence_factors", number as u64)
+					.await?;
+			},
+			super::MetricValue::PrunedBlockHeaders(number) => {
+				self.record_u64("pruned_block_headers", number as u64)
+					.await?;
+			},
+			super::MetricValue::PrunedAppData(number) => {
+				self.record_u64("pruned_app_data", number as u64).await?;
+			},
+			super::MetricValue::RPCNodeLatency(host, latency) => {
+				self.record_node_f64("rpc_node_latency", host, latency)
+					.await?;
+			},
+			super::MetricValue::RPCNodeErrorRate(host, error_rate) => {
+				self.record_node_f64("rpc_node_error_rate", host, error_rate)
+					.await?;
+			},
+			super::MetricValue::RPCNodeHeadLag(host, head_lag) => {
+				self.record_node_f64("rpc_node_head_lag", host, head_lag as f64)
+					.await?;
+			},
 			#[cfg(feature = "crawl")]
 			super::MetricValue::CrawlCellsSuccessRate(number) => {
 				self.record_f64("crawl_cells_success_rate", number).await?;
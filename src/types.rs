@@ -1,12 +1,16 @@
 //! Shared light client structs and enums.
 
+use crate::alerts::AlertSender;
 use crate::network::p2p::MemoryStoreConfig;
-use crate::network::rpc::{Event, Node as RpcNode};
-use crate::utils::{extract_app_lookup, extract_kate};
+use crate::network::rpc::{self, Event, Node as RpcNode, NodesHealth};
+use crate::utils::{
+	calculate_confidence, calculate_matrix_confidence, extract_app_lookup, extract_kate,
+	matrix_cell_count,
+};
 use avail_core::DataLookup;
 use avail_subxt::{primitives::Header as DaHeader, utils::H256};
 use bip39::{Language, Mnemonic, MnemonicType};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use codec::{Decode, Encode};
 use color_eyre::{
 	eyre::{eyre, WrapErr},
@@ -18,9 +22,11 @@ use kate_recovery::{
 };
 use libp2p::kad::Mode as KadMode;
 use libp2p::{Multiaddr, PeerId};
+use rand::Rng;
 use serde::{de::Error, Deserialize, Serialize};
 use sp_core::crypto::Ss58Codec;
 use sp_core::{blake2_256, bytes, ed25519};
+use std::collections::BTreeSet;
 use std::fmt::{self, Display, Formatter};
 use std::fs;
 use std::num::{NonZeroU8, NonZeroUsize};
@@ -28,7 +34,7 @@ use std::ops::Range;
 use std::str::FromStr;
 use std::time::{Duration, Instant};
 use subxt::ext::sp_core::{sr25519::Pair, Pair as _};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 use tokio_retry::strategy::{jitter, ExponentialBackoff, FibonacciBackoff};
 
 const CELL_SIZE: usize = 32;
@@ -49,9 +55,9 @@ pub struct CliOpts {
 	/// Path to the toml identity file
 	#[arg(short, long, value_name = "FILE", default_value = "identity.toml")]
 	pub identity: String,
-	/// AppID for application client
-	#[arg(long, value_name = "app-id")]
-	pub app_id: Option<u32>,
+	/// AppIDs for application client, separated by comma
+	#[arg(long, value_name = "app-id", value_delimiter = ',')]
+	pub app_id: Vec<u32>,
 	/// Testnet or devnet selection
 	#[arg(short, long, value_name = "network")]
 	pub network: Option<Network>,
@@ -79,6 +85,39 @@ pub struct CliOpts {
 	/// ed25519 private key for libp2p keypair generation
 	#[arg(long)]
 	pub private_key: Option<String>,
+	#[command(subcommand)]
+	pub command: Option<Command>,
+}
+
+/// Database inspection commands, light client is not started if command is given
+#[derive(Subcommand)]
+pub enum Command {
+	/// Print stored headers, confidence, app data, sampling reports, justifications and equivocations for the block range as JSON
+	Dump {
+		/// First block of the range
+		#[arg(long)]
+		from: u32,
+		/// Last block of the range
+		#[arg(long)]
+		to: u32,
+	},
+	/// Export stored data into the portable JSON snapshot file
+	Export {
+		#[arg(value_name = "FILE")]
+		path: String,
+	},
+	/// Import stored data from the snapshot file
+	Import {
+		#[arg(value_name = "FILE")]
+		path: String,
+	},
+	/// Print per column family statistics as JSON
+	Stats,
+	/// Export stored finality sync checkpoint into the JSON file, usable as trusted checkpoint
+	ExportCheckpoint {
+		#[arg(value_name = "FILE")]
+		path: String,
+	},
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -107,6 +146,10 @@ pub struct BlockVerified {
 pub struct ClientChannels {
 	pub block_sender: broadcast::Sender<BlockVerified>,
 	pub rpc_event_receiver: broadcast::Receiver<Event>,
+	/// Channel used to hand blocks missed due to the channel lag over to the backfill
+	pub backfill_sender: mpsc::UnboundedSender<Range<u32>>,
+	/// Channel used to raise alerts posted to the configured webhooks
+	pub alert_sender: AlertSender,
 }
 
 impl TryFrom<(DaHeader, Option<f64>)> for BlockVerified {
@@ -173,22 +216,66 @@ impl TryFrom<String> for KademliaMode {
 ///
 /// * `LightClient` - light client is running
 /// * `AppClient` - app client is running alongside the light client
+/// * `AppClients` - app client is running alongside the light client, for multiple applications
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Mode {
 	LightClient,
 	AppClient(u32),
+	AppClients(Vec<u32>),
 }
 
-impl From<Option<u32>> for Mode {
-	fn from(app_id: Option<u32>) -> Self {
-		match app_id {
-			None => Mode::LightClient,
-			Some(0) => Mode::LightClient,
-			Some(app_id) => Mode::AppClient(app_id),
+impl From<&[u32]> for Mode {
+	fn from(app_ids: &[u32]) -> Self {
+		match app_ids {
+			[] => Mode::LightClient,
+			[app_id] => Mode::AppClient(*app_id),
+			app_ids => Mode::AppClients(app_ids.to_vec()),
 		}
 	}
 }
 
+/// Resolves application ID of the data request.
+/// If application ID is not requested, the only configured application is used.
+/// Returns `None` if requested application is not configured, or if request is ambiguous.
+pub fn resolve_app_id(app_ids: &[u32], requested: Option<u32>) -> Option<u32> {
+	match (requested, app_ids) {
+		(Some(app_id), app_ids) => app_ids.contains(&app_id).then_some(app_id),
+		(None, [app_id]) => Some(*app_id),
+		(None, _) => None,
+	}
+}
+
+/// Application IDs can be configured either as a single ID or as a list of IDs.
+/// Application ID 0 is ignored, since it is used to disable application client.
+pub mod app_ids_format {
+	use serde::{self, Deserialize, Deserializer, Serialize, Serializer};
+
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	enum AppIds {
+		Single(u32),
+		List(Vec<u32>),
+	}
+
+	pub fn serialize<S>(app_ids: &[u32], serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		app_ids.serialize(serializer)
+	}
+
+	pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u32>, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let app_ids = match AppIds::deserialize(deserializer)? {
+			AppIds::Single(app_id) => vec![app_id],
+			AppIds::List(app_ids) => app_ids,
+		};
+		Ok(app_ids.into_iter().filter(|&app_id| app_id != 0).collect())
+	}
+}
+
 pub mod block_matrix_partition_format {
 	use kate_recovery::matrix::Partition;
 	use serde::{self, Deserialize, Deserializer, Serializer};
@@ -319,6 +406,139 @@ pub struct FibonacciConfig {
 	pub retries: usize,
 }
 
+/// Avail target block time in seconds, used to estimate number of blocks in time based settings
+const BLOCK_TIME: u32 = 20;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KademliaRecordStore {
+	/// Records are kept in memory and lost on restart
+	Memory,
+	/// Records are persisted in the database column family
+	Disk,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionPolicy {
+	/// Keeps given number of the latest blocks
+	Blocks(u32),
+	/// Keeps blocks from the given number of last days (estimated using target block time)
+	Days(u32),
+}
+
+impl RetentionPolicy {
+	/// Number of the latest blocks to keep
+	pub fn blocks(&self) -> u32 {
+		match self {
+			RetentionPolicy::Blocks(blocks) => *blocks,
+			RetentionPolicy::Days(days) => days.saturating_mul(24 * 60 * 60 / BLOCK_TIME),
+		}
+	}
+
+	/// First block to keep, given the latest block number
+	pub fn first_retained(&self, latest: u32) -> u32 {
+		latest.saturating_add(1).saturating_sub(self.blocks())
+	}
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplingStrategy {
+	/// Cells are sampled using a random seed
+	Random,
+	/// Cells are sampled using a seed derived from the block hash and the given local secret
+	Seeded(String),
+}
+
+// Secret is not printed, since it is logged with the configuration
+impl fmt::Debug for SamplingStrategy {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			SamplingStrategy::Random => write!(f, "Random"),
+			SamplingStrategy::Seeded(_) => write!(f, "Seeded(***)"),
+		}
+	}
+}
+
+impl SamplingStrategy {
+	/// Seed used to generate positions of the sampled block cells
+	pub fn seed(&self, block_hash: H256) -> [u8; 32] {
+		match self {
+			SamplingStrategy::Random => rand::thread_rng().gen(),
+			SamplingStrategy::Seeded(secret) => {
+				blake2_256(&[block_hash.as_bytes(), secret.as_bytes()].concat())
+			},
+		}
+	}
+}
+
+/// Known-good finality checkpoint, finality sync resumes from the block with the given validator set
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TrustedCheckpoint {
+	pub number: u32,
+	pub hash: H256,
+	pub set_id: u64,
+	pub validator_set: Vec<ed25519::Public>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FinalityCheckpoint {
+	/// Checkpoint given in the configuration
+	Inline(TrustedCheckpoint),
+	/// Path to the JSON file with the checkpoint (e.g. exported with the `export-checkpoint` command)
+	File(String),
+}
+
+impl FinalityCheckpoint {
+	/// Returns the configured checkpoint, reading it from the file if needed
+	pub fn load(&self) -> Result<TrustedCheckpoint> {
+		match self {
+			FinalityCheckpoint::Inline(checkpoint) => Ok(checkpoint.clone()),
+			FinalityCheckpoint::File(path) => {
+				let checkpoint = fs::read_to_string(path)
+					.wrap_err(format!("Failed to read finality checkpoint from {path}"))?;
+				serde_json::from_str(&checkpoint)
+					.wrap_err(format!("Failed to parse finality checkpoint from {path}"))
+			},
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfidenceModel {
+	/// Confidence is `1 - 1/2^n` for `n` verified cells, regardless of the block dimensions
+	Legacy,
+	/// Confidence is the probability that at least one of the cells sampled without replacement
+	/// from the extended matrix is withheld, if the withholding threshold fraction of cells is withheld.
+	/// Matrix is extended by the fixed factor of 2, as done by the network.
+	Matrix { withholding_threshold: f64 },
+}
+
+impl ConfidenceModel {
+	/// Number of cells to sample to achieve the given confidence
+	pub fn cell_count(&self, confidence: f64, dimensions: Dimensions) -> u32 {
+		match self {
+			ConfidenceModel::Legacy => rpc::cell_count_for_confidence(confidence),
+			ConfidenceModel::Matrix {
+				withholding_threshold,
+			} => matrix_cell_count(confidence, dimensions, *withholding_threshold),
+		}
+	}
+
+	/// Confidence achieved with the given number of verified cells
+	pub fn confidence(&self, count: u32, dimensions: Dimensions) -> f64 {
+		match self {
+			ConfidenceModel::Legacy => calculate_confidence(count),
+			ConfidenceModel::Matrix {
+				withholding_threshold,
+			} => calculate_matrix_confidence(count, dimensions, *withholding_threshold),
+		}
+	}
+}
+
 /// Representation of a configuration used by this project.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(default)]
@@ -356,10 +576,18 @@ pub struct RuntimeConfig {
 	pub full_node_ws: Vec<String>,
 	/// Genesis hash of the network to be connected to. Set to a string beginning with "DEV" to connect to any network.
 	pub genesis_hash: String,
-	/// ID of application used to start application client. If app_id is not set, or set to 0, application client is not started (default: 0).
-	pub app_id: Option<u32>,
+	/// ID or list of IDs of applications used to start application client. If app_id is not set, or set to 0, application client is not started.
+	/// Data submitted over the API is submitted with the first configured ID (default: 0).
+	/// Data of all configured applications is reconstructed from the same verified blocks.
+	#[serde(rename = "app_id", with = "app_ids_format")]
+	pub app_ids: Vec<u32>,
 	/// Confidence threshold, used to calculate how many cells need to be sampled to achieve desired confidence (default: 92.0).
 	pub confidence: f64,
+	/// Model used to calculate number of sampled cells and achieved confidence, set either to `legacy` or to `matrix` with the withholding threshold.
+	/// Matrix model takes the block dimensions into account, withholding threshold is the fraction of the extended matrix
+	/// which has to be withheld to make the block unavailable, e.g. 0.5 for the 2x erasure extension, must be greater than 0 and less than 1.
+	/// Extension factor is fixed to the 2x row extension used by the network, so it is not configurable (default: legacy).
+	pub confidence_model: ConfidenceModel,
 	/// File system path where RocksDB used by light client, stores its data.
 	pub avail_path: String,
 	/// Log level, default is `INFO`. See `<https://docs.rs/log/0.4.14/log/enum.LevelFilter.html>` for possible log level values. (default: `INFO`).
@@ -377,6 +605,15 @@ pub struct RuntimeConfig {
 	pub query_proof_rpc_parallel_tasks: usize,
 	/// Number of seconds to postpone block processing after block finalized message arrives (default: 0).
 	pub block_processing_delay: Option<u32>,
+	/// Maximum number of blocks sampled concurrently by the light client, must be greater than 0. Blocks are still stored in block order (default: 4).
+	pub max_blocks_in_flight: usize,
+	/// Number of times cells which are not fetched are re-sampled, before block confidence is considered failed (default: 3).
+	pub sampling_retries: u32,
+	/// Number of seconds to wait before the first re-sampling of cells which are not fetched, doubled on each next retry (default: 10).
+	pub sampling_retry_delay: u64,
+	/// Cell sampling strategy, set either to `random` or to `seeded` with the local secret.
+	/// Seed is stored in the block sampling report, so sampled positions can be replayed (default: random).
+	pub sampling_strategy: SamplingStrategy,
 	/// Fraction and number of the block matrix part to fetch (e.g. 2/20 means second 1/20 part of a matrix) (default: None)
 	#[serde(with = "block_matrix_partition_format")]
 	pub block_matrix_partition: Option<Partition>,
@@ -384,6 +621,9 @@ pub struct RuntimeConfig {
 	pub sync_start_block: Option<u32>,
 	/// Enable or disable synchronizing finality. If disabled, finality is assumed to be verified until the starting block at the point the LC is started and is only checked for new blocks. (default: true)
 	pub sync_finality_enable: bool,
+	/// Trusted checkpoint to start synchronizing finality from, set either `inline` or as a JSON `file` path.
+	/// Used if no checkpoint is stored, or if it is later than the stored one (default: None).
+	pub finality_checkpoint: Option<FinalityCheckpoint>,
 	/// Maximum number of cells per request for proof queries (default: 30).
 	pub max_cells_per_rpc: Option<usize>,
 	/// Threshold for the number of cells fetched via DHT for the app client (default: 5000)
@@ -432,6 +672,8 @@ pub struct RuntimeConfig {
 	pub max_kad_record_size: u64,
 	/// The maximum number of provider records for which the local node is the provider. (default: 1024).
 	pub max_kad_provided_keys: u64,
+	/// Kademlia record store, set to `memory` or `disk`. Disk store keeps records in the database, so they are served after restarts (default: memory).
+	pub kad_record_store: KademliaRecordStore,
 	/// Set the configuration based on which the retries will be orchestrated, max duration [in seconds] between retries and number of tries.
 	/// (default:
 	/// fibonacci:
@@ -440,6 +682,33 @@ pub struct RuntimeConfig {
 	///     retries: 6,
 	/// )
 	pub retry_config: RetryConfig,
+	/// Number of seconds between the background health probes of the configured RPC nodes, must be greater than 0 (default: 30).
+	pub rpc_health_probe_interval: u64,
+	/// Maximum number of blocks the node finalized head can be behind the best finalized head of all nodes,
+	/// for the node to be considered healthy. Requests are routed to the healthiest node (default: 5).
+	pub rpc_max_head_lag: u32,
+	/// Number of configured RPC nodes, including the connected one, which have to report the same hash
+	/// of the skipped block header before it is emitted. If not set, quorum mode is disabled (default: None).
+	pub rpc_header_quorum: Option<usize>,
+	/// Retention policy for block confidence factors, set either to `blocks` or `days` to keep.
+	/// If not set, confidence factors are never pruned (default: None).
+	pub confidence_retention: Option<RetentionPolicy>,
+	/// Retention policy for block headers, justifications and equivocations, set either to `blocks` or `days` to keep.
+	/// If not set, block headers are never pruned (default: None).
+	pub block_header_retention: Option<RetentionPolicy>,
+	/// Retention policy for application data, set either to `blocks` or `days` to keep.
+	/// If not set, application data is never pruned (default: None).
+	pub app_data_retention: Option<RetentionPolicy>,
+	/// Sets the database retention pruning interval in blocks, must be greater than 0 (default: 180).
+	pub retention_pruning_interval: u32,
+	/// Webhook URLs to post alerts to, on failed block confidence, repeated RPC node failover,
+	/// finality verification failure, application data reconstruction failure
+	/// and RPC nodes diverging on the skipped block header (default: []).
+	pub alert_webhooks: Vec<String>,
+	/// Number of times failed alert post is retried per webhook (default: 3).
+	pub alert_retries: u32,
+	/// Number of seconds to wait before the first retry of failed alert post, doubled on each next retry (default: 1).
+	pub alert_retry_delay: u64,
 	#[cfg(feature = "crawl")]
 	#[serde(flatten)]
 	pub crawl: crate::crawl_client::CrawlConfig,
@@ -456,7 +725,12 @@ pub struct Delay(pub Option<Duration>);
 /// Light client configuration (see [RuntimeConfig] for details)
 pub struct LightClientConfig {
 	pub confidence: f64,
+	pub confidence_model: ConfidenceModel,
 	pub block_processing_delay: Delay,
+	pub max_blocks_in_flight: NonZeroUsize,
+	pub sampling_retries: u32,
+	pub sampling_retry_delay: Duration,
+	pub sampling_strategy: SamplingStrategy,
 }
 
 impl Delay {
@@ -475,7 +749,14 @@ impl From<&RuntimeConfig> for LightClientConfig {
 
 		LightClientConfig {
 			confidence: val.confidence,
+			confidence_model: val.confidence_model,
 			block_processing_delay: Delay(block_processing_delay),
+			// Zero is rejected on config load
+			max_blocks_in_flight: NonZeroUsize::new(val.max_blocks_in_flight)
+				.unwrap_or(NonZeroUsize::MIN),
+			sampling_retries: val.sampling_retries,
+			sampling_retry_delay: Duration::from_secs(val.sampling_retry_delay),
+			sampling_strategy: val.sampling_strategy.clone(),
 		}
 	}
 }
@@ -599,6 +880,7 @@ pub struct KademliaConfig {
 	pub max_kad_record_size: usize,
 	pub max_kad_provided_keys: usize,
 	pub kademlia_mode: KademliaMode,
+	pub record_store: KademliaRecordStore,
 }
 
 impl From<&RuntimeConfig> for KademliaConfig {
@@ -618,6 +900,7 @@ impl From<&RuntimeConfig> for KademliaConfig {
 			max_kad_record_size: val.max_kad_record_size as usize,
 			max_kad_provided_keys: val.max_kad_provided_keys as usize,
 			kademlia_mode: val.operation_mode,
+			record_store: val.kad_record_store,
 		}
 	}
 }
@@ -719,18 +1002,76 @@ impl From<&RuntimeConfig> for IdentifyConfig {
 #[derive(Clone)]
 pub struct SyncClientConfig {
 	pub confidence: f64,
+	pub confidence_model: ConfidenceModel,
 	pub disable_rpc: bool,
 	pub dht_parallelization_limit: usize,
 	pub is_last_step: bool,
+	pub sampling_strategy: SamplingStrategy,
 }
 
 impl From<&RuntimeConfig> for SyncClientConfig {
 	fn from(val: &RuntimeConfig) -> Self {
 		SyncClientConfig {
 			confidence: val.confidence,
+			confidence_model: val.confidence_model,
 			disable_rpc: val.disable_rpc,
 			dht_parallelization_limit: val.dht_parallelization_limit,
-			is_last_step: val.app_id.is_none(),
+			is_last_step: val.app_ids.is_empty(),
+			sampling_strategy: val.sampling_strategy.clone(),
+		}
+	}
+}
+
+/// Database retention configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Copy)]
+pub struct RetentionConfig {
+	pub confidence: Option<RetentionPolicy>,
+	pub block_header: Option<RetentionPolicy>,
+	pub app_data: Option<RetentionPolicy>,
+	pub pruning_interval: u32,
+}
+
+impl From<&RuntimeConfig> for RetentionConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		RetentionConfig {
+			confidence: val.confidence_retention,
+			block_header: val.block_header_retention,
+			app_data: val.app_data_retention,
+			pruning_interval: val.retention_pruning_interval,
+		}
+	}
+}
+
+/// RPC node health configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Copy)]
+pub struct RpcHealthConfig {
+	pub probe_interval: Duration,
+	pub max_head_lag: u32,
+}
+
+impl From<&RuntimeConfig> for RpcHealthConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		RpcHealthConfig {
+			probe_interval: Duration::from_secs(val.rpc_health_probe_interval),
+			max_head_lag: val.rpc_max_head_lag,
+		}
+	}
+}
+
+/// Alerts configuration (see [RuntimeConfig] for details)
+#[derive(Clone)]
+pub struct AlertsConfig {
+	pub webhooks: Vec<String>,
+	pub retries: u32,
+	pub retry_delay: Duration,
+}
+
+impl From<&RuntimeConfig> for AlertsConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		AlertsConfig {
+			webhooks: val.alert_webhooks.clone(),
+			retries: val.alert_retries,
+			retry_delay: Duration::from_secs(val.alert_retry_delay),
 		}
 	}
 }
@@ -769,8 +1110,9 @@ impl Default for RuntimeConfig {
 			relays: Vec::new(),
 			full_node_ws: vec!["ws://127.0.0.1:9944".to_owned()],
 			genesis_hash: "DEV".to_owned(),
-			app_id: None,
+			app_ids: vec![],
 			confidence: 99.9,
+			confidence_model: ConfidenceModel::Legacy,
 			avail_path: "avail_path".to_owned(),
 			log_level: "INFO".to_owned(),
 			log_format_json: false,
@@ -779,6 +1121,10 @@ impl Default for RuntimeConfig {
 			dht_parallelization_limit: 20,
 			query_proof_rpc_parallel_tasks: 8,
 			block_processing_delay: Some(20),
+			max_blocks_in_flight: 4,
+			sampling_retries: 3,
+			sampling_retry_delay: 10,
+			sampling_strategy: SamplingStrategy::Random,
 			block_matrix_partition: None,
 			sync_start_block: None,
 			sync_finality_enable: false,
@@ -801,6 +1147,7 @@ impl Default for RuntimeConfig {
 			max_kad_record_number: 2400000,
 			max_kad_record_size: 8192,
 			max_kad_provided_keys: 1024,
+			kad_record_store: KademliaRecordStore::Memory,
 			#[cfg(feature = "crawl")]
 			crawl: crate::crawl_client::CrawlConfig::default(),
 			origin: "external".to_string(),
@@ -810,6 +1157,17 @@ impl Default for RuntimeConfig {
 				max_delay: 10,
 				retries: 6,
 			}),
+			rpc_health_probe_interval: 30,
+			rpc_max_head_lag: 5,
+			rpc_header_quorum: None,
+			confidence_retention: None,
+			block_header_retention: None,
+			app_data_retention: None,
+			retention_pruning_interval: 180,
+			finality_checkpoint: None,
+			alert_webhooks: vec![],
+			alert_retries: 3,
+			alert_retry_delay: 1,
 		}
 	}
 }
@@ -902,6 +1260,11 @@ impl RuntimeConfig {
 	/// A range bounded inclusively below and exclusively above
 	pub fn sync_range(&self, end: u32) -> Range<u32> {
 		let start = self.sync_start_block.unwrap_or(end);
+		// Blocks before the confidence retention window would be pruned right away
+		let start = match self.confidence_retention {
+			Some(retention) => start.max(retention.first_retained(end)),
+			None => start,
+		};
 		Range { start, end }
 	}
 
@@ -935,7 +1298,9 @@ impl RuntimeConfig {
 			self.port = port;
 		}
 		self.sync_finality_enable |= opts.finality_sync_enable;
-		self.app_id = opts.app_id.or(self.app_id);
+		if !opts.app_id.is_empty() {
+			self.app_ids = opts.app_id.iter().copied().filter(|&id| id != 0).collect();
+		}
 		self.ws_transport_enable |= opts.ws_transport_enable;
 		if let Some(secret_key) = &opts.private_key {
 			self.secret_key = Some(SecretKey::Key {
@@ -949,6 +1314,33 @@ impl RuntimeConfig {
 			})
 		}
 
+		self.validate()
+	}
+
+	/// Checks that the configured values are usable, so misconfiguration fails on startup.
+	fn validate(&self) -> Result<()> {
+		if self.retention_pruning_interval == 0 {
+			return Err(eyre!("Retention pruning interval must be greater than 0"));
+		}
+		if self.max_blocks_in_flight == 0 {
+			return Err(eyre!(
+				"Maximum number of blocks in flight must be greater than 0"
+			));
+		}
+		if self.rpc_health_probe_interval == 0 {
+			return Err(eyre!("RPC health probe interval must be greater than 0"));
+		}
+		if let ConfidenceModel::Matrix {
+			withholding_threshold,
+		} = self.confidence_model
+		{
+			// also rejects NaN, since comparisons with NaN are false
+			if !(withholding_threshold > 0.0 && withholding_threshold < 1.0) {
+				return Err(eyre!(
+					"Withholding threshold must be greater than 0 and less than 1"
+				));
+			}
+		}
 		Ok(())
 	}
 }
@@ -989,7 +1381,7 @@ impl IdentityConfig {
 	}
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Decode, Encode)]
 pub struct BlockRange {
 	pub first: u32,
 	pub last: u32,
@@ -1017,8 +1409,40 @@ pub struct State {
 	pub sync_header_verified: Option<BlockRange>,
 	pub sync_confidence_achieved: Option<BlockRange>,
 	pub sync_data_verified: Option<BlockRange>,
+	/// Ranges verified in the previous runs, restored on startup.
+	/// Kept apart from the sync ranges, since sync can continue after a gap.
+	pub restored_header_verified: Option<BlockRange>,
+	pub restored_confidence_achieved: Option<BlockRange>,
+	pub restored_data_verified: Option<BlockRange>,
 	pub finality_synced: bool,
 	pub connected_node: RpcNode,
+	/// Health of the configured RPC nodes
+	pub rpc_nodes: NodesHealth,
+	/// Last block with pruned confidence factor
+	pub confidence_pruned: Option<u32>,
+	/// Last block with pruned header
+	pub header_pruned: Option<u32>,
+	/// Last block with pruned application data
+	pub data_pruned: Option<u32>,
+	/// Blocks with unfetched cells, waiting to be re-sampled
+	pub confidence_retrying: BTreeSet<u32>,
+	/// Blocks for which confidence is not achieved after all re-sampling retries
+	pub confidence_failed: BTreeSet<u32>,
+	/// Blocks missed due to the channel lag, waiting to be backfilled
+	pub backfilling: BTreeSet<u32>,
+}
+
+impl State {
+	/// Marks block confidence as achieved.
+	/// Retried and backfilled blocks are behind the latest processed block,
+	/// so the range is extended only if needed.
+	pub fn set_confidence_achieved(&mut self, block_number: u32) {
+		match self.confidence_achieved.as_mut() {
+			Some(range) if range.contains(block_number) => {},
+			Some(range) if block_number < range.first => range.first = block_number,
+			_ => self.confidence_achieved.set(block_number),
+		}
+	}
 }
 
 pub trait OptionBlockRange {
@@ -1057,14 +1481,14 @@ pub enum SignerMessage {
 	PrecommitMessage(Precommit),
 }
 
-#[derive(Clone, Debug, Decode, Encode, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Decode, Encode, Serialize, Deserialize)]
 pub struct Precommit {
 	pub target_hash: H256,
 	/// The target block's number
 	pub target_number: u32,
 }
 
-#[derive(Clone, Debug, Decode, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Decode, Encode, Serialize, Deserialize)]
 pub struct SignedPrecommit {
 	pub precommit: Precommit,
 	/// The signature on the message.
@@ -1072,7 +1496,7 @@ pub struct SignedPrecommit {
 	/// The Id of the signer.
 	pub id: ed25519::Public,
 }
-#[derive(Clone, Debug, Decode, Deserialize)]
+#[derive(Clone, Debug, Decode, Encode, Deserialize)]
 pub struct Commit {
 	pub target_hash: H256,
 	/// The target block's number.
@@ -1081,7 +1505,7 @@ pub struct Commit {
 	pub precommits: Vec<SignedPrecommit>,
 }
 
-#[derive(Clone, Debug, Decode)]
+#[derive(Clone, Debug, Decode, Encode)]
 pub struct GrandpaJustification {
 	pub round: u64,
 	pub commit: Commit,
@@ -1099,6 +1523,16 @@ impl<'de> Deserialize<'de> for GrandpaJustification {
 	}
 }
 
+// Justification is serialized as hex encoded SCALE bytes, the same way it is deserialized
+impl Serialize for GrandpaJustification {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: serde::Serializer,
+	{
+		bytes::serialize(&self.encode(), serializer)
+	}
+}
+
 pub struct TimeToLive(pub Duration);
 
 impl TimeToLive {
@@ -9,13 +9,17 @@ use avail_subxt::{
 		da_control::pallet::Call,
 		da_runtime::RuntimeCall,
 	},
-	primitives::{
-		grandpa::AuthorityId, grandpa::ConsensusLog, AppUncheckedExtrinsic, Header as DaHeader,
-	},
+	primitives::{AppUncheckedExtrinsic, Header as DaHeader},
 	utils::H256,
 };
 use codec::Decode;
 use color_eyre::{eyre::WrapErr, Result};
+use std::ops::Range;
+
+use crate::{
+	data::{Database, Key},
+	types::ConfidenceModel,
+};
 use kate_recovery::{
 	data::Cell,
 	matrix::{Dimensions, Position},
@@ -36,6 +40,79 @@ pub fn calculate_confidence(count: u32) -> f64 {
 	100f64 * (1f64 - 1f64 / 2u32.pow(count) as f64)
 }
 
+/// Number of the extended matrix cells which are withheld given the withholding threshold.
+/// Extended matrix has twice the rows, since the extension factor is fixed by the network.
+fn withheld_cells(dimensions: Dimensions, withholding_threshold: f64) -> u32 {
+	let total = dimensions.extended_size();
+	((total as f64 * withholding_threshold).ceil() as u32).clamp(1, total)
+}
+
+/// Probabilities that none of the first `n` cells, sampled without replacement
+/// from the extended matrix, is withheld (hypergeometric distribution)
+fn miss_probabilities(
+	dimensions: Dimensions,
+	withholding_threshold: f64,
+) -> impl Iterator<Item = f64> {
+	let total = dimensions.extended_size();
+	let available = total - withheld_cells(dimensions, withholding_threshold);
+	(0..total).scan(1f64, move |miss, sampled| {
+		*miss *= available.saturating_sub(sampled) as f64 / (total - sampled) as f64;
+		Some(*miss)
+	})
+}
+
+/// Calculates confidence from given number of verified cells, sampled from the extended matrix
+/// of the given dimensions, if the withholding threshold fraction of the cells is withheld
+pub fn calculate_matrix_confidence(
+	count: u32,
+	dimensions: Dimensions,
+	withholding_threshold: f64,
+) -> f64 {
+	let Some(count) = count.checked_sub(1) else {
+		return 0f64;
+	};
+	let miss = miss_probabilities(dimensions, withholding_threshold)
+		.nth(count as usize)
+		.unwrap_or(0f64);
+	100f64 * (1f64 - miss)
+}
+
+/// Calculates number of cells which need to be sampled from the extended matrix
+/// of the given dimensions to achieve given confidence
+pub fn matrix_cell_count(
+	confidence: f64,
+	dimensions: Dimensions,
+	withholding_threshold: f64,
+) -> u32 {
+	miss_probabilities(dimensions, withholding_threshold)
+		.position(|miss| 100f64 * (1f64 - miss) >= confidence)
+		.map(|index| index as u32 + 1)
+		.unwrap_or_else(|| dimensions.extended_size())
+}
+
+/// Calculates confidence of the stored block using the given model.
+/// Returns `None` if verified cell count is not stored,
+/// or if the block header, needed for the matrix dimensions, is not stored.
+pub fn block_confidence(
+	db: &impl Database,
+	model: ConfidenceModel,
+	block_number: u32,
+) -> Result<Option<f64>> {
+	let Some(count) = db.get::<u32>(Key::VerifiedCellCount(block_number))? else {
+		return Ok(None);
+	};
+
+	if model == ConfidenceModel::Legacy {
+		return Ok(Some(calculate_confidence(count)));
+	}
+
+	let Some(header) = db.get::<DaHeader>(Key::BlockHeader(block_number))? else {
+		return Ok(None);
+	};
+	let (rows, cols, _, _) = extract_kate(&header.extension);
+	Ok(Dimensions::new(rows, cols).map(|dimensions| model.confidence(count, dimensions)))
+}
+
 /// Extract fields from extension header
 pub(crate) fn extract_kate(extension: &HeaderExtension) -> (u16, u16, H256, Vec<u8>) {
 	match &extension {
@@ -68,25 +145,11 @@ pub(crate) fn extract_app_lookup(
 	DataLookup::try_from(compact)
 }
 
-pub fn filter_auth_set_changes(header: &DaHeader) -> Vec<Vec<(AuthorityId, u64)>> {
-	let new_auths = header
-		.digest
-		.logs
-		.iter()
-		.filter_map(|e| match &e {
-			// UGHHH, why won't the b"FRNK" just work
-			avail_subxt::config::substrate::DigestItem::Consensus(
-				[b'F', b'R', b'N', b'K'],
-				data,
-			) => match ConsensusLog::<u32>::decode(&mut data.as_slice()) {
-				Ok(ConsensusLog::ScheduledChange(x)) => Some(x.next_authorities),
-				Ok(ConsensusLog::ForcedChange(_, x)) => Some(x.next_authorities),
-				_ => None,
-			},
-			_ => None,
-		})
-		.collect::<Vec<_>>();
-	new_auths
+/// Returns range of blocks skipped between the last processed block and the received block.
+/// Blocks received out of order, or right after the last processed block, have no gap.
+pub fn block_gap(last_processed: Option<u32>, block_number: u32) -> Option<Range<u32>> {
+	let gap = last_processed?.checked_add(1)?..block_number;
+	(!gap.is_empty()).then_some(gap)
 }
 
 // TODO: Remove unused functions if not needed after next iteration
@@ -113,11 +176,20 @@ fn diff_positions(positions: &[Position], cells: &[Cell]) -> Vec<Position> {
 
 #[cfg(test)]
 mod tests {
-	use super::{can_reconstruct, diff_positions};
+	use super::{
+		block_confidence, block_gap, calculate_confidence, calculate_matrix_confidence,
+		can_reconstruct, diff_positions, matrix_cell_count,
+	};
+	use crate::{
+		data::{mem_db::MemoryDB, Database, Key},
+		types::ConfidenceModel,
+	};
 	use kate_recovery::{
 		data::Cell,
 		matrix::{Dimensions, Position},
 	};
+	use std::ops::Range;
+	use test_case::test_case;
 
 	fn position(row: u32, col: u16) -> Position {
 		Position { row, col }
@@ -130,6 +202,60 @@ mod tests {
 		}
 	}
 
+	#[test_case(1 => 50.0 ; "One cell")]
+	#[test_case(2 => 100.0 * (1.0 - 0.5 * (3.0 / 7.0)) ; "Two cells")]
+	#[test_case(5 => 100.0 ; "More cells than available")]
+	#[test_case(100 => 100.0 ; "More cells than matrix size")]
+	#[test_case(0 => 0.0 ; "No cells")]
+	fn test_calculate_matrix_confidence(count: u32) -> f64 {
+		calculate_matrix_confidence(count, Dimensions::new(1, 4).unwrap(), 0.5)
+	}
+
+	#[test_case(1, 4, 50.0 => 1)]
+	#[test_case(1, 4, 99.0 => 5)]
+	#[test_case(1, 4, 100.0 => 5)]
+	#[test_case(256, 256, 99.9 => 10)]
+	#[test_case(256, 256, 50.0 => 1)]
+	fn test_matrix_cell_count(rows: u16, cols: u16, confidence: f64) -> u32 {
+		matrix_cell_count(confidence, Dimensions::new(rows, cols).unwrap(), 0.5)
+	}
+
+	#[test_case(None, 5 => None ; "No processed blocks")]
+	#[test_case(Some(4), 5 => None ; "Next block")]
+	#[test_case(Some(4), 8 => Some(5..8) ; "Skipped blocks")]
+	#[test_case(Some(8), 5 => None ; "Out of order block")]
+	#[test_case(Some(u32::MAX), 5 => None ; "Overflow")]
+	fn test_block_gap(last_processed: Option<u32>, block_number: u32) -> Option<Range<u32>> {
+		block_gap(last_processed, block_number)
+	}
+
+	#[test]
+	fn test_matrix_confidence_exceeds_legacy() {
+		let dimensions = Dimensions::new(16, 32).unwrap();
+		for count in 1..=14 {
+			let confidence = calculate_matrix_confidence(count, dimensions, 0.5);
+			assert!(confidence >= calculate_confidence(count));
+		}
+	}
+
+	#[test]
+	fn test_block_confidence() {
+		let db = MemoryDB::default();
+		let matrix = ConfidenceModel::Matrix {
+			withholding_threshold: 0.5,
+		};
+		assert_eq!(
+			block_confidence(&db, ConfidenceModel::Legacy, 1).unwrap(),
+			None
+		);
+
+		db.put(Key::VerifiedCellCount(1), 1u32).unwrap();
+		let confidence = block_confidence(&db, ConfidenceModel::Legacy, 1).unwrap();
+		assert_eq!(confidence, Some(50.0));
+		// Matrix dimensions are not known without the block header
+		assert_eq!(block_confidence(&db, matrix, 1).unwrap(), None);
+	}
+
 	#[test]
 	fn test_can_reconstruct() {
 		let dimensions = Dimensions::new(1, 4).unwrap();